@@ -2,7 +2,7 @@ use parser::ast::{ASTElm, ASTNode};
 use parser::md_parser::md_parse;
 
 fn main() {
-    let mut node = ASTNode::new(ASTElm {
+    let node = ASTNode::new(ASTElm {
         ..Default::default()
     });
     /*
@@ -18,7 +18,7 @@ fn main() {
     level*
     ", node);
     */
-    node = md_parse(
+    let document = md_parse(
         "\
 # headering
 #kigouwotukattemasu
@@ -29,8 +29,8 @@ paragraph(kakko)
 
         node,
     );
-    println!("{:?}", node);
-    println!("{:?}", node.render_debug_format());
-    let content = serde_json::to_string_pretty(&node).unwrap();
+    println!("{:?}", document);
+    println!("{:?}", document.render_debug_format());
+    let content = serde_json::to_string_pretty(&document).unwrap();
     println!("{}", content);
 }
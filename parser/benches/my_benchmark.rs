@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate criterion;
 
+use parser::arena::ASTArena;
 use parser::ast::{ASTElm, ASTNode};
 use parser::md_parser::md_parse;
 
@@ -15,17 +16,17 @@ fn fibonacci(n: u64) -> u64 {
     }
 }
 
-fn parse_markdown(value: &str) -> ASTNode {
-    let mut node = ASTNode::new(ASTElm {
+fn parse_markdown(value: &str) -> ASTArena {
+    let node = ASTNode::new(ASTElm {
         ..Default::default()
     });
     md_parse(value, node)
 }
 
-fn n_parse_markdown(n: u64) -> ASTNode {
+fn n_parse_markdown(n: u64) -> ASTArena {
     let mut value = "".to_string();
-    for i in 1..n {
-        value = value + "# big markdown\n*emphasis*\nparagraph\n\n";
+    for _ in 1..n {
+        value += "# big markdown\n*emphasis*\nparagraph\n\n";
     }
     parse_markdown(&value)
 }
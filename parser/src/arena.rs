@@ -0,0 +1,741 @@
+use crate::ast::*;
+use serde::{Serialize, Serializer};
+
+/*
+ * 文書の木 (配列に並べた木)
+ *
+ * md_parse, org_parse などは文書を ASTArena で返す。要素は NodeId で引き、親・兄弟へのリンクを持つ。
+ * パーサの各規則 (nom のコンビネータ) は部分木を ASTNode で組み立て、文書に加えるときにこの配列へ移す。
+ * 文書を組み立てた後の処理 (節への入れ子、リンクの解決など) と、変換・描画・Visitor はすべてこの木を読み書きする。
+ * 作った直後は前順 (文書の順) に並ぶ。後から加えた要素は配列の末尾に置き、切り離した要素も配列に残る。
+ * Rc / RefCell を持たないので、パース済みの文書をスレッドをまたいで共有できる (Send + Sync)。
+ *
+ *   let document = org_parse(source, ASTNode::new(ASTElm::new_document()));
+ *   for node in document.root_node().descendants() {
+ *       let depth = node.ancestors().count();
+ *   }
+ *
+ * JSON にすると根から入れ子にした形 (ASTNode と同じ形) になる。
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaNode {
+    data: ASTElm,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+impl ArenaNode {
+    fn new(data: ASTElm) -> Self {
+        ArenaNode {
+            data,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+        }
+    }
+
+    pub fn data(&self) -> &ASTElm {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut ASTElm {
+        &mut self.data
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn first_child(&self) -> Option<NodeId> {
+        self.first_child
+    }
+
+    pub fn last_child(&self) -> Option<NodeId> {
+        self.last_child
+    }
+
+    pub fn previous_sibling(&self) -> Option<NodeId> {
+        self.previous_sibling
+    }
+
+    pub fn next_sibling(&self) -> Option<NodeId> {
+        self.next_sibling
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ASTArena {
+    nodes: Vec<ArenaNode>,
+}
+
+impl ASTArena {
+    pub fn new(root: ASTNode) -> Self {
+        let mut arena = ASTArena { nodes: vec![] };
+        let (data, children) = root.into_parts();
+        let root = arena.new_node(data);
+        arena.extend(root, children);
+        arena
+    }
+
+    // どこにも繋がっていない要素を加える (append_child, insert_child_before で木に入れる)
+    pub fn new_node(&mut self, data: ASTElm) -> NodeId {
+        self.nodes.push(ArenaNode::new(data));
+        NodeId(self.nodes.len() - 1)
+    }
+
+    // 子孫を前順に並べる
+    fn extend(&mut self, parent: NodeId, children: Vec<ASTNode>) {
+        for child in children {
+            self.append(parent, child);
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> NodeRef<'_> {
+        NodeRef { arena: self, id }
+    }
+
+    pub fn root_node(&self) -> NodeRef<'_> {
+        self.node(self.root())
+    }
+
+    pub fn get(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> &mut ArenaNode {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn data_mut(&mut self, id: NodeId) -> &mut ASTElm {
+        &mut self.nodes[id.0].data
+    }
+
+    pub fn children(&self, id: NodeId) -> Children<'_> {
+        Children { arena: self, next: self.get(id).first_child }
+    }
+
+    // 親から根まで (自身は含まない)
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors { arena: self, next: self.get(id).parent }
+    }
+
+    // id と子孫 (前順: 親が先)
+    pub fn pre_order(&self, id: NodeId) -> PreOrder<'_> {
+        PreOrder { arena: self, root: id, next: Some(id) }
+    }
+
+    // id と子孫 (後順: 子が先)
+    pub fn post_order(&self, id: NodeId) -> PostOrder<'_> {
+        PostOrder { arena: self, root: id, next: Some(self.first_leaf(id)) }
+    }
+
+    // 子孫 (前順, 自身は含まない)
+    pub fn descendants(&self, id: NodeId) -> std::iter::Skip<PreOrder<'_>> {
+        self.pre_order(id).skip(1)
+    }
+
+    // 最初の子を辿った先の葉
+    fn first_leaf(&self, mut id: NodeId) -> NodeId {
+        while let Some(child) = self.get(id).first_child {
+            id = child;
+        }
+        id
+    }
+
+    // parent の最後の子として木を加える
+    pub fn append(&mut self, parent: NodeId, node: ASTNode) -> NodeId {
+        let (data, children) = node.into_parts();
+        let id = self.new_node(data);
+        self.append_child(parent, id);
+        self.extend(id, children);
+        id
+    }
+
+    // sibling の前に木を加える
+    pub fn insert_before(&mut self, sibling: NodeId, node: ASTNode) -> NodeId {
+        let (data, children) = node.into_parts();
+        let id = self.new_node(data);
+        self.insert_child_before(sibling, id);
+        self.extend(id, children);
+        id
+    }
+
+    // 要素 (と子孫) を parent の最後の子に移す
+    pub fn append_child(&mut self, parent: NodeId, id: NodeId) {
+        self.detach(id);
+        let last = self.nodes[parent.0].last_child;
+        match last {
+            Some(last) => self.nodes[last.0].next_sibling = Some(id),
+            None => self.nodes[parent.0].first_child = Some(id),
+        }
+        self.nodes[parent.0].last_child = Some(id);
+        let node = &mut self.nodes[id.0];
+        node.parent = Some(parent);
+        node.previous_sibling = last;
+    }
+
+    // 要素 (と子孫) を sibling の前に移す
+    pub fn insert_child_before(&mut self, sibling: NodeId, id: NodeId) {
+        self.detach(id);
+        let (parent, previous) = (self.nodes[sibling.0].parent, self.nodes[sibling.0].previous_sibling);
+        match previous {
+            Some(previous) => self.nodes[previous.0].next_sibling = Some(id),
+            None => {
+                if let Some(parent) = parent {
+                    self.nodes[parent.0].first_child = Some(id);
+                }
+            }
+        }
+        self.nodes[sibling.0].previous_sibling = Some(id);
+        let node = &mut self.nodes[id.0];
+        node.parent = parent;
+        node.previous_sibling = previous;
+        node.next_sibling = Some(sibling);
+    }
+
+    // 親から切り離す (要素は配列に残るので、NodeId はそのまま使える)
+    pub fn detach(&mut self, id: NodeId) {
+        let node = &self.nodes[id.0];
+        let (parent, previous, next) = (node.parent, node.previous_sibling, node.next_sibling);
+        match previous {
+            Some(previous) => self.nodes[previous.0].next_sibling = next,
+            None => {
+                if let Some(parent) = parent {
+                    self.nodes[parent.0].first_child = next;
+                }
+            }
+        }
+        match next {
+            Some(next) => self.nodes[next.0].previous_sibling = previous,
+            None => {
+                if let Some(parent) = parent {
+                    self.nodes[parent.0].last_child = previous;
+                }
+            }
+        }
+        let node = &mut self.nodes[id.0];
+        node.parent = None;
+        node.previous_sibling = None;
+        node.next_sibling = None;
+    }
+
+    // 子をすべて切り離して返す (木の組み換え用)
+    pub fn take_children(&mut self, id: NodeId) -> Vec<NodeId> {
+        let children: Vec<NodeId> = self.children(id).collect();
+        for &child in &children {
+            self.detach(child);
+        }
+        children
+    }
+
+    /*
+     * 別の文書の根の子を parent の最後の子に移す (#+INCLUDE: で読み込んだ文書を加える用)
+     * 要素は複製せずに配列の末尾へ移し、NodeId を振り直す。移した子の NodeId を返す
+     */
+    pub fn graft(&mut self, parent: NodeId, other: ASTArena) -> Vec<NodeId> {
+        let offset = self.nodes.len();
+        let shift = |id: Option<NodeId>| id.map(|id| NodeId(id.0 + offset));
+        let other_root = NodeId(other.root().0 + offset);
+        for node in other.nodes {
+            self.nodes.push(ArenaNode {
+                data: node.data,
+                parent: shift(node.parent),
+                first_child: shift(node.first_child),
+                last_child: shift(node.last_child),
+                previous_sibling: shift(node.previous_sibling),
+                next_sibling: shift(node.next_sibling),
+            });
+        }
+        let children = self.take_children(other_root);
+        for &child in &children {
+            self.append_child(parent, child);
+        }
+        children
+    }
+
+    // id から下を ASTNode に組み立て直す (部分木を別の木に移す用)
+    pub fn to_node(&self, id: NodeId) -> ASTNode {
+        let mut node = ASTNode::new(self.get(id).data.clone());
+        for child in self.children(id) {
+            node.append_node(self.to_node(child));
+        }
+        node
+    }
+
+    // 要素を書き換える (子は捨てる。前の空白などはそのまま, lossless.rs)
+    pub fn replace_source(&mut self, id: NodeId, text: &str) {
+        self.take_children(id);
+        let trivia = self.data_mut(id).trivia.get_or_insert_with(Default::default);
+        trivia.text = text.to_string();
+        trivia.trailing.clear();
+    }
+
+    pub fn to_source(&self) -> String {
+        self.root_node().to_source()
+    }
+
+    pub fn render_debug_format(&self) -> String {
+        self.root_node().render_debug_format()
+    }
+}
+
+impl From<ASTNode> for ASTArena {
+    fn from(root: ASTNode) -> Self {
+        ASTArena::new(root)
+    }
+}
+
+impl Serialize for ASTArena {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.root_node().serialize(serializer)
+    }
+}
+
+/*
+ * 木の中の一つの要素 (読み出し用)
+ * ASTNode と同じ名前の読み出しに加えて、親・兄弟・祖先を辿れる。
+ */
+#[derive(Clone, Copy)]
+pub struct NodeRef<'a> {
+    arena: &'a ASTArena,
+    id: NodeId,
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn id(self) -> NodeId {
+        self.id
+    }
+
+    pub fn arena(self) -> &'a ASTArena {
+        self.arena
+    }
+
+    pub fn data(self) -> &'a ASTElm {
+        &self.arena.get(self.id).data
+    }
+
+    pub fn node_type(self) -> &'a ASTType {
+        &self.data().elm_type
+    }
+
+    pub fn meta(self) -> &'a ASTMetaData {
+        &self.data().elm_meta
+    }
+
+    pub fn value(self) -> &'a String {
+        &self.data().value
+    }
+
+    pub fn raw_value(self) -> &'a String {
+        &self.data().raw_value
+    }
+
+    pub fn range(self) -> &'a ASTRange {
+        &self.data().range
+    }
+
+    pub fn affiliated(self) -> Option<&'a AffiliatedMeta> {
+        self.data().affiliated.as_ref()
+    }
+
+    pub fn trivia(self) -> Option<&'a Trivia> {
+        self.data().trivia.as_ref()
+    }
+
+    pub fn source_file(self) -> Option<&'a String> {
+        self.data().source_file.as_ref()
+    }
+
+    pub fn heading_level(self) -> Option<usize> {
+        self.meta().heading_level()
+    }
+
+    fn wrap(self, id: Option<NodeId>) -> Option<NodeRef<'a>> {
+        id.map(|id| self.arena.node(id))
+    }
+
+    pub fn parent(self) -> Option<NodeRef<'a>> {
+        self.wrap(self.arena.get(self.id).parent)
+    }
+
+    pub fn first_child(self) -> Option<NodeRef<'a>> {
+        self.wrap(self.arena.get(self.id).first_child)
+    }
+
+    pub fn last_child(self) -> Option<NodeRef<'a>> {
+        self.wrap(self.arena.get(self.id).last_child)
+    }
+
+    pub fn previous_sibling(self) -> Option<NodeRef<'a>> {
+        self.wrap(self.arena.get(self.id).previous_sibling)
+    }
+
+    pub fn next_sibling(self) -> Option<NodeRef<'a>> {
+        self.wrap(self.arena.get(self.id).next_sibling)
+    }
+
+    pub fn has_children(self) -> bool {
+        self.arena.get(self.id).first_child.is_some()
+    }
+
+    pub fn children(self) -> Nodes<'a, Children<'a>> {
+        Nodes { arena: self.arena, ids: self.arena.children(self.id) }
+    }
+
+    // 親から根まで (自身は含まない)
+    pub fn ancestors(self) -> Nodes<'a, Ancestors<'a>> {
+        Nodes { arena: self.arena, ids: self.arena.ancestors(self.id) }
+    }
+
+    // 自身と子孫 (前順: 親が先)
+    pub fn pre_order(self) -> Nodes<'a, PreOrder<'a>> {
+        Nodes { arena: self.arena, ids: self.arena.pre_order(self.id) }
+    }
+
+    // 自身と子孫 (後順: 子が先)
+    pub fn post_order(self) -> Nodes<'a, PostOrder<'a>> {
+        Nodes { arena: self.arena, ids: self.arena.post_order(self.id) }
+    }
+
+    // 子孫 (前順, 自身は含まない)
+    pub fn descendants(self) -> Nodes<'a, std::iter::Skip<PreOrder<'a>>> {
+        Nodes { arena: self.arena, ids: self.arena.descendants(self.id) }
+    }
+
+    pub fn to_node(self) -> ASTNode {
+        self.arena.to_node(self.id)
+    }
+
+    /*
+     * 具象構文モード (lossless::attach_trivia) の木から元の文字列を組み立てる
+     * 自身の leading は含まない。Trivia を持たない要素は raw_value を使う
+     */
+    pub fn to_source(self) -> String {
+        if !self.has_children() {
+            return match self.trivia() {
+                Some(trivia) => trivia.text.clone(),
+                None => self.raw_value().clone(),
+            };
+        }
+        let mut source = String::new();
+        for child in self.children() {
+            if let Some(trivia) = child.trivia() {
+                source += &trivia.leading;
+            }
+            source += &child.to_source();
+        }
+        if let Some(trivia) = self.trivia() {
+            source += &trivia.trailing;
+        }
+        source
+    }
+
+    // デバッグ用 (テストで木の形を比べる用, ASTNode に組み立て直して描画する)
+    pub fn render_debug_format(self) -> String {
+        self.to_node().render_debug_format()
+    }
+}
+
+impl PartialEq for NodeRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.arena, other.arena) && self.id == other.id
+    }
+}
+
+impl std::fmt::Debug for NodeRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeRef").field("id", &self.id).field("data", self.data()).finish()
+    }
+}
+
+// ASTNode と同じ形: { (ASTElm の項目), "children": [...] }
+#[derive(Serialize)]
+struct Nested<'a> {
+    #[serde(flatten)]
+    data: &'a ASTElm,
+    children: NestedChildren<'a>,
+}
+
+struct NestedChildren<'a>(NodeRef<'a>);
+
+impl Serialize for NestedChildren<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.children())
+    }
+}
+
+impl Serialize for NodeRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Nested { data: self.data(), children: NestedChildren(*self) }.serialize(serializer)
+    }
+}
+
+/*
+ * 木の中の一つの要素 (書き換え用, VisitorMut に渡す)
+ * 値は data_mut で書き換え、子の付け替えは arena() から行う。
+ */
+pub struct NodeMut<'a> {
+    arena: &'a mut ASTArena,
+    id: NodeId,
+}
+
+impl<'a> NodeMut<'a> {
+    pub fn new(arena: &'a mut ASTArena, id: NodeId) -> Self {
+        NodeMut { arena, id }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn node(&self) -> NodeRef<'_> {
+        self.arena.node(self.id)
+    }
+
+    pub fn arena(&mut self) -> &mut ASTArena {
+        self.arena
+    }
+
+    pub fn data_mut(&mut self) -> &mut ASTElm {
+        self.arena.data_mut(self.id)
+    }
+
+    pub fn node_type(&self) -> &ASTType {
+        self.node().node_type()
+    }
+
+    pub fn value(&self) -> &String {
+        self.node().value()
+    }
+
+    pub fn set_value(&mut self, v: String) {
+        self.data_mut().value = v;
+    }
+}
+
+// NodeId を NodeRef にして返す
+pub struct Nodes<'a, I> {
+    arena: &'a ASTArena,
+    ids: I,
+}
+
+impl<'a, I: Iterator<Item = NodeId>> Iterator for Nodes<'a, I> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<NodeRef<'a>> {
+        self.ids.next().map(|id| self.arena.node(id))
+    }
+}
+
+pub struct Children<'a> {
+    arena: &'a ASTArena,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Children<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.arena.get(id).next_sibling;
+        Some(id)
+    }
+}
+
+pub struct Ancestors<'a> {
+    arena: &'a ASTArena,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.arena.get(id).parent;
+        Some(id)
+    }
+}
+
+pub struct PreOrder<'a> {
+    arena: &'a ASTArena,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for PreOrder<'_> {
+    type Item = NodeId;
+
+    // 子があれば最初の子、なければ自身か祖先の次の兄弟 (root の外には出ない)
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.arena.get(id).first_child;
+        let mut current = id;
+        while self.next.is_none() && current != self.root {
+            let node = self.arena.get(current);
+            self.next = node.next_sibling;
+            match node.parent {
+                Some(parent) => current = parent,
+                None => break,
+            }
+        }
+        Some(id)
+    }
+}
+
+pub struct PostOrder<'a> {
+    arena: &'a ASTArena,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for PostOrder<'_> {
+    type Item = NodeId;
+
+    // 次の兄弟があればその最初の葉、なければ親
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        let node = self.arena.get(id);
+        self.next = match node.next_sibling {
+            _ if id == self.root => None,
+            Some(sibling) => Some(self.arena.first_leaf(sibling)),
+            None => node.parent,
+        };
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md_parser::md_parse;
+    use std::sync::Arc;
+
+    const SRC: &str = "# Title\n\nSome *emphasis* text";
+
+    fn types(arena: &ASTArena, id: NodeId) -> Vec<ASTType> {
+        arena.children(id).map(|child| arena.get(child).data().elm_type.clone()).collect()
+    }
+
+    #[test]
+    fn test_arena_links() {
+        let arena = md_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let root = arena.root();
+        assert_eq!(types(&arena, root), vec![ASTType::Headers, ASTType::Paragraph]);
+
+        // 前順に並ぶ
+        let paragraph = arena.get(root).last_child().unwrap();
+        assert_eq!(paragraph.index(), 3);
+        assert_eq!(types(&arena, paragraph), vec![ASTType::Text, ASTType::Emphasis, ASTType::Text]);
+        let emphasis = arena.children(paragraph).nth(1).unwrap();
+        assert_eq!(arena.get(emphasis).parent(), Some(paragraph));
+        assert_eq!(arena.get(emphasis).previous_sibling(), arena.get(paragraph).first_child());
+        assert_eq!(arena.get(emphasis).next_sibling(), arena.get(paragraph).last_child());
+
+        let range = arena.get(emphasis).data().range;
+        assert_eq!(range, ASTRange::new(ASTPos::new(3, 6, 14), ASTPos::new(3, 16, 24)));
+        assert_eq!(ASTArena::new(arena.to_node(root)), arena);
+    }
+
+    #[test]
+    fn test_node_ref() {
+        let arena = md_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let emphasis = arena.root_node().descendants().find(|node| *node.node_type() == ASTType::Emphasis).unwrap();
+        let ancestors: Vec<ASTType> = emphasis.ancestors().map(|node| node.node_type().clone()).collect();
+        assert_eq!(ancestors, vec![ASTType::Paragraph, ASTType::Document]);
+        assert_eq!(emphasis.parent().unwrap().first_child().unwrap().value(), "Some ");
+        assert_eq!(emphasis.next_sibling().unwrap().value(), " text");
+        assert_eq!(emphasis.previous_sibling().unwrap().next_sibling(), Some(emphasis));
+        assert_eq!(emphasis.render_debug_format(), "<emphasis><text>emphasis</text></emphasis>");
+    }
+
+    #[test]
+    fn test_arena_traversal() {
+        let arena = md_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let root = arena.root();
+        let order = |ids: Vec<NodeId>| ids.iter().map(|id| id.index()).collect::<Vec<_>>();
+        assert_eq!(order(arena.pre_order(root).collect()), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(order(arena.post_order(root).collect()), vec![2, 1, 4, 6, 5, 7, 3, 0]);
+
+        // 途中の要素から辿ると、その外には出ない
+        let paragraph = NodeId(3);
+        assert_eq!(order(arena.descendants(paragraph).collect()), vec![4, 5, 6, 7]);
+        assert_eq!(order(arena.post_order(paragraph).collect()), vec![4, 6, 5, 7, 3]);
+        assert_eq!(order(arena.ancestors(NodeId(6)).collect()), vec![5, 3, 0]);
+        assert_eq!(arena.ancestors(root).count(), 0);
+    }
+
+    #[test]
+    fn test_arena_edit() {
+        let mut arena = md_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let root = arena.root();
+        let header = arena.get(root).first_child().unwrap();
+        arena.detach(header);
+        assert_eq!(arena.get(header).parent(), None);
+        assert_eq!(types(&arena, root), vec![ASTType::Paragraph]);
+
+        let mut node = ASTNode::new(ASTElm::new_paragraph("", "", ASTRange::default()));
+        node.append(ASTElm::new_text("end", ASTRange::default()));
+        let paragraph = arena.append(root, node);
+        assert_eq!(types(&arena, root), vec![ASTType::Paragraph, ASTType::Paragraph]);
+        assert_eq!(arena.node(paragraph).render_debug_format(), "<paragraph><text>end</text></paragraph>");
+
+        // 切り離した要素は別の位置に戻せる
+        arena.insert_child_before(paragraph, header);
+        assert_eq!(types(&arena, root), vec![ASTType::Paragraph, ASTType::Headers, ASTType::Paragraph]);
+        arena.insert_before(header, ASTNode::new(ASTElm::new_text("first", ASTRange::default())));
+        assert_eq!(arena.node(header).previous_sibling().unwrap().value(), "first");
+        assert_eq!(arena.take_children(paragraph).len(), 1);
+        assert!(!arena.node(paragraph).has_children());
+    }
+
+    #[test]
+    fn test_arena_graft() {
+        let mut arena = md_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let other = md_parse("*a* b\n\n# c", ASTNode::new(ASTElm::new_document()));
+        let root = arena.root();
+        let grafted = arena.graft(root, other);
+        assert_eq!(grafted.len(), 2);
+        assert_eq!(
+            types(&arena, root),
+            vec![ASTType::Headers, ASTType::Paragraph, ASTType::Paragraph, ASTType::Headers]
+        );
+        assert_eq!(arena.node(grafted[0]).parent(), Some(arena.root_node()));
+        let emphasis = arena.node(grafted[0]).first_child().unwrap();
+        assert_eq!(emphasis.render_debug_format(), "<emphasis><text>a</text></emphasis>");
+    }
+
+    #[test]
+    fn test_arena_serialize() {
+        // JSON は入れ子の ASTNode と同じ形
+        let arena = md_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let document = arena.to_node(arena.root());
+        assert_eq!(serde_json::to_string(&arena).unwrap(), serde_json::to_string(&document).unwrap());
+    }
+
+    #[test]
+    fn test_arena_shared_across_threads() {
+        let arena = Arc::new(md_parse(SRC, ASTNode::new(ASTElm::new_document())));
+        let shared = Arc::clone(&arena);
+        let count = std::thread::spawn(move || shared.children(shared.root()).count()).join().unwrap();
+        assert_eq!(count, 2);
+    }
+}
@@ -1,77 +1,63 @@
 use serde::Serialize;
-use std::cell::{RefCell};
-use std::cell::RefMut;
-use std::rc::Rc;
+use std::collections::BTreeMap;
 
-type Link = Rc<RefCell<ASTNode>>;
-type Position = RefCell<u32>;
-
-#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
 pub struct ASTPos {
-    line: Position,
-    ch: Position,
-    pos: Position,
+    line: u32,
+    ch: u32,
+    pos: u32,
 }
 
 impl ASTPos {
     pub fn new(line: u32, ch: u32, pos: u32) -> ASTPos {
-        ASTPos{
-            line:   RefCell::new(line),
-            ch:     RefCell::new(ch),
-            pos:    RefCell::new(pos)
-        }
+        ASTPos{ line, ch, pos }
     }
 
-    pub fn increase_pos_n(&self, n: u32) {
-        // NOTE: 実行時Borrowチェッカーに怒られるので、変数に一旦入れる
-        let a = self.pos();
-        self.set_pos( a + n );
+    pub fn increase_pos_n(&mut self, n: u32) {
+        self.pos += n;
     }
     
-    pub fn increase_line_n(&self, n: u32) {
-        let a = self.line();
-        self.set_line( a + n );
-        self.set_ch(1);
+    pub fn increase_line_n(&mut self, n: u32) {
+        self.line += n;
+        self.ch = 1;
         self.increase_pos_n(n);
     }
 
-    pub fn increase_ch_n(&self, n: u32){
-        let a = self.ch();
-        self.set_ch( a + n );
+    pub fn increase_ch_n(&mut self, n: u32){
+        self.ch += n;
         self.increase_pos_n(n);
     }
 
-    // pos_mut() のようなRust流のインターフェースにしたいが、無理っぽい？
-    pub fn set_pos(&self, n: u32){
-        *self.pos.borrow_mut() = n;
+    pub fn set_pos(&mut self, n: u32){
+        self.pos = n;
     }
 
-    pub fn set_line(&self, n: u32){
-        *self.line.borrow_mut() = n;
+    pub fn set_line(&mut self, n: u32){
+        self.line = n;
     }
 
-    pub fn set_ch(&self, n: u32){
-        *self.ch.borrow_mut() = n;
+    pub fn set_ch(&mut self, n: u32){
+        self.ch = n;
     }
 
     pub fn pos(&self) -> u32 {
-        *self.pos.borrow()
+        self.pos
     }
 
     pub fn line(&self) -> u32 {
-        *self.line.borrow()
+        self.line
     }
 
     pub fn ch(&self) -> u32 {
-        *self.ch.borrow()
+        self.ch
     }
 
 }
 
 #[test]
-fn test_ASTPos_increase_positions() {
+fn test_ast_pos_increase_positions() {
 
-    let pos = ASTPos::new(1,1,0);
+    let mut pos = ASTPos::new(1,1,0);
     assert_eq!( pos.pos(), 0 );
     assert_eq!( pos.line(), 1 );
     assert_eq!( pos.ch(), 1 );
@@ -87,7 +73,7 @@ fn test_ASTPos_increase_positions() {
 
 }
 
-#[derive(Debug, Default, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
 pub struct ASTRange {
     pub begin: ASTPos,
     pub end: ASTPos,
@@ -104,22 +90,31 @@ pub struct ASTNode {
     #[serde(flatten)]
     data: ASTElm,
 
-    children: Vec<Link>,
+    children: Vec<ASTNode>,
 }
 
-#[derive(Debug, Default, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct ASTElm {
     pub elm_type: ASTType,
     pub elm_meta: ASTMetaData,
     pub value: String,
     pub raw_value: String, // パース前のデータ
     pub range: ASTRange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub affiliated: Option<AffiliatedMeta>, // 直前の行の #+NAME: #+CAPTION: など (Org)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trivia: Option<Trivia>, // 具象構文モードでの元の文字列 (lossless.rs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>, // range を数えたファイル (#+INCLUDE: で読み込んだ要素のみ, None は元の文書)
 }
 
 impl ASTElm {
 
     fn build(elm_type: ASTType, elm_meta: ASTMetaData, value: &str, raw_value: &str, range: ASTRange ) -> Self {
-        ASTElm { elm_type, elm_meta, value: value.to_string(), raw_value: raw_value.to_string(), range, }
+        ASTElm {
+            elm_type, elm_meta, value: value.to_string(), raw_value: raw_value.to_string(), range,
+            affiliated: None, trivia: None, source_file: None,
+        }
     }
 
     pub fn new_document() -> Self {
@@ -133,16 +128,15 @@ impl ASTElm {
     }
 
     pub fn new_headers(level: usize, value: &str, raw_value: &str, range: ASTRange ) -> Self {
-        let mut metalevel: ASTMetaData;
-        match level {
-            1 => metalevel = ASTMetaData::H1,
-            2 => metalevel = ASTMetaData::H2,
-            3 => metalevel = ASTMetaData::H3,
-            4 => metalevel = ASTMetaData::H4,
-            5 => metalevel = ASTMetaData::H5,
-            6 => metalevel = ASTMetaData::H6,
-            _ => metalevel = ASTMetaData::H1,
-        }
+        let metalevel = match level {
+            1 => ASTMetaData::H1,
+            2 => ASTMetaData::H2,
+            3 => ASTMetaData::H3,
+            4 => ASTMetaData::H4,
+            5 => ASTMetaData::H5,
+            6 => ASTMetaData::H6,
+            _ => ASTMetaData::H1,
+        };
         ASTElm::build( ASTType::Headers, metalevel, value, raw_value, range )
     }
 
@@ -154,6 +148,44 @@ impl ASTElm {
         ASTElm::build( ASTType::Emphasis, ASTMetaData::Nil, value, raw_value, range )
     }
 
+    pub fn new_strong( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Strong, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    pub fn new_underline( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Underline, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // value: 記号を除いた中身(インライン書式は解釈しない)
+    pub fn new_verbatim( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Verbatim, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    pub fn new_code( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Code, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    pub fn new_strikethrough( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Strikethrough, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    pub fn new_highlight( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Highlight, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    pub fn new_superscript( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Superscript, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    pub fn new_subscript( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Subscript, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // value: 変換後の絵文字, raw_value: ショートコード(:smile:)
+    pub fn new_emoji( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Emoji, ASTMetaData::Nil, value, raw_value, range )
+    }
+
     pub fn new_softbreak( range: ASTRange ) -> Self {
         ASTElm::build( ASTType::SoftBreak, ASTMetaData::Nil, "\n", "\n", range )
     }
@@ -162,30 +194,257 @@ impl ASTElm {
         ASTElm::build( ASTType::HardBreak, ASTMetaData::Nil, "\n", "\n", range )
     }
 
+    // Org見出し value: タイトル部分の文字列
+    pub fn new_headline( meta: HeadlineMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Headline, ASTMetaData::Headline(meta), value, raw_value, range )
+    }
+
+    // 見出しとその配下の要素をまとめる
+    pub fn new_section( level: usize, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Section, ASTMetaData::Section{ level }, "", "", range )
+    }
+
+    // value: リンク先(記述のまま), 子要素: リンクの説明
+    pub fn new_link( meta: LinkMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Link, ASTMetaData::Link(meta), value, raw_value, range )
+    }
+
+    // <<target>>
+    pub fn new_target( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Target, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // インラインタスク 子要素: 見出し, (計画行), 内容
+    pub fn new_inlinetask( raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Inlinetask, ASTMetaData::Nil, "", raw_value, range )
+    }
+
+    // #+BEGIN: clocktable :maxlevel 2 ... #+END:  子要素: 内容
+    pub fn new_dynamic_block( meta: BlockMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::DynamicBlock, ASTMetaData::Block(meta), "", raw_value, range )
+    }
+
+    // :LOGBOOK: などのドロワー 子要素: 内容
+    pub fn new_drawer( name: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Drawer, ASTMetaData::Drawer{ name: name.to_string() }, "", raw_value, range )
+    }
+
+    // CLOCK: [2024-01-02 Tue 10:00]--[2024-01-02 Tue 11:30] =>  1:30  value: "CLOCK:" より後ろ
+    pub fn new_clock( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Clock, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // <<<radio target>>>
+    pub fn new_radio_target( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::RadioTarget, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // [fn:label] [fn::text] [fn:label:text]  子要素: インラインの定義
+    pub fn new_footnote_reference( meta: FootnoteMeta, raw_value: &str, range: ASTRange ) -> Self {
+        let value = meta.label.clone().unwrap_or_default();
+        ASTElm::build( ASTType::FootnoteReference, ASTMetaData::Footnote(meta), &value, raw_value, range )
+    }
+
+    // [fn:label] definition  子要素: 定義の内容
+    pub fn new_footnote_definition( meta: FootnoteMeta, raw_value: &str, range: ASTRange ) -> Self {
+        let value = meta.label.clone().unwrap_or_default();
+        ASTElm::build( ASTType::FootnoteDefinition, ASTMetaData::Footnote(meta), &value, raw_value, range )
+    }
+
+    // \(x^2\) \[x\] $x$ $$x$$ \ref{eq}  value: 区切りの内側 (コマンドは全体)
+    pub fn new_latex_fragment( meta: LatexMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::LatexFragment, ASTMetaData::Latex(meta), value, raw_value, range )
+    }
+
+    // \begin{equation} ... \end{equation}  value: 環境全体
+    pub fn new_latex_environment( meta: LatexMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::LatexEnvironment, ASTMetaData::Latex(meta), value, raw_value, range )
+    }
+
+    // \alpha  value: UTF-8 の文字
+    pub fn new_entity( meta: EntityMeta, raw_value: &str, range: ASTRange ) -> Self {
+        let value = meta.utf8.clone();
+        ASTElm::build( ASTType::Entity, ASTMetaData::Entity(meta), &value, raw_value, range )
+    }
+
+    // {{{name(args)}}}  value: 展開した文字列, 子要素: 展開した内容
+    pub fn new_macro( meta: MacroMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Macro, ASTMetaData::Macro(meta), "", raw_value, range )
+    }
+
+    // #+INCLUDE: "file.org"  value: ファイル名, 子要素: 読み込んだ内容
+    pub fn new_include( meta: IncludeMeta, raw_value: &str, range: ASTRange ) -> Self {
+        let value = meta.file.clone();
+        ASTElm::build( ASTType::Include, ASTMetaData::Include(meta), &value, raw_value, range )
+    }
+
+    pub fn new_property_drawer( raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::PropertyDrawer, ASTMetaData::Nil, "", raw_value, range )
+    }
+
+    // :KEY: value
+    pub fn new_node_property( key: &str, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::NodeProperty, ASTMetaData::Property{ key: key.to_string() }, value, raw_value, range )
+    }
+
+    pub fn new_plain_list( kind: ListKind, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::PlainList, ASTMetaData::List{ kind }, "", raw_value, range )
+    }
+
+    pub fn new_item( meta: ItemMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Item, ASTMetaData::Item(meta), "", raw_value, range )
+    }
+
+    // 記述リストの項目名 (term :: description の term)
+    pub fn new_item_tag( raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::ItemTag, ASTMetaData::Nil, "", raw_value, range )
+    }
+
+    // [2/5] [40%]  value: 集計後の表記
+    pub fn new_statistics_cookie( meta: StatisticsMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::StatisticsCookie, ASTMetaData::Statistics(meta), value, raw_value, range )
+    }
+
+    /*
+     * #+BEGIN_NAME ... #+END_NAME
+     * elm_type: SrcBlock, ExampleBlock, ExportBlock, CommentBlock, VerseBlock, QuoteBlock, CenterBlock, SpecialBlock
+     * value: 内容をそのまま扱うブロック(src, example, export, comment)の内容
+     */
+    pub fn new_block( elm_type: ASTType, meta: BlockMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( elm_type, ASTMetaData::Block(meta), value, raw_value, range )
+    }
+
+    // <2024-01-02 Tue 10:00 +1w> value: 記述のまま
+    pub fn new_timestamp( meta: TimestampMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Timestamp, ASTMetaData::Timestamp(meta), raw_value, raw_value, range )
+    }
+
+    // SCHEDULED: <..> DEADLINE: <..> CLOSED: [..]  子要素: タイムスタンプ
+    pub fn new_planning( meta: PlanningMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Planning, ASTMetaData::Planning(meta), "", raw_value, range )
+    }
+
+    // | a | b |  子要素: 行
+    pub fn new_table( meta: TableMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Table, ASTMetaData::Table(meta), "", raw_value, range )
+    }
+
+    pub fn new_table_row( kind: TableRowKind, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::TableRow, ASTMetaData::TableRow{ kind }, "", raw_value, range )
+    }
+
+    // value: 欄の値 (計算式があれば計算後の値)
+    pub fn new_table_cell( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::TableCell, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // #+KEY: value, #+KEY[option]: value
+    pub fn new_keyword( key: &str, option: Option<&str>, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        let meta = ASTMetaData::Keyword{ key: key.to_string(), option: option.map(String::from) };
+        ASTElm::build( ASTType::Keyword, meta, value, raw_value, range )
+    }
+
+    // #+CALL: name[header](arguments) header  value: 記述のまま
+    pub fn new_babel_call( meta: BabelCallMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::BabelCall, ASTMetaData::BabelCall(meta), value, raw_value, range )
+    }
+
+    // call_name[header](arguments)[header]
+    pub fn new_inline_babel_call( meta: BabelCallMeta, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::InlineBabelCall, ASTMetaData::BabelCall(meta), raw_value, raw_value, range )
+    }
+
+    // : 固定幅の行  value: 先頭の ': ' を除いた内容 (複数行)
+    pub fn new_fixed_width( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::FixedWidth, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // # コメント行  value: 先頭の '# ' を除いた内容 (複数行)
+    pub fn new_comment( value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::Comment, ASTMetaData::Nil, value, raw_value, range )
+    }
+
+    // ----- (Org), --- *** (Markdown)
+    pub fn new_horizontal_rule( raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::HorizontalRule, ASTMetaData::Nil, "", raw_value, range )
+    }
+
+    // src_python[:results output]{1+1}  value: 内容
+    pub fn new_inline_src_block( meta: BlockMeta, value: &str, raw_value: &str, range: ASTRange ) -> Self {
+        ASTElm::build( ASTType::InlineSrcBlock, ASTMetaData::Block(meta), value, raw_value, range )
+    }
+
 
 }
 
 
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub enum ASTType {
+    #[default]
     Document,
     Paragraph,
     Headers,
     Text,
     Emphasis,
+    Strong,
+    Underline,
+    Verbatim,
+    Code,
+    Strikethrough,
+    Highlight,
+    Superscript,
+    Subscript,
+    Emoji,
     SoftBreak,
     HardBreak,
+    Headline,
+    Section,
+    Keyword,
+    Link,
+    Target,
+    RadioTarget,
+    FootnoteReference,
+    FootnoteDefinition,
+    PropertyDrawer,
+    NodeProperty,
+    Drawer,
+    Clock,
+    Timestamp,
+    Planning,
+    Table,
+    TableRow,
+    TableCell,
+    PlainList,
+    Item,
+    ItemTag,
+    StatisticsCookie,
+    SrcBlock,
+    ExampleBlock,
+    ExportBlock,
+    CommentBlock,
+    VerseBlock,
+    QuoteBlock,
+    CenterBlock,
+    SpecialBlock,
+    LatexEnvironment,
+    LatexFragment,
+    Entity,
+    Macro,
+    Include,
+    BabelCall,
+    InlineBabelCall,
+    InlineSrcBlock,
+    FixedWidth,
+    Comment,
+    HorizontalRule,
+    Inlinetask,
+    DynamicBlock,
 }
 
-impl Default for ASTType {
-    fn default() -> Self {
-        ASTType::Document
-    }
-}
-
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub enum ASTMetaData {
+    #[default]
     Nil,
     H1,
     H2,
@@ -193,14 +452,446 @@ pub enum ASTMetaData {
     H4,
     H5,
     H6,
+    Headline(HeadlineMeta),
+    Section { level: usize },
+    Keyword { key: String, option: Option<String> },
+    Link(LinkMeta),
+    Property { key: String },
+    Drawer { name: String },
+    Footnote(FootnoteMeta),
+    Timestamp(TimestampMeta),
+    Planning(PlanningMeta),
+    Table(TableMeta),
+    TableRow { kind: TableRowKind },
+    Document(DocumentMeta),
+    List { kind: ListKind },
+    Item(ItemMeta),
+    Statistics(StatisticsMeta),
+    Block(BlockMeta),
+    Latex(LatexMeta),
+    Entity(EntityMeta),
+    Macro(MacroMeta),
+    Include(IncludeMeta),
+    BabelCall(BabelCallMeta),
 }
 
-impl Default for ASTMetaData {
-    fn default() -> Self {
-        ASTMetaData::Nil
+impl ASTMetaData {
+    // 見出しのレベル(Markdown, Org 共通)
+    pub fn heading_level(&self) -> Option<usize> {
+        match self {
+            ASTMetaData::H1 => Some(1),
+            ASTMetaData::H2 => Some(2),
+            ASTMetaData::H3 => Some(3),
+            ASTMetaData::H4 => Some(4),
+            ASTMetaData::H5 => Some(5),
+            ASTMetaData::H6 => Some(6),
+            ASTMetaData::Headline(meta) => Some(meta.level),
+            _ => None,
+        }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TodoType {
+    Todo,
+    Done,
+}
+
+/*
+ * Org見出しの付加情報
+ * ** TODO [#A] COMMENT Title :tag1:tag2:
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HeadlineMeta {
+    pub level: usize,
+    pub todo_keyword: Option<String>,
+    pub todo_type: Option<TodoType>,
+    pub priority: Option<String>,
+    pub tags: Vec<String>,
+    pub commented: bool,
+    pub properties: BTreeMap<String, String>, // プロパティドロワー (キーは大文字, KEY+ は KEY に追記)
+    pub planning: PlanningMeta,
+    pub number: Option<String>, // 見出し番号 "1.2" (#+OPTIONS: num:)
+}
+
+/*
+ * 文書の付加情報 (Org)
+ * #+TITLE: #+AUTHOR: #+DATE: #+LANGUAGE: #+FILETAGS: #+CATEGORY: #+SETUPFILE: #+OPTIONS:
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DocumentMeta {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub language: Option<String>,
+    pub filetags: Vec<String>,
+    pub category: Option<String>,
+    pub setupfile: Option<String>,
+    pub options: ExportOptions,
+    pub toc: Vec<TocEntry>, // toc: が有効な場合の目次
+    pub footnotes: Vec<Footnote>,
+    pub targets: BTreeMap<String, LinkResolution>, // <<target>>, <<<radio target>>> (名前は小文字)
+}
+
+/*
+ * 脚注の付加情報
+ * label: [fn::text] は None
+ * number: 文書中で最初に参照された順の番号 (参照されない定義は None)
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FootnoteMeta {
+    pub label: Option<String>,
+    pub inline: bool,
+    pub number: Option<usize>,
+}
+
+// 参照された脚注の一覧 (番号順)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Footnote {
+    pub label: Option<String>,
+    pub number: usize,
+    pub definition: Option<ASTRange>, // 定義またはインラインの参照の範囲
+    pub references: Vec<ASTRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SubSuperscript {
+    On,     // ^:t   a_b a^b
+    Braces, // ^:{}  a_{b} a^{b} のみ
+    Off,    // ^:nil
+}
+
+// :ARCHIVE: タグの付いた見出しの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ArchivedTrees {
+    Keep,         // arch:t        すべて出力する
+    HeadlineOnly, // arch:headline 見出しのみ出力する
+    Drop,         // arch:nil      出力しない
+}
+
+/*
+ * #+OPTIONS: H:3 num:t toc:2 ^:{} arch:headline
+ * toc, num の深さを省略した場合は headline_levels (H:) まで
+ * 出力に影響しない項目 (todo:, tags:, pri: など) は raw にのみ入れる
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportOptions {
+    pub headline_levels: usize,
+    pub section_numbers: bool,
+    pub section_number_depth: Option<usize>,
+    pub toc: bool,
+    pub toc_depth: Option<usize>,
+    pub sub_superscript: SubSuperscript,
+    pub archived_trees: ArchivedTrees,
+    pub raw: BTreeMap<String, String>, // すべての項目 (key:value)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TocEntry {
+    pub level: usize, // 入れ子の深さ (1から)
+    pub title: String,
+    pub number: Option<String>,
+    pub range: ASTRange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct OrgDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct OrgTime {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TimestampKind {
+    Active,        // <...>
+    Inactive,      // [...]
+    ActiveRange,   // <...>--<...>, <... 10:00-11:30>
+    InactiveRange, // [...]--[...], [... 10:00-11:30]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TimeUnit {
+    Hour,  // h
+    Day,   // d
+    Week,  // w
+    Month, // m
+    Year,  // y
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum RepeaterKind {
+    Cumulate, // +1w
+    CatchUp,  // ++1w
+    Restart,  // .+1w
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Repeater {
+    pub kind: RepeaterKind,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum DelayKind {
+    All,   // -2d  (繰り返しのすべてに適用)
+    First, // --2d (最初の一回のみ)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Delay {
+    pub kind: DelayKind,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+/*
+ * タイムスタンプ
+ * <2024-01-02 Tue 10:00-11:30 +1w -2d>
+ * 範囲の場合は end_date, end_time に終わりを持つ
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimestampMeta {
+    pub kind: TimestampKind,
+    pub date: OrgDate,
+    pub time: Option<OrgTime>,
+    pub end_date: Option<OrgDate>,
+    pub end_time: Option<OrgTime>,
+    pub repeater: Option<Repeater>,
+    pub delay: Option<Delay>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PlanningMeta {
+    pub scheduled: Option<TimestampMeta>,
+    pub deadline: Option<TimestampMeta>,
+    pub closed: Option<TimestampMeta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TableRowKind {
+    Standard,
+    Rule,    // |---+---|
+    Special, // | <10> | <r> |, | / | < | > |
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Alignment {
+    Left,   // <l>
+    Center, // <c>
+    Right,  // <r>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TableColumn {
+    pub width: Option<u32>, // <10>
+    pub align: Option<Alignment>,
+    pub group_start: bool, // <  <>
+    pub group_end: bool,   // >  <>
+}
+
+/*
+ * 表の付加情報
+ * columns: 幅指定・列グループの行から集めた列の設定
+ * formulas: #+TBLFM: の式 ("::" で区切られたものは分ける)
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TableMeta {
+    pub columns: Vec<TableColumn>,
+    pub formulas: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ListKind {
+    Unordered,   // - + *
+    Ordered,     // 1. 1)
+    Descriptive, // - term :: description
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Checkbox {
+    Off,   // [ ]
+    On,    // [X]
+    Trans, // [-]
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ItemMeta {
+    pub bullet: String,
+    pub counter: Option<u32>, // [@5]
+    pub checkbox: Option<Checkbox>,
+}
+
+/*
+ * 統計クッキー [2/5] [40%]
+ * 親の項目・見出しの配下にあるチェックボックス(またはTODO)から集計する
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StatisticsMeta {
+    pub percent: bool,
+    pub done: usize,
+    pub total: usize,
+}
+
+/*
+ * ブロックの付加情報
+ * #+BEGIN_SRC python -n :results output :exports both
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BlockMeta {
+    pub name: String,                          // SRC, QUOTE など (大文字), 動的ブロックは clocktable など記述のまま
+    pub parameters: String,                    // 名前より後ろの文字列
+    pub language: Option<String>,              // SRC の言語, EXPORT のバックエンド
+    pub switches: Vec<String>,                 // -n -r -l "(ref:%s)"
+    pub header_args: BTreeMap<String, String>, // :results output (キーは ':' を除く)
+    pub expanded: Option<String>,              // noweb 参照 <<name>> を展開した内容 (:noweb yes)
+}
+
+/*
+ * 要素の付加キーワード (要素の直前の行に書く)
+ * #+NAME: fig1
+ * #+CAPTION[短い説明]: 説明
+ * #+ATTR_HTML: :width 300 :alt 図
+ * #+HEADER: :results output
+ * #+RESULTS[hash]: name
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AffiliatedMeta {
+    pub name: Option<String>,
+    pub caption: Option<String>, // 複数行は空白で繋ぐ
+    pub short_caption: Option<String>,
+    pub attributes: BTreeMap<String, BTreeMap<String, String>>, // #+ATTR_HTML: :width 300 -> html -> width -> 300
+    pub headers: Vec<String>,
+    pub results: Option<ResultsMeta>,
+    pub raw_value: String, // 付加キーワードの行
+    pub range: ASTRange,
+}
+
+/*
+ * 要素の範囲に入らない文字列 (空行・見出しの # ・行末の空白など)
+ * 子の leading と親の trailing、子を持たない要素の text を繋ぐと入力と一致する
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Trivia {
+    pub leading: String,  // 前の兄弟 (最初の子なら親の始まり) からこの要素の始まりまで
+    pub text: String,     // 子を持たない要素の範囲の文字列
+    pub trailing: String, // 最後の子の後ろから要素の終わりまで
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResultsMeta {
+    pub hash: Option<String>,
+    pub name: Option<String>,
+    pub source: Option<ASTRange>, // この結果を出力したソースブロック・#+CALL: の範囲
+}
+
+/*
+ * コードの呼び出しの付加情報
+ * #+CALL: name[inside_header](arguments) end_header
+ * call_name[inside_header](arguments)[end_header]
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BabelCallMeta {
+    pub name: String,
+    pub inside_header: Option<String>,
+    pub arguments: String,
+    pub end_header: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum LatexDelimiter {
+    Paren,        // \( ... \)
+    Bracket,      // \[ ... \]
+    Dollar,       // $ ... $
+    DoubleDollar, // $$ ... $$
+    Command,      // \command[option]{argument}
+    Environment,  // \begin{name} ... \end{name}
+}
+
+/*
+ * LaTeX の断片・環境の付加情報
+ * display: 独立した行に表示する数式 (\[ \], $$ $$, 環境)
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatexMeta {
+    pub delimiter: LatexDelimiter,
+    pub display: bool,
+    pub environment: Option<String>, // \begin{equation} の equation
+}
+
+// \alpha \rarr{} などのエンティティ (org_entity の変換テーブルから引く)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityMeta {
+    pub name: String,
+    pub latex: String,
+    pub math: bool, // LaTeX の数式モードで使う
+    pub html: String,
+    pub utf8: String,
+}
+
+// {{{name(arg1, arg2)}}}
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MacroMeta {
+    pub name: String, // 小文字
+    pub arguments: Vec<String>,
+}
+
+/*
+ * #+INCLUDE: "file.org" src python :lines "5-10" :minlevel 2
+ * 読み込んだ内容の位置情報は、読み込んだファイルの中の位置になる (どのファイルかは要素の source_file に入る)
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IncludeMeta {
+    pub file: String,
+    pub block: Option<String>,  // SRC, EXAMPLE, EXPORT など (大文字)
+    pub parameters: String,     // ブロックの引数 (言語など)
+    pub lines: Option<String>,  // "5-10" (10行目も含む)
+    pub minlevel: Option<usize>,
+    pub error: Option<String>,  // 読み込めなかった場合の理由
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LinkFormat {
+    Bracket, // [[target][description]]
+    Plain,   // https://example.com
+    Angle,   // <https://example.com>
+}
+
+/*
+ * リンクの付加情報
+ * [[file:x.org::*Heading][description]]
+ *   link_type: "file", path: "x.org", search_option: Some("*Heading")
+ * 種類の無いリンクは "custom-id"(#id), "coderef"((ref)), "fuzzy"(*Headline, target) になる
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkMeta {
+    pub link_type: String,
+    pub path: String,
+    pub search_option: Option<String>,
+    pub format: LinkFormat,
+    pub resolved: Option<LinkResolution>, // 文書内リンクの解決結果
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LinkResolutionKind {
+    Headline,
+    Target,
+    RadioTarget,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkResolution {
+    pub kind: LinkResolutionKind,
+    pub name: String, // 見出しのタイトル、ターゲット名
+    pub range: ASTRange,
+}
+
 impl ASTNode {
     pub fn new(v: ASTElm) -> Self {
         ASTNode {
@@ -215,8 +906,7 @@ impl ASTNode {
     }
 
     pub fn append_node(&mut self, node: ASTNode) {
-        let rc_node = Rc::new(RefCell::new(node));
-        self.children.push(Rc::clone(&rc_node));
+        self.children.push(node);
     }
 
     pub fn append_node_from_vec(&mut self, nodes: Vec<ASTNode>) {
@@ -225,6 +915,39 @@ impl ASTNode {
         }
     }
 
+    // 要素と子に分ける (arena.rs で並べ替える用)
+    pub(crate) fn into_parts(self) -> (ASTElm, Vec<ASTNode>) {
+        (self.data, self.children)
+    }
+
+    pub fn children(&self) -> &[ASTNode] {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut Vec<ASTNode> {
+        &mut self.children
+    }
+
+    // 自身と子孫 (前順: 親が先)
+    pub fn pre_order(&self) -> PreOrder<'_> {
+        PreOrder { stack: vec![self] }
+    }
+
+    // 自身と子孫 (後順: 子が先)
+    pub fn post_order(&self) -> PostOrder<'_> {
+        PostOrder { stack: vec![(self, false)] }
+    }
+
+    // 子孫 (前順, 自身は含まない)
+    pub fn descendants(&self) -> std::iter::Skip<PreOrder<'_>> {
+        self.pre_order().skip(1)
+    }
+
+    // 見出しのレベル(Markdown, Org 共通)
+    pub fn heading_level(&self) -> Option<usize> {
+        self.meta().heading_level()
+    }
+
     //
     // --- setter, getter ---
     //
@@ -289,9 +1012,42 @@ impl ASTNode {
         *self.range_mut() = v;
     }
 
+    pub fn affiliated(&self) -> Option<&AffiliatedMeta> {
+        self.data.affiliated.as_ref()
+    }
+
+    pub fn affiliated_mut(&mut self) -> Option<&mut AffiliatedMeta> {
+        self.data.affiliated.as_mut()
+    }
+
+    pub fn set_affiliated(&mut self, v: Option<AffiliatedMeta>) {
+        self.data.affiliated = v;
+    }
+
+    pub fn trivia(&self) -> Option<&Trivia> {
+        self.data.trivia.as_ref()
+    }
+
+    pub fn trivia_mut(&mut self) -> Option<&mut Trivia> {
+        self.data.trivia.as_mut()
+    }
+
+    pub fn set_trivia(&mut self, v: Option<Trivia>) {
+        self.data.trivia = v;
+    }
+
+    pub fn source_file(&self) -> Option<&String> {
+        self.data.source_file.as_ref()
+    }
+
+    pub fn set_source_file(&mut self, v: Option<String>) {
+        self.data.source_file = v;
+    }
+
     //
     // --- rendering ---
     //
+
     pub fn render_debug_format(&self) -> String {
         self._render_debug_format(self)
     }
@@ -299,29 +1055,56 @@ impl ASTNode {
     fn _render_tag(&self, tagname: &str, node: &ASTNode) -> String {
         let mut result: String = "<".to_string() + tagname + ">";
         for child in &node.children {
-            result = result + &self._render_debug_format(&child.borrow());
+            result = result + &self._render_debug_format(child);
         }
         result = result + "</" + tagname + ">";
-        return result;
+        result
     }
 
     fn _render_debug_format(&self, node: &ASTNode) -> String {
         let mut result: String = "".to_string();
         match node.node_type() {
             ASTType::Document => {
-                result += &self._render_tag("document", &node);
+                result += &self._render_tag("document", node);
             }
             ASTType::Paragraph => {
-                result += &self._render_tag("paragraph", &node);
+                result += &self._render_tag("paragraph", node);
             }
             ASTType::Headers => {
-                result += &self._render_tag("header", &node);
+                result += &self._render_tag("header", node);
             }
             ASTType::Text => {
                 result += &("<text>".to_string() + &node.value().to_string() + "</text>");
             }
             ASTType::Emphasis => {
-                result += &self._render_tag("emphasis", &node);
+                result += &self._render_tag("emphasis", node);
+            }
+            ASTType::Strong => {
+                result += &self._render_tag("strong", node);
+            }
+            ASTType::Underline => {
+                result += &self._render_tag("underline", node);
+            }
+            ASTType::Verbatim => {
+                result += &("<verbatim>".to_string() + node.value() + "</verbatim>");
+            }
+            ASTType::Code => {
+                result += &("<code>".to_string() + node.value() + "</code>");
+            }
+            ASTType::Strikethrough => {
+                result += &self._render_tag("strikethrough", node);
+            }
+            ASTType::Highlight => {
+                result += &self._render_tag("highlight", node);
+            }
+            ASTType::Superscript => {
+                result += &self._render_tag("superscript", node);
+            }
+            ASTType::Subscript => {
+                result += &self._render_tag("subscript", node);
+            }
+            ASTType::Emoji => {
+                result += &("<emoji>".to_string() + node.value() + "</emoji>");
             }
             ASTType::SoftBreak => {
                 //result = node.value().to_string();
@@ -330,24 +1113,196 @@ impl ASTNode {
             ASTType::HardBreak => {
                 result += "<hardbreak />";
             }
-            _ => {
-                result = result + "<error>Undefined ASTNode Type</error>";
+            ASTType::Headline => {
+                result += &self._render_tag("headline", node);
+            }
+            ASTType::Section => {
+                result += &self._render_tag("section", node);
+            }
+            ASTType::Link => {
+                if node.children.is_empty() {
+                    result += &("<link>".to_string() + node.value() + "</link>");
+                } else {
+                    result += &self._render_tag("link", node);
+                }
+            }
+            ASTType::Target => {
+                result += &("<target>".to_string() + node.value() + "</target>");
+            }
+            ASTType::RadioTarget => {
+                result += &("<radio>".to_string() + node.value() + "</radio>");
+            }
+            ASTType::FootnoteReference => {
+                if node.children.is_empty() {
+                    result += &("<fnref>".to_string() + node.value() + "</fnref>");
+                } else {
+                    result += &self._render_tag("fnref", node);
+                }
+            }
+            ASTType::FootnoteDefinition => {
+                result += &self._render_tag("fndef", node);
+            }
+            ASTType::PropertyDrawer => {
+                result += &self._render_tag("properties", node);
+            }
+            ASTType::NodeProperty => {
+                result += &("<property>".to_string() + node.value() + "</property>");
+            }
+            ASTType::Drawer => {
+                result += &self._render_tag("drawer", node);
+            }
+            ASTType::Clock => {
+                result += &("<clock>".to_string() + node.value() + "</clock>");
+            }
+            ASTType::Timestamp => {
+                result += &("<timestamp>".to_string() + node.value() + "</timestamp>");
+            }
+            ASTType::Planning => {
+                result += &self._render_tag("planning", node);
+            }
+            ASTType::Table => {
+                result += &self._render_tag("table", node);
+            }
+            ASTType::TableRow => {
+                result += &self._render_tag("row", node);
+            }
+            ASTType::TableCell => {
+                result += &self._render_tag("cell", node);
+            }
+            ASTType::PlainList => {
+                result += &self._render_tag("list", node);
+            }
+            ASTType::Item => {
+                result += &self._render_tag("item", node);
+            }
+            ASTType::ItemTag => {
+                result += &self._render_tag("tag", node);
+            }
+            ASTType::StatisticsCookie => {
+                result += &("<statistics>".to_string() + node.value() + "</statistics>");
+            }
+            ASTType::SrcBlock => {
+                result += &("<src>".to_string() + node.value() + "</src>");
+            }
+            ASTType::ExampleBlock => {
+                result += &("<example>".to_string() + node.value() + "</example>");
+            }
+            ASTType::ExportBlock => {
+                result += &("<export>".to_string() + node.value() + "</export>");
+            }
+            ASTType::CommentBlock => {
+                result += &("<commentblock>".to_string() + node.value() + "</commentblock>");
+            }
+            ASTType::VerseBlock => {
+                result += &self._render_tag("verse", node);
+            }
+            ASTType::QuoteBlock => {
+                result += &self._render_tag("quote", node);
+            }
+            ASTType::CenterBlock => {
+                result += &self._render_tag("center", node);
+            }
+            ASTType::SpecialBlock => {
+                result += &self._render_tag("special", node);
+            }
+            ASTType::LatexEnvironment => {
+                result += &("<latexenv>".to_string() + node.value() + "</latexenv>");
+            }
+            ASTType::LatexFragment => {
+                result += &("<latex>".to_string() + node.value() + "</latex>");
+            }
+            ASTType::Entity => {
+                result += &("<entity>".to_string() + node.value() + "</entity>");
+            }
+            ASTType::Macro => {
+                if node.children.is_empty() {
+                    result += &("<macro>".to_string() + node.value() + "</macro>");
+                } else {
+                    result += &self._render_tag("macro", node);
+                }
+            }
+            ASTType::Include => {
+                if node.children.is_empty() {
+                    result += &("<include>".to_string() + node.value() + "</include>");
+                } else {
+                    result += &self._render_tag("include", node);
+                }
+            }
+            ASTType::Keyword => {
+                result += &("<keyword>".to_string() + node.value() + "</keyword>");
+            }
+            ASTType::BabelCall => {
+                result += &("<call>".to_string() + node.value() + "</call>");
+            }
+            ASTType::InlineBabelCall => {
+                result += &("<inlinecall>".to_string() + node.value() + "</inlinecall>");
+            }
+            ASTType::InlineSrcBlock => {
+                result += &("<inlinesrc>".to_string() + node.value() + "</inlinesrc>");
+            }
+            ASTType::FixedWidth => {
+                result += &("<fixedwidth>".to_string() + node.value() + "</fixedwidth>");
+            }
+            ASTType::Comment => {
+                result += &("<comment>".to_string() + node.value() + "</comment>");
+            }
+            ASTType::HorizontalRule => {
+                result += "<hr />";
+            }
+            ASTType::Inlinetask => {
+                result += &self._render_tag("inlinetask", node);
+            }
+            ASTType::DynamicBlock => {
+                result += &self._render_tag("dynamic", node);
             }
         }
-        return result;
+        result
     }
 }
 
 // イテレータの実装
-/*
-impl<T> Iterator for ASTNode<T> {
-    type Item = T;
+pub struct PreOrder<'a> {
+    stack: Vec<&'a ASTNode>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = &'a ASTNode;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+pub struct PostOrder<'a> {
+    stack: Vec<(&'a ASTNode, bool)>, // (要素, 子を積んだか)
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = &'a ASTNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded || node.children.is_empty() {
+                return Some(node);
+            }
+            self.stack.push((node, true));
+            self.stack.extend(node.children.iter().rev().map(|child| (child, false)));
+        }
+    }
+}
+
+// for node in &document { ... } は前順に辿る
+impl<'a> IntoIterator for &'a ASTNode {
+    type Item = &'a ASTNode;
+    type IntoIter = PreOrder<'a>;
 
+    fn into_iter(self) -> Self::IntoIter {
+        self.pre_order()
     }
 }
-*/
 
 /*
 #[cfg(test)]
@@ -0,0 +1,1217 @@
+use crate::arena::NodeRef;
+use crate::ast::*;
+use crate::org_parser::INLINETASK_MIN_LEVEL;
+
+use serde::Serialize;
+
+/*
+ * Markdown と Org の相互変換
+ *
+ * どちらのパーサーで作った木でも、Org または Markdown のテキストに書き出す。
+ *   見出し       # Title            <-> * Title
+ *   強調         *em* **strong**    <-> /em/ *strong*
+ *   リンク       [desc](url)        <-> [[url][desc]]
+ *   コード       ```lang            <-> #+BEGIN_SRC lang
+ *   脚注         [^label]           <-> [fn:label]
+ * 書き出す先で表せない要素は、近い形にするか中身だけを残して ConversionWarning に記録する。
+ * (黙って捨てることはしない)
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Syntax {
+    Org,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Conversion {
+    pub text: String,
+    pub warnings: Vec<ConversionWarning>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversionWarning {
+    pub node_type: ASTType,
+    pub message: String,
+    pub range: ASTRange, // 変換元での位置
+}
+
+/*
+ * Markdown の書き出し方
+ * 見出しは # (ATX), 強調は *em* **strong**, 箇条書きは - と 1. (番号は振り直す), コードは ``` で囲む。
+ * 記法と読まれる文字は \ で逃がす。
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkdownOptions {
+    pub width: Option<usize>, // 段落を折り返す幅 (None: 元の改行のまま)
+}
+
+pub fn to_org(node: NodeRef) -> Conversion {
+    Converter::new(Syntax::Org, MarkdownOptions::default()).convert(node)
+}
+
+pub fn to_markdown(node: NodeRef) -> Conversion {
+    Converter::new(Syntax::Markdown, MarkdownOptions::default()).convert(node)
+}
+
+/*
+ * 木を整形した CommonMark にする (Markdown の整形にも使う)
+ *   md_parse(source) -> render_markdown
+ * 表せない要素は to_markdown と同じ扱いにする (警告は返さない)
+ */
+pub fn render_markdown(node: NodeRef, options: &MarkdownOptions) -> String {
+    Converter::new(Syntax::Markdown, options.clone()).convert(node).text
+}
+
+struct Converter {
+    syntax: Syntax,
+    options: MarkdownOptions,
+    margin: usize, // Markdown: 段落の左の字下げ (リストの項目・引用の中)
+    warnings: Vec<ConversionWarning>,
+    footnotes: Vec<String>, // Markdown: インライン脚注の定義 (文書の最後に置く)
+}
+
+// 子要素を並べる
+fn children_of(node: NodeRef) -> Vec<NodeRef> {
+    node.children().collect()
+}
+
+// 2行目以降を字下げする
+fn indent_rest(text: &str, width: usize) -> String {
+    let indent = " ".repeat(width);
+    let lines: Vec<String> = text
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| if i == 0 || line.is_empty() { line.to_string() } else { indent.clone() + line })
+        .collect();
+    lines.join("\n")
+}
+
+// 末尾を改行で終える
+fn with_line_ending(s: &str) -> String {
+    if s.is_empty() || s.ends_with('\n') {
+        s.to_string()
+    } else {
+        s.to_string() + "\n"
+    }
+}
+
+// 中身に含まれない長さのバッククォート (`code`, ```fence)
+fn backtick_fence(s: &str, min: usize) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in s.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat((longest + 1).max(min))
+}
+
+// ブロックの中で見出しや #+ と読まれる行を , で逃がす (Org)
+fn escape_org_block(value: &str) -> String {
+    let lines: Vec<String> = value
+        .split('\n')
+        .map(|line| {
+            let trimmed = line.trim_start().trim_start_matches(',');
+            if trimmed.starts_with('*') || trimmed.starts_with("#+") {
+                let indent = line.len() - line.trim_start().len();
+                format!("{},{}", &line[..indent], &line[indent..])
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+impl Converter {
+    fn new(syntax: Syntax, options: MarkdownOptions) -> Self {
+        Converter { syntax, options, margin: 0, warnings: vec![], footnotes: vec![] }
+    }
+
+    // 字下げした中身を書き出す
+    fn indented<T>(&mut self, width: usize, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.margin += width;
+        let result = f(self);
+        self.margin -= width;
+        result
+    }
+
+    fn is_org(&self) -> bool {
+        self.syntax == Syntax::Org
+    }
+
+    fn warn(&mut self, node: NodeRef, message: &str) {
+        self.warnings.push(ConversionWarning {
+            node_type: node.node_type().clone(),
+            message: message.to_string(),
+            range: *node.range(),
+        });
+    }
+
+    fn convert(mut self, node: NodeRef) -> Conversion {
+        let mut text = match node.node_type() {
+            ASTType::Document | ASTType::Section => self.blocks(node),
+            _ if self.is_object(node) => self.inline(node),
+            _ => self.block(node),
+        };
+        if !self.footnotes.is_empty() {
+            text = text + "\n\n" + &self.footnotes.join("\n\n");
+        }
+        Conversion { text: with_line_ending(&text), warnings: self.warnings }
+    }
+
+    fn is_object(&self, node: NodeRef) -> bool {
+        matches!(
+            node.node_type(),
+            ASTType::Text
+                | ASTType::Emphasis
+                | ASTType::Strong
+                | ASTType::Underline
+                | ASTType::Verbatim
+                | ASTType::Code
+                | ASTType::Strikethrough
+                | ASTType::Highlight
+                | ASTType::Superscript
+                | ASTType::Subscript
+                | ASTType::Emoji
+                | ASTType::SoftBreak
+                | ASTType::HardBreak
+                | ASTType::Link
+                | ASTType::Target
+                | ASTType::RadioTarget
+                | ASTType::FootnoteReference
+                | ASTType::Timestamp
+                | ASTType::StatisticsCookie
+                | ASTType::LatexFragment
+                | ASTType::Entity
+                | ASTType::Macro
+                | ASTType::InlineBabelCall
+                | ASTType::InlineSrcBlock
+        )
+    }
+
+    /* ---------- 要素 ---------- */
+
+    // 要素の並び (空行で区切る。Org の見出し直後の計画行・プロパティドロワーは続けて書く)
+    fn blocks(&mut self, node: NodeRef) -> String {
+        let mut result = String::new();
+        let mut previous: Option<ASTType> = None;
+        for child in children_of(node) {
+            let text = self.block(child);
+            if text.is_empty() {
+                continue;
+            }
+            let attached = matches!(child.node_type(), ASTType::Planning | ASTType::PropertyDrawer)
+                && matches!(previous, Some(ASTType::Headline) | Some(ASTType::Planning));
+            if previous.is_some() {
+                result.push_str(if attached { "\n" } else { "\n\n" });
+            }
+            result.push_str(&text);
+            previous = Some(child.node_type().clone());
+        }
+        result
+    }
+
+    fn block(&mut self, node: NodeRef) -> String {
+        let text = self.element(node);
+        match node.affiliated() {
+            Some(affiliated) if self.is_org() && !text.is_empty() => affiliated.raw_value.clone() + "\n" + &text,
+            Some(_) => {
+                self.warn(node, "affiliated keywords (#+NAME:, #+CAPTION:) are dropped");
+                text
+            }
+            None => text,
+        }
+    }
+
+    fn element(&mut self, node: NodeRef) -> String {
+        let org = self.is_org();
+        match node.node_type() {
+            ASTType::Document | ASTType::Section => self.blocks(node),
+            ASTType::Paragraph if org => self.inlines(node),
+            ASTType::Paragraph => self.markdown_paragraph(node),
+            ASTType::Headers | ASTType::Headline => self.headline(node),
+            ASTType::Inlinetask => {
+                let children = children_of(node);
+                let headline = children.iter().find(|child| *child.node_type() == ASTType::Headline).copied();
+                let title = headline.map(|headline| self.inlines(headline)).unwrap_or_default();
+                let mut blocks = vec![];
+                for &child in children.iter().filter(|child| *child.node_type() != ASTType::Headline) {
+                    let text = self.block(child);
+                    if !text.is_empty() {
+                        blocks.push(text);
+                    }
+                }
+                if org {
+                    let level = headline.and_then(|headline| headline.heading_level());
+                    let stars = "*".repeat(level.unwrap_or(INLINETASK_MIN_LEVEL));
+                    let headline = match headline {
+                        Some(headline) => self.headline(headline),
+                        None => stars.clone() + " " + &title,
+                    };
+                    let mut lines = vec![headline];
+                    lines.extend(blocks);
+                    lines.push(stars + " END");
+                    lines.join("\n")
+                } else {
+                    self.warn(node, "inline task is written as a bold paragraph");
+                    let mut lines = vec![format!("**{}**", title)];
+                    lines.extend(blocks);
+                    lines.join("\n\n")
+                }
+            }
+            ASTType::PlainList => {
+                let children = children_of(node);
+                let mut number = None;
+                let mut items = vec![];
+                for &child in children.iter() {
+                    items.push(self.item(child, &mut number));
+                }
+                items.join("\n")
+            }
+            ASTType::Item => self.item(node, &mut None),
+            ASTType::Table => self.table(node),
+            ASTType::SrcBlock | ASTType::ExampleBlock => {
+                let meta = match node.meta() {
+                    ASTMetaData::Block(meta) => meta.clone(),
+                    _ => BlockMeta::default(),
+                };
+                let is_src = *node.node_type() == ASTType::SrcBlock;
+                if org {
+                    let name = if is_src { "SRC" } else { "EXAMPLE" };
+                    let parameters = match (&meta.language, meta.parameters.is_empty()) {
+                        (_, false) => " ".to_string() + &meta.parameters,
+                        (Some(language), true) => " ".to_string() + language,
+                        (None, true) => String::new(),
+                    };
+                    let value = with_line_ending(&escape_org_block(node.value()));
+                    format!("#+BEGIN_{}{}\n{}#+END_{}", name, parameters, value, name)
+                } else {
+                    if !meta.switches.is_empty() || !meta.header_args.is_empty() {
+                        self.warn(node, "block switches and header arguments are not representable in Markdown");
+                    }
+                    let fence = backtick_fence(node.value(), 3);
+                    let language = if is_src { meta.language.unwrap_or_default() } else { String::new() };
+                    format!("{}{}\n{}{}", fence, language, with_line_ending(node.value()), fence)
+                }
+            }
+            ASTType::FixedWidth => {
+                if org {
+                    let lines: Vec<String> = node
+                        .value()
+                        .split('\n')
+                        .map(|line| if line.is_empty() { ":".to_string() } else { ": ".to_string() + line })
+                        .collect();
+                    lines.join("\n")
+                } else {
+                    let fence = backtick_fence(node.value(), 3);
+                    format!("{}\n{}{}", fence, with_line_ending(node.value()), fence)
+                }
+            }
+            ASTType::ExportBlock => {
+                let backend = match node.meta() {
+                    ASTMetaData::Block(meta) => meta.language.clone().unwrap_or_default(),
+                    _ => String::new(),
+                };
+                if org {
+                    format!("#+BEGIN_EXPORT {}\n{}#+END_EXPORT", backend, with_line_ending(node.value()))
+                } else if ["html", "markdown", "md"].contains(&backend.to_lowercase().as_str()) {
+                    node.value().trim_end().to_string()
+                } else {
+                    self.warn(node, &format!("export block for \"{}\" is dropped", backend));
+                    String::new()
+                }
+            }
+            ASTType::CommentBlock => {
+                if org {
+                    format!("#+BEGIN_COMMENT\n{}#+END_COMMENT", with_line_ending(&escape_org_block(node.value())))
+                } else {
+                    format!("<!--\n{}-->", with_line_ending(node.value()))
+                }
+            }
+            ASTType::Comment => {
+                if org {
+                    let lines: Vec<String> = node
+                        .value()
+                        .split('\n')
+                        .map(|line| if line.is_empty() { "#".to_string() } else { "# ".to_string() + line })
+                        .collect();
+                    lines.join("\n")
+                } else {
+                    format!("<!-- {} -->", node.value())
+                }
+            }
+            ASTType::QuoteBlock => {
+                let contents = self.indented(2, |converter| converter.blocks(node));
+                if org {
+                    format!("#+BEGIN_QUOTE\n{}\n#+END_QUOTE", contents)
+                } else {
+                    let lines: Vec<String> = contents
+                        .split('\n')
+                        .map(|line| if line.is_empty() { ">".to_string() } else { "> ".to_string() + line })
+                        .collect();
+                    lines.join("\n")
+                }
+            }
+            ASTType::VerseBlock => {
+                let contents = self.inlines(node);
+                if org {
+                    format!("#+BEGIN_VERSE\n{}\n#+END_VERSE", contents)
+                } else {
+                    // 行ごとに改行する
+                    contents.split('\n').collect::<Vec<&str>>().join("\\\n")
+                }
+            }
+            ASTType::CenterBlock | ASTType::SpecialBlock => {
+                let name = match node.meta() {
+                    ASTMetaData::Block(meta) => meta.name.clone(),
+                    _ => "CENTER".to_string(),
+                };
+                let contents = self.blocks(node);
+                if org {
+                    format!("#+BEGIN_{}\n{}\n#+END_{}", name, contents, name)
+                } else {
+                    self.warn(node, &format!("{} block is written as its contents", name));
+                    contents
+                }
+            }
+            ASTType::DynamicBlock => {
+                let (name, parameters) = match node.meta() {
+                    ASTMetaData::Block(meta) => (meta.name.clone(), meta.parameters.clone()),
+                    _ => (String::new(), String::new()),
+                };
+                let contents = self.blocks(node);
+                if org {
+                    let header = if parameters.is_empty() { name } else { name + " " + &parameters };
+                    format!("#+BEGIN: {}\n{}\n#+END:", header, contents)
+                } else {
+                    self.warn(node, "dynamic block is written as its contents");
+                    contents
+                }
+            }
+            ASTType::Drawer => {
+                let name = match node.meta() {
+                    ASTMetaData::Drawer { name } => name.clone(),
+                    _ => String::new(),
+                };
+                if org {
+                    let contents = self.blocks(node);
+                    if contents.is_empty() {
+                        format!(":{}:\n:END:", name)
+                    } else {
+                        format!(":{}:\n{}\n:END:", name, contents)
+                    }
+                } else {
+                    self.warn(node, &format!("drawer :{}: is dropped", name));
+                    String::new()
+                }
+            }
+            ASTType::FootnoteDefinition => {
+                let label = match node.meta() {
+                    ASTMetaData::Footnote(meta) => meta.label.clone().unwrap_or_default(),
+                    _ => node.value().clone(),
+                };
+                let contents = self.indented(4.max(label.chars().count() + 5), |converter| converter.blocks(node));
+                if org {
+                    format!("[fn:{}] {}", label, contents)
+                } else {
+                    indent_rest(&format!("[^{}]: {}", label, contents), 4)
+                }
+            }
+            ASTType::HorizontalRule => if org { "-----" } else { "---" }.to_string(),
+            ASTType::Keyword => {
+                if org {
+                    node.raw_value().trim().to_string()
+                } else {
+                    let key = match node.meta() {
+                        ASTMetaData::Keyword { key, .. } => key.clone(),
+                        _ => String::new(),
+                    };
+                    self.warn(node, &format!("keyword #+{}: is dropped", key));
+                    String::new()
+                }
+            }
+            ASTType::Include => {
+                if !org && !children_of(node).is_empty() {
+                    return self.blocks(node);
+                }
+                self.org_only(node, "#+INCLUDE: is dropped")
+            }
+            ASTType::Planning => self.org_only(node, "planning line is dropped"),
+            ASTType::PropertyDrawer => self.org_only(node, "property drawer is dropped"),
+            ASTType::NodeProperty => self.org_only(node, "node property is dropped"),
+            ASTType::Clock => self.org_only(node, "clock line is dropped"),
+            ASTType::BabelCall => self.org_only(node, "#+CALL: is dropped"),
+            ASTType::LatexEnvironment => {
+                if !org {
+                    self.warn(node, "LaTeX environment is written as raw text");
+                }
+                node.raw_value().clone()
+            }
+            // 項目の見出し・表の行は親が書き出す
+            ASTType::ItemTag | ASTType::TableRow | ASTType::TableCell => self.inlines(node),
+            _ => self.inline(node),
+        }
+    }
+
+    // Org の記述をそのまま書き出す (Markdown では捨てる)
+    fn org_only(&mut self, node: NodeRef, message: &str) -> String {
+        if self.is_org() {
+            node.raw_value().clone()
+        } else {
+            self.warn(node, message);
+            String::new()
+        }
+    }
+
+    fn headline(&mut self, node: NodeRef) -> String {
+        let level = node.heading_level().unwrap_or(1);
+        let title = self.inlines(node);
+        let meta = match node.meta() {
+            ASTMetaData::Headline(meta) => meta.clone(),
+            _ => HeadlineMeta::default(),
+        };
+        let mut parts = vec![];
+        if self.is_org() {
+            parts.push("*".repeat(level));
+            parts.extend(meta.todo_keyword.clone());
+            parts.extend(meta.priority.as_ref().map(|priority| format!("[#{}]", priority)));
+            if meta.commented {
+                parts.push("COMMENT".to_string());
+            }
+            parts.push(title);
+            if !meta.tags.is_empty() {
+                parts.push(format!(":{}:", meta.tags.join(":")));
+            }
+        } else {
+            if level > 6 {
+                self.warn(node, "headline deeper than level 6 is written as level 6");
+            }
+            if meta.priority.is_some() || !meta.tags.is_empty() || meta.commented {
+                self.warn(node, "headline priority, tags and COMMENT are not representable in Markdown");
+            }
+            parts.push("#".repeat(level.min(6)));
+            parts.extend(meta.todo_keyword.clone());
+            // 末尾の # は閉じの記号と読まれる
+            parts.push(match title.strip_suffix('#') {
+                Some(rest) => rest.to_string() + "\\#",
+                None => title,
+            });
+        }
+        parts.retain(|part| !part.is_empty());
+        parts.join(" ")
+    }
+
+    /*
+     * リストの項目
+     * number: 番号付きリストの次の番号 (Markdown)
+     */
+    fn item(&mut self, node: NodeRef, number: &mut Option<u32>) -> String {
+        let meta = match node.meta() {
+            ASTMetaData::Item(meta) => meta.clone(),
+            _ => ItemMeta { bullet: "-".to_string(), ..Default::default() },
+        };
+        let ordered = meta.bullet.starts_with(|c: char| c.is_ascii_digit());
+        let mut bullet = if self.is_org() {
+            meta.bullet.clone()
+        } else if ordered {
+            let n = meta.counter.or(*number).unwrap_or(1);
+            *number = Some(n + 1);
+            format!("{}.", n)
+        } else {
+            "-".to_string()
+        };
+        let width = bullet.chars().count() + 1;
+        if self.is_org() {
+            if let Some(counter) = meta.counter {
+                bullet += &format!(" [@{}]", counter);
+            }
+        }
+        match meta.checkbox {
+            Some(Checkbox::On) => bullet += if self.is_org() { " [X]" } else { " [x]" },
+            Some(Checkbox::Off) => bullet += " [ ]",
+            Some(Checkbox::Trans) => {
+                if !self.is_org() {
+                    self.warn(node, "partially checked checkbox [-] is written as [ ]");
+                }
+                bullet += if self.is_org() { " [-]" } else { " [ ]" };
+            }
+            None => {}
+        }
+
+        let children = children_of(node);
+        if let Some(tag) = children.iter().find(|child| *child.node_type() == ASTType::ItemTag).copied() {
+            let tag = self.inlines(tag);
+            if self.is_org() {
+                bullet += &format!(" {} ::", tag);
+            } else {
+                self.warn(node, "description list item is written as a bold term");
+                bullet += &format!(" **{}**:", tag);
+            }
+        }
+
+        // 折り返しは一行目 (項目の記号の後ろ) に合わせる
+        let margin = bullet.chars().count() + 1;
+        let contents = self.indented(margin, |converter| {
+            let mut contents = String::new();
+            for &child in children.iter().filter(|child| *child.node_type() != ASTType::ItemTag) {
+                let text = converter.block(child);
+                if text.is_empty() {
+                    continue;
+                }
+                if !contents.is_empty() {
+                    contents.push_str(if *child.node_type() == ASTType::PlainList { "\n" } else { "\n\n" });
+                }
+                contents.push_str(&text);
+            }
+            contents
+        });
+        let text = if contents.is_empty() { bullet } else { bullet + " " + &contents };
+        indent_rest(&text, width)
+    }
+
+    fn table(&mut self, node: NodeRef) -> String {
+        let org = self.is_org();
+        // None は区切り線
+        let mut rows: Vec<Option<Vec<String>>> = vec![];
+        for row in children_of(node) {
+            match row.meta() {
+                ASTMetaData::TableRow { kind: TableRowKind::Rule } => rows.push(None),
+                ASTMetaData::TableRow { kind: TableRowKind::Special } if !org => {
+                    self.warn(row, "table row for column settings is dropped");
+                }
+                _ => {
+                    let mut cells: Vec<String> = children_of(row).into_iter().map(|cell| self.inlines(cell)).collect();
+                    if !org {
+                        cells = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+                    }
+                    rows.push(Some(cells));
+                }
+            }
+        }
+        if let ASTMetaData::Table(meta) = node.meta() {
+            if !org && !meta.formulas.is_empty() {
+                self.warn(node, "table formulas (#+TBLFM:) are dropped");
+            }
+        }
+
+        if !org {
+            // 見出し行の後ろにだけ区切り線を置く
+            let header_rule = rows.len() > 1 && rows[0].is_some() && rows[1].is_none();
+            if !header_rule {
+                self.warn(node, "the first row of the table is written as the header");
+            }
+            if rows.iter().skip(2).any(Option::is_none) {
+                self.warn(node, "table rules other than under the header are dropped");
+            }
+            let mut cells: Vec<Option<Vec<String>>> = rows.into_iter().flatten().map(Some).collect();
+            if !cells.is_empty() {
+                cells.insert(1, None);
+            }
+            rows = cells;
+        }
+
+        let columns = rows.iter().flatten().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|i| {
+                let width = rows.iter().flatten().filter_map(|row| row.get(i)).map(|cell| cell.chars().count()).max();
+                width.unwrap_or(0).max(if org { 1 } else { 3 })
+            })
+            .collect();
+        let lines: Vec<String> = rows
+            .iter()
+            .map(|row| match row {
+                Some(row) => {
+                    let cells: Vec<String> = widths
+                        .iter()
+                        .enumerate()
+                        .map(|(i, width)| {
+                            let cell = row.get(i).map(String::as_str).unwrap_or("");
+                            format!(" {}{} ", cell, " ".repeat(width - cell.chars().count()))
+                        })
+                        .collect();
+                    format!("|{}|", cells.join("|"))
+                }
+                None => {
+                    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+                    format!("|{}|", rule.join(if org { "+" } else { "|" }))
+                }
+            })
+            .collect();
+        let mut table = lines.join("\n");
+        if let (true, ASTMetaData::Table(meta)) = (org, node.meta()) {
+            if !meta.formulas.is_empty() {
+                table += &format!("\n#+TBLFM: {}", meta.formulas.join("::"));
+            }
+        }
+        table
+    }
+
+    /* ---------- オブジェクト ---------- */
+
+    // 改行の後ろの字下げは書き出す先で付け直す (詩のブロックは字下げを残す)
+    fn inlines(&mut self, node: NodeRef) -> String {
+        let children = children_of(node);
+        self.inline_run(&children, *node.node_type() == ASTType::VerseBlock)
+    }
+
+    fn inline_run(&mut self, children: &[NodeRef], keep_indent: bool) -> String {
+        let push = |result: &mut String, text: &str| {
+            if result.ends_with('\n') && !keep_indent {
+                result.push_str(text.trim_start_matches([' ', '\t']));
+            } else {
+                result.push_str(text);
+            }
+        };
+        let mut result = String::new();
+        // Markdown では続いたテキストをまとめて逃がす (== や単語の中の _ は前後を見て判断する)
+        let mut text = String::new();
+        for &child in children {
+            if *child.node_type() == ASTType::Text && !self.is_org() {
+                text.push_str(child.value());
+                continue;
+            }
+            if !text.is_empty() {
+                push(&mut result, &escape_markdown(&std::mem::take(&mut text)));
+            }
+            let inline = self.inline(child);
+            push(&mut result, &inline);
+        }
+        if !text.is_empty() {
+            push(&mut result, &escape_markdown(&text));
+        }
+        result
+    }
+
+    /*
+     * Markdown の段落
+     * 改行を空白にして options.width で折り返す (強制改行は残す)。行頭が記法と読まれる場合は \ で逃がす
+     */
+    fn markdown_paragraph(&mut self, node: NodeRef) -> String {
+        let children = children_of(node);
+        let mut segments: Vec<String> = children
+            .split(|child| *child.node_type() == ASTType::HardBreak)
+            .map(|segment| self.inline_run(segment, false))
+            .collect();
+        while segments.len() > 1 && segments.last().is_some_and(|segment| segment.trim().is_empty()) {
+            segments.pop();
+        }
+        let width = self.options.width.map(|width| width.saturating_sub(self.margin).max(1));
+        let segments: Vec<String> = segments
+            .iter()
+            .map(|segment| {
+                let lines = match width {
+                    Some(width) => wrap(segment, width),
+                    None => segment.split('\n').map(|line| line.trim_end().to_string()).collect(),
+                };
+                lines.iter().map(|line| escape_line_start(line)).collect::<Vec<String>>().join("\n")
+            })
+            .collect();
+        segments.join("\\\n")
+    }
+
+    // 子を markers で囲む
+    fn wrap(&mut self, node: NodeRef, open: &str, close: &str) -> String {
+        format!("{}{}{}", open, self.inlines(node), close)
+    }
+
+    fn inline(&mut self, node: NodeRef) -> String {
+        let org = self.is_org();
+        match node.node_type() {
+            ASTType::Text if org => node.value().clone(),
+            ASTType::Text => escape_markdown(node.value()),
+            ASTType::Emoji => node.value().clone(),
+            ASTType::SoftBreak => "\n".to_string(),
+            ASTType::HardBreak => if org { "\\\\\n" } else { "\\\n" }.to_string(),
+            ASTType::Emphasis => {
+                if org {
+                    self.wrap(node, "/", "/")
+                } else {
+                    self.wrap(node, "*", "*")
+                }
+            }
+            ASTType::Strong => {
+                if org {
+                    self.wrap(node, "*", "*")
+                } else {
+                    self.wrap(node, "**", "**")
+                }
+            }
+            ASTType::Underline => {
+                if org {
+                    self.wrap(node, "_", "_")
+                } else {
+                    self.warn(node, "underline is written as plain text");
+                    self.inlines(node)
+                }
+            }
+            ASTType::Strikethrough => {
+                if org {
+                    self.wrap(node, "+", "+")
+                } else {
+                    self.wrap(node, "~~", "~~")
+                }
+            }
+            ASTType::Highlight => {
+                if org {
+                    self.warn(node, "highlight is written as plain text");
+                    self.inlines(node)
+                } else {
+                    self.wrap(node, "==", "==")
+                }
+            }
+            ASTType::Superscript => {
+                if org {
+                    self.wrap(node, "^{", "}")
+                } else {
+                    self.wrap(node, "^", "^")
+                }
+            }
+            ASTType::Subscript => {
+                if org {
+                    self.wrap(node, "_{", "}")
+                } else {
+                    self.wrap(node, "~", "~")
+                }
+            }
+            ASTType::Verbatim | ASTType::Code => {
+                if org {
+                    let marker = if *node.node_type() == ASTType::Code { "~" } else { "=" };
+                    format!("{}{}{}", marker, node.value(), marker)
+                } else {
+                    let fence = backtick_fence(node.value(), 1);
+                    let padding = if node.value().starts_with('`') || node.value().ends_with('`') { " " } else { "" };
+                    format!("{}{}{}{}{}", fence, padding, node.value(), padding, fence)
+                }
+            }
+            ASTType::Link => self.link(node),
+            ASTType::Target => {
+                if org {
+                    format!("<<{}>>", node.value())
+                } else {
+                    self.warn(node, "link target <<...>> is dropped");
+                    String::new()
+                }
+            }
+            ASTType::RadioTarget => {
+                if org {
+                    node.raw_value().clone()
+                } else {
+                    self.warn(node, "radio target is written as plain text");
+                    node.value().clone()
+                }
+            }
+            ASTType::FootnoteReference => self.footnote_reference(node),
+            ASTType::Timestamp => {
+                if !org {
+                    self.warn(node, "timestamp is written as plain text");
+                }
+                node.raw_value().clone()
+            }
+            ASTType::StatisticsCookie => node.raw_value().clone(),
+            ASTType::LatexFragment => {
+                if !org {
+                    self.warn(node, "LaTeX fragment is written as raw text");
+                }
+                node.raw_value().clone()
+            }
+            ASTType::Entity => match node.meta() {
+                ASTMetaData::Entity(meta) if !org => meta.utf8.clone(),
+                _ => node.raw_value().clone(),
+            },
+            ASTType::Macro => {
+                if org {
+                    node.raw_value().clone()
+                } else if !children_of(node).is_empty() {
+                    self.inlines(node)
+                } else {
+                    self.warn(node, "undefined macro is written as raw text");
+                    node.raw_value().clone()
+                }
+            }
+            ASTType::InlineSrcBlock => {
+                if org {
+                    node.raw_value().clone()
+                } else {
+                    self.warn(node, "inline source block is written as inline code");
+                    let fence = backtick_fence(node.value(), 1);
+                    format!("{}{}{}", fence, node.value(), fence)
+                }
+            }
+            ASTType::InlineBabelCall => self.org_only(node, "inline call_ is dropped"),
+            _ => {
+                // オブジェクトの中に紛れた要素
+                let text = self.element(node);
+                text.replace('\n', " ")
+            }
+        }
+    }
+
+    fn link(&mut self, node: NodeRef) -> String {
+        let meta = match node.meta() {
+            ASTMetaData::Link(meta) => meta.clone(),
+            _ => return self.inlines(node),
+        };
+        let description = self.inlines(node);
+        // 電波リンクは本文の文字列
+        if meta.link_type == "radio" {
+            return if description.is_empty() { node.value().clone() } else { description };
+        }
+        let target = match meta.link_type.as_str() {
+            "custom-id" => format!("#{}", meta.path),
+            "coderef" => format!("({})", meta.path),
+            "fuzzy" => meta.path.clone(),
+            "file" if !org_file_needs_type(&meta.path) => meta.path.clone(),
+            link_type => format!("{}:{}", link_type, meta.path),
+        };
+        let target = match &meta.search_option {
+            Some(option) => format!("{}::{}", target, option),
+            None => target,
+        };
+
+        if self.is_org() {
+            if description.is_empty() {
+                format!("[[{}]]", target)
+            } else {
+                format!("[[{}][{}]]", target, description)
+            }
+        } else {
+            let url = match meta.link_type.as_str() {
+                "http" | "https" | "ftp" | "mailto" | "custom-id" => target,
+                "file" => meta.path.clone(),
+                _ => {
+                    self.warn(node, &format!("{} link is written as a plain URL", meta.link_type));
+                    target
+                }
+            };
+            if meta.search_option.is_some() {
+                self.warn(node, "search option of the file link is dropped");
+            }
+            let autolink = meta.format != LinkFormat::Bracket && description.is_empty() && *node.value() == url;
+            if autolink && !url.contains([' ', '\n']) {
+                return format!("<{}>", url);
+            }
+            let description = if description.is_empty() { escape_markdown(node.value()) } else { description };
+            // 空白は %20 に、括弧は \ で逃がす (<> で囲むと、折り返したときに <> の中に改行が入る)
+            let url = url.replace(' ', "%20").replace('(', "\\(").replace(')', "\\)");
+            format!("[{}]({})", description, url)
+        }
+    }
+
+    fn footnote_reference(&mut self, node: NodeRef) -> String {
+        let meta = match node.meta() {
+            ASTMetaData::Footnote(meta) => meta.clone(),
+            _ => FootnoteMeta::default(),
+        };
+        let definition = self.inlines(node);
+        if self.is_org() {
+            let label = meta.label.unwrap_or_default();
+            if meta.inline {
+                format!("[fn:{}:{}]", label, definition)
+            } else {
+                format!("[fn:{}]", label)
+            }
+        } else if meta.inline {
+            // 定義を文書の最後に移す
+            let label = meta.label.unwrap_or_else(|| format!("inline-{}", self.footnotes.len() + 1));
+            self.footnotes.push(format!("[^{}]: {}", label, definition));
+            format!("[^{}]", label)
+        } else {
+            format!("[^{}]", meta.label.unwrap_or_default())
+        }
+    }
+}
+
+/*
+ * Markdown で記法と読まれる文字を \ で逃がす
+ * 行頭だけで意味を持つ記号 (# > - 1.) は escape_line_start で扱う
+ */
+fn escape_markdown(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut result = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let previous = if i > 0 { chars.get(i - 1) } else { None };
+        let next = chars.get(i + 1);
+        let escape = match c {
+            '\\' | '`' | '*' | '[' | ']' | '<' | '~' | '^' => true,
+            // 単語の中の _ は強調にならない
+            '_' => !(previous.is_some_and(|c| c.is_alphanumeric()) && next.is_some_and(|c| c.is_alphanumeric())),
+            // ==highlight==
+            '=' => previous == Some(&'=') || next == Some(&'='),
+            // 文字参照 &amp; &#123;
+            '&' => {
+                let rest: String =
+                    chars[i + 1..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '#').collect();
+                !rest.is_empty() && chars.get(i + 1 + rest.chars().count()) == Some(&';')
+            }
+            _ => false,
+        };
+        if escape {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+// 行頭で見出し・引用・リスト・Setext 見出しの下線と読まれる行を逃がす
+fn escape_line_start(line: &str) -> String {
+    let first = line.split(' ').next().unwrap_or("");
+    let marker = |c: char| !first.is_empty() && first.chars().all(|x| x == c);
+    let digits = first.trim_end_matches(['.', ')']);
+    if (marker('#') && first.len() <= 6) || line.starts_with('>') || first == "-" || first == "+" {
+        return "\\".to_string() + line;
+    }
+    let ordered = !digits.is_empty() && digits.len() <= 9 && digits.chars().all(|c| c.is_ascii_digit());
+    if ordered && first.len() == digits.len() + 1 {
+        return format!("{}\\{}", digits, &line[digits.len()..]);
+    }
+    let rule = line.trim_end();
+    let underline = !rule.is_empty() && (rule.chars().all(|c| c == '=') || rule.chars().all(|c| c == '-' || c == ' '));
+    if underline {
+        return "\\".to_string() + line;
+    }
+    line.to_string()
+}
+
+/*
+ * 語の間で width 文字に折り返す (改行・空白の並びは一つの空白にする)
+ * 行頭に来ると記法と読まれる語の前では折り返さない
+ */
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut current = String::new();
+    for word in text.split([' ', '\n']).filter(|word| !word.is_empty()) {
+        if current.is_empty() {
+            current = word.to_string();
+        } else if current.chars().count() + 1 + word.chars().count() <= width || escape_line_start(word) != word {
+            current.push(' ');
+            current.push_str(word);
+        } else {
+            lines.push(std::mem::take(&mut current));
+            current = word.to_string();
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+// file: を付けないとファイルと読まれないパス
+fn org_file_needs_type(path: &str) -> bool {
+    !(path.starts_with('/') || path.starts_with("./") || path.starts_with("../") || path.starts_with("~/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md_parser::{md_parse, md_parse_with_options, MdParseOptions};
+    use crate::org_parser::org_parse;
+
+    fn messages(conversion: &Conversion) -> Vec<(ASTType, &str)> {
+        conversion.warnings.iter().map(|warning| (warning.node_type.clone(), warning.message.as_str())).collect()
+    }
+
+    #[test]
+    fn test_markdown_to_org() {
+        let src = "# Title\n\nSome *em* text ~~gone~~ ==mark==\n\n## Sub\n\nline one\nline two";
+        let options = MdParseOptions::all_extensions();
+        let conversion = to_org(md_parse_with_options(src, ASTNode::new(ASTElm::new_document()), options).root_node());
+        assert_eq!(conversion.text, "* Title\n\nSome /em/ text +gone+ mark\n\n** Sub\n\nline one\nline two\n");
+        assert_eq!(messages(&conversion), vec![(ASTType::Highlight, "highlight is written as plain text")]);
+        assert_eq!(conversion.warnings[0].range.begin.line(), 3);
+    }
+
+    const ORG: &str = "#+TITLE: Doc\n\
+                       * TODO [#A] Task :work:\n\
+                       SCHEDULED: <2024-01-05 Fri>\n\
+                       :PROPERTIES:\n\
+                       :ID: x\n\
+                       :END:\n\
+                       Some /em/, *strong*, =code= and [[https://example.com][a link]][fn:1].\n\
+                       Inline[fn::Inline note.] and _under_.\n\
+                       \n\
+                       - [X] done\n\
+                       - [ ] todo\n  - nested\n\
+                       \n\
+                       \n\
+                       1. first\n   continued\n\
+                       2. second\n\
+                       \n\
+                       #+BEGIN_SRC python -n :results output\n\
+                       print(1)\n\
+                       #+END_SRC\n\
+                       \n\
+                       | a | b |\n\
+                       |---+---|\n\
+                       | 1 | 2 |\n\
+                       \n\
+                       [fn:1] The note.\n";
+
+    #[test]
+    fn test_org_to_markdown() {
+        let conversion = to_markdown(org_parse(ORG, ASTNode::new(ASTElm::new_document())).root_node());
+        assert_eq!(
+            conversion.text,
+            "# TODO Task\n\
+             \n\
+             Some *em*, **strong**, `code` and [a link](https://example.com)[^1].\n\
+             Inline[^inline-1] and under.\n\
+             \n\
+             - [x] done\n\
+             - [ ] todo\n  - nested\n\
+             \n\
+             1. first\n   continued\n\
+             2. second\n\
+             \n\
+             ```python\n\
+             print(1)\n\
+             ```\n\
+             \n\
+             | a   | b   |\n\
+             |-----|-----|\n\
+             | 1   | 2   |\n\
+             \n\
+             [^1]: The note.\n\
+             \n\
+             [^inline-1]: Inline note.\n"
+        );
+        // 表せないものは警告にする
+        assert_eq!(
+            messages(&conversion),
+            vec![
+                (ASTType::Keyword, "keyword #+TITLE: is dropped"),
+                (ASTType::Headline, "headline priority, tags and COMMENT are not representable in Markdown"),
+                (ASTType::Planning, "planning line is dropped"),
+                (ASTType::PropertyDrawer, "property drawer is dropped"),
+                (ASTType::Underline, "underline is written as plain text"),
+                (ASTType::SrcBlock, "block switches and header arguments are not representable in Markdown"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_org_to_org() {
+        let conversion = to_org(org_parse(ORG, ASTNode::new(ASTElm::new_document())).root_node());
+        assert!(conversion.warnings.is_empty());
+        assert!(conversion.text.starts_with(
+            "#+TITLE: Doc\n\
+             \n\
+             * TODO [#A] Task :work:\n\
+             SCHEDULED: <2024-01-05 Fri>\n\
+             :PROPERTIES:\n\
+             :ID: x\n\
+             :END:\n\
+             \n\
+             Some /em/, *strong*, =code= and [[https://example.com][a link]][fn:1].\n\
+             Inline[fn::Inline note.] and _under_.\n"
+        ));
+        assert!(conversion.text.contains("\n#+BEGIN_SRC python -n :results output\nprint(1)\n#+END_SRC\n"));
+        assert!(conversion.text.ends_with("| a | b |\n|---+---|\n| 1 | 2 |\n\n[fn:1] The note.\n"));
+    }
+
+    #[test]
+    fn test_convert_blocks_and_links() {
+        let src = "#+CAPTION: Listing\n\
+                   #+BEGIN_EXAMPLE\n\
+                   * not a headline\n\
+                   #+END_EXAMPLE\n\
+                   \n\
+                   See [[#intro][intro]], [[./a.org::*Part]] and [[Somewhere]].\n\
+                   \n\
+                   - term :: description\n\
+                   \n\
+                   #+BEGIN_QUOTE\n\
+                   Quoted\n\
+                   \n\
+                   text\n\
+                   #+END_QUOTE";
+        let document = org_parse(src, ASTNode::new(ASTElm::new_document()));
+
+        let org = to_org(document.root_node());
+        assert!(org.text.starts_with("#+CAPTION: Listing\n#+BEGIN_EXAMPLE\n,* not a headline\n#+END_EXAMPLE\n"));
+
+        let markdown = to_markdown(document.root_node());
+        assert_eq!(
+            markdown.text,
+            "```\n* not a headline\n```\n\
+             \n\
+             See [intro](#intro), [./a.org::\\*Part](./a.org) and [Somewhere](Somewhere).\n\
+             \n\
+             - **term**: description\n\
+             \n\
+             > Quoted\n\
+             >\n\
+             > text\n"
+        );
+        assert_eq!(
+            messages(&markdown),
+            vec![
+                (ASTType::ExampleBlock, "affiliated keywords (#+NAME:, #+CAPTION:) are dropped"),
+                (ASTType::Link, "search option of the file link is dropped"),
+                (ASTType::Link, "fuzzy link is written as a plain URL"),
+                (ASTType::Item, "description list item is written as a bold term"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_escape() {
+        let src = "# Title #\n\
+                   \n\
+                   1. not a list\n\
+                   - not a list either\n\
+                   \n\
+                   2 * 3 = 6 == six, a_b and _c_ &amp; [x] <b> `code`\n\
+                   \n\
+                   ===\n\
+                   \n\
+                   > no quote";
+        let document = md_parse(src, ASTNode::new(ASTElm::new_document()));
+        let once = render_markdown(document.root_node(), &MarkdownOptions::default());
+        assert_eq!(
+            once,
+            "# Title \\#\n\
+             \n\
+             1\\. not a list\n\
+             \\- not a list either\n\
+             \n\
+             2 \\* 3 = 6 \\=\\= six, a_b and \\_c\\_ \\&amp; \\[x\\] \\<b> \\`code\\`\n\
+             \n\
+             \\=\\=\\=\n\
+             \n\
+             \\> no quote\n"
+        );
+        // エスケープは読み戻すと元の記号になるので、もう一度整形しても変わらない
+        let again = md_parse(&once, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(render_markdown(again.root_node(), &MarkdownOptions::default()), once);
+    }
+
+    #[test]
+    fn test_render_markdown_wrap() {
+        let options = MarkdownOptions { width: Some(20) };
+        let src = "A paragraph that is long enough to wrap - twice or more.\nSecond line.";
+        let document = md_parse(src, ASTNode::new(ASTElm::new_document()));
+        let once = render_markdown(document.root_node(), &options);
+        // リストの記号と読まれる "-" の前では折り返さない
+        assert_eq!(once, "A paragraph that is\nlong enough to wrap -\ntwice or more.\nSecond line.\n");
+        let again = md_parse(&once, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(render_markdown(again.root_node(), &options), once);
+
+        let src = "- an item whose text is long enough to wrap\n  - and a nested one that wraps as well";
+        let document = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            render_markdown(document.root_node(), &options),
+            "- an item whose text\n  is long enough to\n  wrap\n  - and a nested one\n    that wraps as\n    well\n"
+        );
+
+        // リンク先の空白では折り返さない
+        let src = "See [[./my long file name.org][the notes]] (v2).";
+        let document = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            render_markdown(document.root_node(), &options),
+            "See [the\nnotes](./my%20long%20file%20name.org)\n(v2).\n"
+        );
+        let document = org_parse("[[https://example.com/a_(b)][x]]", ASTNode::new(ASTElm::new_document()));
+        assert_eq!(render_markdown(document.root_node(), &options), "[x](https://example.com/a_\\(b\\))\n");
+    }
+}
@@ -0,0 +1,267 @@
+// 絵文字ショートコード(:smile:)からUnicodeへの変換テーブル
+// NOTE: 二分探索を行うので、ショートコードのバイト順でソートしておくこと
+
+static EMOJI_TABLE: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("alarm_clock", "⏰"),
+    ("angry", "😠"),
+    ("anguished", "😧"),
+    ("ant", "🐜"),
+    ("apple", "🍎"),
+    ("arrow_down", "⬇️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_up", "⬆️"),
+    ("astonished", "😲"),
+    ("baby", "👶"),
+    ("balloon", "🎈"),
+    ("bangbang", "‼️"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("bird", "🐦"),
+    ("birthday", "🎂"),
+    ("blush", "😊"),
+    ("book", "📖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("bow", "🙇"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bus", "🚌"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("car", "🚗"),
+    ("cat", "😺"),
+    ("checkered_flag", "🏁"),
+    ("cherries", "🍒"),
+    ("christmas_tree", "🎄"),
+    ("clap", "👏"),
+    ("clipboard", "📋"),
+    ("clock1", "🕐"),
+    ("closed_book", "📕"),
+    ("cloud", "☁️"),
+    ("coffee", "☕"),
+    ("computer", "💻"),
+    ("confetti_ball", "🎊"),
+    ("confounded", "😖"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("cookie", "🍪"),
+    ("cool", "🆒"),
+    ("cow", "🐮"),
+    ("crab", "🦀"),
+    ("cry", "😢"),
+    ("crying_cat_face", "😿"),
+    ("dart", "🎯"),
+    ("dash", "💨"),
+    ("date", "📅"),
+    ("disappointed", "😞"),
+    ("dizzy", "💫"),
+    ("dog", "🐶"),
+    ("dollar", "💵"),
+    ("dragon", "🐉"),
+    ("droplet", "💧"),
+    ("ear", "👂"),
+    ("earth_asia", "🌏"),
+    ("egg", "🥚"),
+    ("envelope", "✉️"),
+    ("exclamation", "❗"),
+    ("expressionless", "😑"),
+    ("eyes", "👀"),
+    ("facepunch", "👊"),
+    ("fearful", "😨"),
+    ("file_folder", "📁"),
+    ("fire", "🔥"),
+    ("fish", "🐟"),
+    ("fist", "✊"),
+    ("flushed", "😳"),
+    ("four_leaf_clover", "🍀"),
+    ("frog", "🐸"),
+    ("frowning", "😦"),
+    ("gem", "💎"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("grey_question", "❔"),
+    ("grimacing", "😬"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("guitar", "🎸"),
+    ("hammer", "🔨"),
+    ("hand", "✋"),
+    ("hankey", "💩"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("heavy_check_mark", "✔️"),
+    ("hourglass", "⌛"),
+    ("house", "🏠"),
+    ("hugs", "🤗"),
+    ("hushed", "😯"),
+    ("icecream", "🍦"),
+    ("imp", "👿"),
+    ("information_source", "ℹ️"),
+    ("innocent", "😇"),
+    ("jack_o_lantern", "🎃"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("kiss", "💋"),
+    ("kissing", "😗"),
+    ("kissing_heart", "😘"),
+    ("laughing", "😆"),
+    ("leaves", "🍃"),
+    ("lemon", "🍋"),
+    ("link", "🔗"),
+    ("lips", "👄"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("love_letter", "💌"),
+    ("mag", "🔍"),
+    ("mailbox", "📫"),
+    ("mask", "😷"),
+    ("memo", "📝"),
+    ("microphone", "🎤"),
+    ("moon", "🌔"),
+    ("mount_fuji", "🗻"),
+    ("muscle", "💪"),
+    ("musical_note", "🎵"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("no_entry", "⛔"),
+    ("no_good", "🙅"),
+    ("no_mouth", "😶"),
+    ("nose", "👃"),
+    ("notebook", "📓"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("open_mouth", "😮"),
+    ("package", "📦"),
+    ("page_facing_up", "📄"),
+    ("paperclip", "📎"),
+    ("partly_sunny", "⛅"),
+    ("pencil", "📝"),
+    ("pencil2", "✏️"),
+    ("penguin", "🐧"),
+    ("pensive", "😔"),
+    ("persevere", "😣"),
+    ("phone", "☎️"),
+    ("pig", "🐷"),
+    ("pill", "💊"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝️"),
+    ("poop", "💩"),
+    ("pray", "🙏"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rabbit", "🐰"),
+    ("rage", "😡"),
+    ("rainbow", "🌈"),
+    ("raised_hands", "🙌"),
+    ("ramen", "🍜"),
+    ("recycle", "♻️"),
+    ("relaxed", "☺️"),
+    ("relieved", "😌"),
+    ("rice", "🍚"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("rose", "🌹"),
+    ("runner", "🏃"),
+    ("sake", "🍶"),
+    ("satisfied", "😆"),
+    ("scream", "😱"),
+    ("see_no_evil", "🙈"),
+    ("seedling", "🌱"),
+    ("shit", "💩"),
+    ("shrug", "🤷"),
+    ("sleeping", "😴"),
+    ("sleepy", "😪"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("smiling_imp", "😈"),
+    ("smirk", "😏"),
+    ("snail", "🐌"),
+    ("snake", "🐍"),
+    ("snowflake", "❄️"),
+    ("snowman", "⛄"),
+    ("sob", "😭"),
+    ("soccer", "⚽"),
+    ("sparkles", "✨"),
+    ("speech_balloon", "💬"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("stuck_out_tongue", "😛"),
+    ("stuck_out_tongue_winking_eye", "😜"),
+    ("sunflower", "🌻"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("sushi", "🍣"),
+    ("sweat", "😓"),
+    ("sweat_drops", "💦"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("tea", "🍵"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("tiger", "🐯"),
+    ("tired_face", "😫"),
+    ("tomato", "🍅"),
+    ("tongue", "👅"),
+    ("trophy", "🏆"),
+    ("tulip", "🌷"),
+    ("turtle", "🐢"),
+    ("umbrella", "☔"),
+    ("unamused", "😒"),
+    ("unlock", "🔓"),
+    ("upside_down_face", "🙃"),
+    ("v", "✌️"),
+    ("warning", "⚠️"),
+    ("wave", "👋"),
+    ("weary", "😩"),
+    ("whale", "🐳"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("wolf", "🐺"),
+    ("worried", "😟"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("yum", "😋"),
+    ("zap", "⚡"),
+    ("zzz", "💤"),
+];
+
+// ショートコード(コロンを除いた名前)から絵文字を引く
+pub fn lookup_emoji(shortcode: &str) -> Option<&'static str> {
+    EMOJI_TABLE
+        .binary_search_by(|(name, _)| name.cmp(&shortcode))
+        .ok()
+        .map(|idx| EMOJI_TABLE[idx].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emoji_table_sorted() {
+        for pair in EMOJI_TABLE.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} must be placed before {}", pair[0].0, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_lookup_emoji() {
+        assert_eq!(lookup_emoji("smile"), Some("😄"));
+        assert_eq!(lookup_emoji("+1"), Some("👍"));
+        assert_eq!(lookup_emoji("not_an_emoji"), None);
+    }
+}
@@ -1,5 +1,23 @@
+pub mod arena;
 pub mod ast;
+pub mod convert;
+pub mod emoji;
+pub mod lossless;
 pub mod md_parser;
+pub mod org_affiliated;
+pub mod org_agenda;
+pub mod org_clock;
+pub mod org_document;
+pub mod org_element;
+pub mod org_entity;
+pub mod org_include;
+pub mod org_link;
+pub mod org_macro;
+pub mod org_parser;
+pub mod org_table;
+pub mod org_timestamp;
+pub mod transform;
+pub mod visit;
 
 #[cfg(test)]
 mod tests {
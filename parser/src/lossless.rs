@@ -0,0 +1,138 @@
+use crate::arena::{ASTArena, NodeId};
+use crate::ast::*;
+use crate::md_parser::md_parse;
+use crate::org_parser::org_parse;
+
+/*
+ * 具象構文モード
+ *
+ * 要素の範囲の隙間 (空行・見出しの記号・行末の空白など) を Trivia として要素に持たせ、
+ * to_source() で入力をそのまま再現できるようにする。
+ * 一つの要素だけを書き換え (replace_source) て、残りは元のまま書き出す用途に使う。
+ *
+ *   # Title\n\ntext  ->  <document>
+ *                          <header leading="" trailing="">
+ *                            <text leading="# " text="Title" />
+ *                          </header>
+ *                          <paragraph leading="\n\n"> <text text="text" /> </paragraph>
+ *                        </document>
+ *
+ * 文書は入力全体を持つ。範囲が親の中に収まらない要素と、#+INCLUDE: ・マクロの展開結果は
+ * 元の文字列を持たない (空の範囲として扱う)。
+ */
+pub fn attach_trivia(document: &mut ASTArena, source: &str) {
+    let chars: Vec<char> = source.chars().collect();
+    let root = document.root();
+    attach(document, root, &chars, 0, chars.len(), String::new());
+}
+
+pub fn md_parse_lossless(s: &str, node: ASTNode) -> ASTArena {
+    let mut document = md_parse(s, node);
+    attach_trivia(&mut document, s);
+    document
+}
+
+pub fn org_parse_lossless(s: &str, node: ASTNode) -> ASTArena {
+    let mut document = org_parse(s, node);
+    attach_trivia(&mut document, s);
+    document
+}
+
+// begin..end: 要素が持つ文字 (文字単位の位置)
+fn attach(document: &mut ASTArena, id: NodeId, chars: &[char], begin: usize, end: usize, leading: String) {
+    let slice = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+    let expanded = matches!(document.node(id).node_type(), ASTType::Include | ASTType::Macro);
+    let mut cursor = begin;
+    let mut has_children = false;
+    let children: Vec<NodeId> = document.children(id).collect();
+    for child in children {
+        has_children = true;
+        let range = *document.node(child).range();
+        let from = range.begin.pos() as usize;
+        let to = range.end.pos() as usize;
+        let fits = !expanded && cursor <= from && from <= to && to <= end;
+        let (from, to) = if fits { (from, to) } else { (cursor, cursor) };
+        attach(document, child, chars, from, to, slice(cursor, from));
+        cursor = to;
+    }
+    let trivia = if has_children {
+        Trivia { leading, text: String::new(), trailing: slice(cursor, end) }
+    } else {
+        Trivia { leading, text: slice(begin, end), trailing: String::new() }
+    };
+    document.data_mut(id).trivia = Some(trivia);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::NodeRef;
+
+    // 各要素の to_source() はその範囲の文字列と一致する (展開された中身を除く)
+    fn assert_ranges(node: NodeRef, chars: &[char]) {
+        let range = node.range();
+        let source: String = chars[range.begin.pos() as usize..range.end.pos() as usize].iter().collect();
+        assert_eq!(node.to_source(), source, "{:?}", node.node_type());
+        if !matches!(node.node_type(), ASTType::Include | ASTType::Macro) {
+            for child in node.children() {
+                assert_ranges(child, chars);
+            }
+        }
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let src = "#  Title  \n\n\n*emphasis*   and  text \nnext line  \n\n## Sub ##\n\n";
+        let document = md_parse_lossless(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(document.to_source(), src);
+
+        // 空行は次の要素の前に付く
+        let blocks: Vec<_> = document.root_node().children().collect();
+        assert_eq!(blocks[0].trivia().unwrap().leading, "");
+        assert_eq!(blocks[1].trivia().unwrap().leading, "\n\n\n");
+        assert_eq!(blocks[0].to_source(), "#  Title  ");
+        for child in document.root_node().children() {
+            assert_ranges(child, &src.chars().collect::<Vec<char>>());
+        }
+    }
+
+    #[test]
+    fn test_org_round_trip() {
+        let src = "#+TITLE: Doc\n\
+                   #+MACRO: greet Hello $1\n\
+                   \n\
+                   * TODO [#A] Task   :work:\n\
+                   SCHEDULED: <2024-01-05 Fri>\n\
+                   :PROPERTIES:\n\
+                   :ID: x\n\
+                   :END:\n\
+                   Some /em/ and {{{greet(you)}}}[fn:1].  \n\
+                   \n\
+                   - [X] done\n\
+                   - [ ] todo\n  - nested\n\
+                   \n\
+                   #+NAME: code\n\
+                   #+BEGIN_SRC python\n\
+                   print(1)\n\
+                   #+END_SRC\n\
+                   | a | b |\n\
+                   |---+---|\n\
+                   ** Sub\n\
+                   \n\
+                   [fn:1] The note.\n\n";
+        let document = org_parse_lossless(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(document.to_source(), src);
+        for child in document.root_node().children() {
+            assert_ranges(child, &src.chars().collect::<Vec<char>>());
+        }
+    }
+
+    #[test]
+    fn test_replace_source() {
+        let src = "# Title\n\nSome *emphasis* here.\n";
+        let mut document = md_parse_lossless(src, ASTNode::new(ASTElm::new_document()));
+        let emphasis = document.root_node().descendants().find(|node| *node.node_type() == ASTType::Emphasis);
+        document.replace_source(emphasis.unwrap().id(), "**strong**");
+        assert_eq!(document.to_source(), "# Title\n\nSome **strong** here.\n");
+    }
+}
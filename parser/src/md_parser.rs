@@ -1,22 +1,50 @@
 extern crate nom;
 
+use crate::arena::ASTArena;
 use crate::ast::*;
+use crate::emoji::lookup_emoji;
 
-use nom::branch::{alt, permutation};
-use nom::character::complete::{
-    alpha1, anychar, char, digit1, line_ending, multispace0, not_line_ending, space0, space1,
-};
-use nom::combinator::{cond, eof, map, not, peek};
+use nom::branch::alt;
+use nom::bytes::complete::take_while1;
+use nom::character::complete::{char, line_ending};
+use nom::combinator::{eof, map, not, peek};
 use nom::error::{ErrorKind, ParseError};
 use nom::multi::{many0, many1, many_m_n};
 use nom::sequence::{delimited, tuple};
-use nom::{AsChar, Err, IResult, InputIter, InputLength, InputTakeAtPosition, Slice};
+use nom::{AsChar, Err, IResult, InputIter, InputLength, Slice};
 use nom::Parser as NomTParser;
 use std::ops::RangeFrom;
-use std::rc::Rc;
 use std::cell::RefCell;
 
-use log::{info, trace, warn, debug};
+use log::debug;
+use serde::Deserialize;
+
+/*
+ * 拡張構文の有効・無効
+ * 既定ではすべて無効(commonmarkの範囲のみ解析する)
+ * JSON から読む場合、書かなかった項目は無効になる ({"emoji": true})
+ */
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MdParseOptions {
+    pub strikethrough: bool, // ~~strike~~
+    pub highlight: bool,     // ==highlight==
+    pub superscript: bool,   // ^sup^
+    pub subscript: bool,     // ~sub~
+    pub emoji: bool,         // :smile:
+}
+
+impl MdParseOptions {
+    pub fn all_extensions() -> Self {
+        MdParseOptions {
+            strikethrough: true,
+            highlight: true,
+            superscript: true,
+            subscript: true,
+            emoji: true,
+        }
+    }
+}
 
 struct Parser {
     current_pos: RefCell<ASTPos>,
@@ -24,16 +52,22 @@ struct Parser {
     tran_buff: RefCell<Vec<ASTPos>>,
     pos_lock: RefCell<bool>,
     pos_br: RefCell<bool>,
+    options: MdParseOptions,
 }
 
 impl Parser {
     fn new() -> Self {
+        Parser::with_options(MdParseOptions::default())
+    }
+
+    fn with_options(options: MdParseOptions) -> Self {
         Self {
             current_pos: RefCell::new(ASTPos::new(1,1,0)),
             previous_pos: RefCell::new(ASTPos::new(1,1,0)),
             tran_buff: RefCell::new(vec![]),
             pos_lock: RefCell::new(false),
             pos_br: RefCell::new(false),
+            options,
         }
     }
 
@@ -52,7 +86,7 @@ impl Parser {
 
     fn pos_begin_transaction(&self) {
         if !*self.pos_lock.borrow() {
-            self.tran_buff.borrow_mut().push( self.current_pos.borrow().clone() );
+            self.tran_buff.borrow_mut().push( *self.current_pos.borrow() );
 
             /* for debug */
             let depth = self.pos_tran_depth();
@@ -77,14 +111,9 @@ impl Parser {
             /* for debug */
             let depth = self.pos_tran_depth();
 
-            match self.tran_buff.borrow_mut().pop() {
-                Some(result) => { 
-                    self.current_pos.borrow().set_pos( result.pos() );
-                    self.current_pos.borrow().set_line( result.line() );
-                    self.current_pos.borrow().set_ch( result.ch() );
-                    debug!("{empty:>width$}transaction({}): ROLLBACK {:?}", depth, self.current_pos, width=depth*2, empty="");
-                },
-                None => {},
+            if let Some(result) = self.tran_buff.borrow_mut().pop() {
+                *self.current_pos.borrow_mut() = result;
+                debug!("{empty:>width$}transaction({}): ROLLBACK {:?}", depth, self.current_pos, width=depth*2, empty="");
             }
         }
     }
@@ -94,15 +123,15 @@ impl Parser {
             Some(begin) => {
                 let end = self.current_pos.borrow();
                 ASTRange {
-                    begin: begin.clone(),
-                    end: end.clone(),
+                    begin: *begin,
+                    end: *end,
                 }
             },
             None => {
                 let end = self.current_pos.borrow();
                 ASTRange {
                     begin: ASTPos::new(1,1,0),
-                    end: end.clone(),
+                    end: *end,
                 }
             },
         }
@@ -113,15 +142,15 @@ impl Parser {
             Some(begin) => {
                 let end = if *self.pos_br.borrow() { self.previous_pos.borrow() } else { self.current_pos.borrow() };
                 ASTRange {
-                    begin: begin.clone(),
-                    end: end.clone(),
+                    begin: *begin,
+                    end: *end,
                 }
             },
             None => {
                 let end = self.current_pos.borrow();
                 ASTRange {
                     begin: ASTPos::new(1,1,0),
-                    end: end.clone(),
+                    end: *end,
                 }
             },
         }
@@ -148,20 +177,10 @@ impl Parser {
 
     // -- pos counter --
     fn be_change_pos(&self) {
-        let pre_pos = self.previous_pos.borrow_mut();
-        pre_pos.set_line( self.current_pos.borrow().line() );
-        pre_pos.set_ch( self.current_pos.borrow().ch() );
-        pre_pos.set_pos( self.current_pos.borrow().pos() );
+        *self.previous_pos.borrow_mut() = *self.current_pos.borrow();
         *self.pos_br.borrow_mut() = false;
     }
 
-    fn increase_pos_n(&self, n: u32) {
-        if !*self.pos_lock.borrow() {
-            self.be_change_pos();
-            self.current_pos.borrow_mut().increase_pos_n(n);
-        }
-    }
-    
     fn increase_line_n(&self, n: u32) {
         if !*self.pos_lock.borrow() {
             self.be_change_pos();
@@ -177,10 +196,6 @@ impl Parser {
         }
     }
 
-    fn increase_pos(&self) {
-        self.increase_pos_n(1);
-    }
-
     fn increase_line(&self) {
         self.increase_line_n(1);
     }
@@ -189,10 +204,6 @@ impl Parser {
         self.increase_ch_n(1);
     }
 
-    fn pos_print_debug(&self) {
-        println!("{:?}", &self.current_pos);
-    }
-
     /*
      * パーサーごとに位置情報のロールバックをいちいち書いていられないので
      * ロールバックを簡素にするためにパーサーをラップして透過的なクロージャーを返す
@@ -276,16 +287,15 @@ impl Parser {
     fn decide_char_printable(&self) -> impl Fn(char) -> Result<char, String> {
         move |input| {
             let i = input as u32;
-            let mut r = false;
 
             // 制御文字の判定
-            r = 0x00 <= i && i <= 0x1F;
+            let mut r = i <= 0x1F;
             r = r || i == 0x7F; // delete
 
             // 半角スペースの判定
             r = r || i == 0x20;
 
-            if r == false {
+            if !r {
                 Ok(input)
             } else {
                 Err("not printable char".to_string())
@@ -357,18 +367,10 @@ impl Parser {
 
 // UTILITIES
 
-fn util_vecstr_to_str(s: Vec<&str>) -> String {
-    s.concat()
-}
-
 fn util_vecstring_to_string(s: Vec<String>) -> String {
     s.into_iter().collect()
 }
 
-fn util_vecchar_to_string(s: Vec<char>) -> String {
-    s.into_iter().collect()
-}
-
 impl Parser {
     /* ------- characters ------- */
     // コントロール文字、スペース・タブ・改行を除くUTF-8文字すべてを受けいれる
@@ -399,19 +401,12 @@ impl Parser {
             map(
                 alt((self.parse_tab(), self.parse_space(), self.parse_nc_char())),
                 |input_s: char| {
-                    let ret: String = input_s.to_string();
-                    return ret;
+                    input_s.to_string()
                 },
             )(s)
         }
     }
 
-    fn parse_blank_char(&self) -> impl Fn(&str) -> IResult<&str, char> + '_  {
-        move |s| {
-            alt((self.single_char(' '), self.single_char('\n')))(s)
-        }
-    }
-
     // 記号(ASCII)・コントロール文字除くUTF-8文字すべてを受けいれる
     // not-special-char
     // インライン書式のパース用→ *Empasis*
@@ -714,18 +709,6 @@ impl Parser {
         }
     }
 
-    fn parse_hard_break_node(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
-        move |s| {
-            match self.parse_hard_break()(s) {
-                Ok((remain, _)) => {
-                    let node = ASTNode::new(ASTElm::new_hardbreak( self.pos_get_range() ));
-                    Ok((remain, node))
-                }
-                Err(e) => Err(e),
-            }
-        }
-    }
-
     fn parse_break_or_eof(&self) -> impl Fn(&str) -> IResult<&str, String> + '_  {
         move |s| {
             alt((
@@ -735,12 +718,6 @@ impl Parser {
         }
     }
 
-    fn parse_char(&self) -> impl Fn(&str) -> IResult<&str, String> + '_  {
-        move |s| {
-            alt((self.parse_soft_break(), self.parse_nbr_char()))(s)
-        }
-    }
-
     fn parse_nbr_string(&self) -> impl Fn(&str) -> IResult<&str, String> + '_  {
         move |s| {
             map(many1(self.parse_nbr_char()), |input_s: Vec<String>| {
@@ -749,14 +726,6 @@ impl Parser {
         }
     }
 
-    fn parse_string(&self) -> impl Fn(&str) -> IResult<&str, String> + '_  {
-        move |s| {
-            map(many1(self.parse_char()), |input_s: Vec<String>| {
-                util_vecstring_to_string(input_s)
-            })(s)
-        }
-    }
-
     fn parse_nsp_string(&self) -> impl Fn(&str) -> IResult<&str, String> + '_  {
         move |s| {
             map(many1(self.parse_nsp_char()), |input_s: Vec<String>| {
@@ -786,10 +755,15 @@ impl Parser {
         move |s| {
             match alt(with_tran!(self,
                     map(self.parse_nsp_string(), |input_s: String| {
-                        let node = ASTNode::new( ASTElm::new_text( &input_s, self.pos_get_range() ));
-                        return node;
+                        ASTNode::new( ASTElm::new_text( &input_s, self.pos_get_range() ))
                     }),
-                    self.parse_emphasis()
+                    self.parse_escape(),
+                    self.parse_emphasis(),
+                    self.parse_strikethrough(),
+                    self.parse_subscript(),
+                    self.parse_superscript(),
+                    self.parse_highlight(),
+                    self.parse_emoji()
             ))(s)
             {
                 Ok((remain, node)) => Ok((remain, node)),
@@ -826,6 +800,160 @@ impl Parser {
         }
     }
 
+    /*
+     * 拡張構文で使う区切り文字列(~~, == など)
+     * 一文字ずつ読むことで位置情報を更新する
+     */
+    fn parse_delimiter(&self, delim: &'static str) -> impl Fn(&str) -> IResult<&str, &str> + '_ {
+        move |s| {
+            let mut remain = s;
+            for c in delim.chars() {
+                remain = self.single_char(c)(remain)?.0;
+            }
+            Ok((remain, s.slice(..s.len()-remain.len())))
+        }
+    }
+
+    /*
+     * 区切り文字で囲まれたインライン書式(拡張構文)
+     * 強調と同様に、区切り文字の内側に空白・改行が隣接する場合は受理しない
+     * allow_break: 書式の途中での改行(ソフトブレイク)を許容するか
+     * */
+    fn parse_delimited_inline(&self, open: &'static str, close: &'static str, allow_break: bool)
+        -> impl Fn(&str) -> IResult<&str, Vec<ASTNode>> + '_
+    {
+        move |s| {
+            let content = |i| {
+                if allow_break {
+                    alt(with_tran!(self, self.parse_inline_syntax(), self.parse_soft_break_node()))(i)
+                } else {
+                    with_tran!(self, self.parse_inline_syntax())(i)
+                }
+            };
+            with_tran!(self,
+                delimited(
+                    tuple((self.parse_delimiter(open), isolate!(self, peek(not(alt((self.parse_soft_break(),self.parse_space_string()))))))),
+                    many1(content),
+                    tuple((isolate!(self, peek(not(alt((self.parse_soft_break(),self.parse_space_string()))))), self.parse_delimiter(close))),
+                )
+            )(s)
+        }
+    }
+
+    // 打ち消し線 ~~strike~~
+    fn parse_strikethrough(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
+        move |s| {
+            if !self.options.strikethrough {
+                return Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char)));
+            }
+            match self.parse_delimited_inline("~~", "~~", true)(s) {
+                Ok((remain, nodes)) => {
+                    let mut node = ASTNode::new(ASTElm::new_strikethrough(
+                            "", s.slice(..s.len()-remain.len()), self.pos_get_range()
+                            ));
+                    node.append_node_from_vec(nodes);
+                    Ok((remain, node))
+                }
+                Err(_) => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
+    // ハイライト ==highlight==
+    fn parse_highlight(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
+        move |s| {
+            if !self.options.highlight {
+                return Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char)));
+            }
+            match self.parse_delimited_inline("==", "==", true)(s) {
+                Ok((remain, nodes)) => {
+                    let mut node = ASTNode::new(ASTElm::new_highlight(
+                            "", s.slice(..s.len()-remain.len()), self.pos_get_range()
+                            ));
+                    node.append_node_from_vec(nodes);
+                    Ok((remain, node))
+                }
+                Err(_) => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
+    // 上付き文字 ^sup^ (改行は含まない)
+    fn parse_superscript(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
+        move |s| {
+            if !self.options.superscript {
+                return Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char)));
+            }
+            match self.parse_delimited_inline("^", "^", false)(s) {
+                Ok((remain, nodes)) => {
+                    let mut node = ASTNode::new(ASTElm::new_superscript(
+                            "", s.slice(..s.len()-remain.len()), self.pos_get_range()
+                            ));
+                    node.append_node_from_vec(nodes);
+                    Ok((remain, node))
+                }
+                Err(_) => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
+    /*
+     * 下付き文字 ~sub~ (改行は含まない)
+     * ~~ は打ち消し線の区切りなので、下付き文字としては扱わない
+     * */
+    fn parse_subscript(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
+        move |s| {
+            if !self.options.subscript || s.starts_with("~~") {
+                return Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char)));
+            }
+            match self.parse_delimited_inline("~", "~", false)(s) {
+                Ok((remain, nodes)) => {
+                    let mut node = ASTNode::new(ASTElm::new_subscript(
+                            "", s.slice(..s.len()-remain.len()), self.pos_get_range()
+                            ));
+                    node.append_node_from_vec(nodes);
+                    Ok((remain, node))
+                }
+                Err(_) => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
+    // 絵文字ショートコードの名前部分(テーブルに存在するもののみ受理)
+    fn parse_emoji_shortcode(&self) -> impl Fn(&str) -> IResult<&str, &'static str> + '_  {
+        move |s| {
+            let (remain, name) = take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-')(s)?;
+            match lookup_emoji(name) {
+                Some(emoji) => {
+                    self.increase_ch_n(name.chars().count() as u32);
+                    Ok((remain, emoji))
+                }
+                None => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
+    // 絵文字 :smile:
+    fn parse_emoji(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
+        move |s| {
+            if !self.options.emoji {
+                return Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char)));
+            }
+            match with_tran!(self,
+                delimited(self.single_char(':'), self.parse_emoji_shortcode(), self.single_char(':'))
+            )(s)
+            {
+                Ok((remain, emoji)) => {
+                    let node = ASTNode::new(ASTElm::new_emoji(
+                            emoji, s.slice(..s.len()-remain.len()), self.pos_get_range()
+                            ));
+                    Ok((remain, node))
+                }
+                Err(_) => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
     /*
      * パラグラフ中のアスタリスクのパース
      * (強調構文から漏れたアスタリスクの処理)
@@ -833,8 +961,7 @@ impl Parser {
     fn parse_sp_asterisk(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_  {
         move |s| {
             match map(self.parse_asterisk_char(), |input_s: char| {
-                let node = ASTNode::new(ASTElm::new_text( &input_s.to_string(), self.pos_get_range() ));
-                return node;
+                ASTNode::new(ASTElm::new_text( &input_s.to_string(), self.pos_get_range() ))
             })(s)
             {
                 Ok((remain, node)) => Ok((remain, node)),
@@ -843,14 +970,30 @@ impl Parser {
         }
     }
 
+    /*
+     * バックスラッシュエスケープ (\* \# など)
+     * 記号そのものをテキストにする
+     */
+    fn parse_escape(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            match with_tran!(self, tuple((self.single_char('\\'), self.parse_sp_char())))(s) {
+                Ok((remain, (_, c))) => {
+                    let mut node = ASTNode::new(ASTElm::new_text( &c.to_string(), self.pos_get_range() ));
+                    node.set_raw_value(s.slice(..s.len()-remain.len()).to_string());
+                    Ok((remain, node))
+                }
+                Err(_) => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char))),
+            }
+        }
+    }
+
     /*
      * 構文上意味がない漏れてきた記号のパース
      */
     fn parse_sp_symbol(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
         move |s| {
             match map(self.parse_sp_char(), |input_s: char| {
-                let node = ASTNode::new(ASTElm::new_text( &input_s.to_string(), self.pos_get_range() ));
-                return node;
+                ASTNode::new(ASTElm::new_text( &input_s.to_string(), self.pos_get_range() ))
             })(s)
             {
                 Ok((remain, node)) => Ok((remain, node)),
@@ -877,7 +1020,7 @@ impl Parser {
                     map(isolate!(self, self.parse_nbr_string()), |input_s: String| {
                         many1(self.parse_inline())(&input_s).unwrap().1
                     }),
-                    many0((self.single_char('#'))),
+                    many0(self.single_char('#')),
                     isolate!(self, peek(self.parse_break_or_eof())),
             )))(s);
             match r {
@@ -905,7 +1048,7 @@ impl Parser {
             match map(isolate!(self, self.parse_separate()), |input_s: String| {
                 ( input_s.to_owned(), many1(alt((
                         self.parse_inline(),
-                        self.parse_soft_break_node(),
+                        with_tran!(self, self.parse_soft_break_node()),
                         self.parse_sp_asterisk(),
                 )))(&input_s).unwrap().1 )
             })(s) {
@@ -995,21 +1138,24 @@ impl Parser {
         node.set_value("".to_string());
         node.set_raw_value(s.to_string());
 
-        match with_tran!(self, many0(with_tran!(self, self.parse_blocks())))(s) {
-            Ok((remain, result)) => {
-                node.append_node_from_vec(result);
-                node.set_range( self.pos_get_range() );
-            }
-            Err(_) => {}
+        if let Ok((_, result)) = with_tran!(self, many0(with_tran!(self, self.parse_blocks())))(s) {
+            node.append_node_from_vec(result);
+            node.set_range( self.pos_get_range() );
         }
 
-        return node;
+        node
     }
 }
 
-pub fn md_parse(s: &str, node: ASTNode) -> ASTNode {
+// 文書は ASTArena で返す (node は根になる要素)
+pub fn md_parse(s: &str, node: ASTNode) -> ASTArena {
     let parser = Parser::new();
-    parser.parse_document(s, node)
+    ASTArena::new(parser.parse_document(s, node))
+}
+
+pub fn md_parse_with_options(s: &str, node: ASTNode, options: MdParseOptions) -> ASTArena {
+    let parser = Parser::with_options(options);
+    ASTArena::new(parser.parse_document(s, node))
 }
 
 #[cfg(test)]
@@ -1133,6 +1279,17 @@ mod tests {
         assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(2,10,22)));
     }
 
+    #[test]
+    fn test_parse_paragraph_soft_break_range(){
+        // ソフトブレイクの範囲は改行だけを指す (段落の先頭からにはならない)
+        let parser = Parser::new();
+        let node = parser.parse_paragraph()("this is text\nmultiline").unwrap().1;
+        let ranges: Vec<(u32, u32)> = node.children().iter()
+            .map(|child| (child.range().begin.pos(), child.range().end.pos()))
+            .collect();
+        assert_eq!(ranges, vec![(0, 12), (12, 13), (13, 22)]);
+    }
+
     #[test]
     fn test_parse_paragraph_multiline_other_block(){
         let parser = Parser::new();
@@ -1176,4 +1333,98 @@ mod tests {
         );
 
     }
+
+    #[test]
+    fn test_parse_extensions_disabled(){
+        let parser = Parser::new();
+        assert_eq!(parser.parse_paragraph()("~~a~~ ==b== ^c^ ~d~ :smile:").unwrap().1.render_debug_format(),
+        "<paragraph><text>~</text><text>~</text><text>a</text><text>~</text><text>~</text><text> </text><text>=</text><text>=</text><text>b</text><text>=</text><text>=</text><text> </text><text>^</text><text>c</text><text>^</text><text> </text><text>~</text><text>d</text><text>~</text><text> </text><text>:</text><text>smile</text><text>:</text></paragraph>"
+        );
+    }
+
+    #[test]
+    fn test_parse_strikethrough_and_subscript(){
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(1,1,0)));
+
+        assert_eq!(parser.parse_strikethrough()("~~strike~~").unwrap().1.render_debug_format(),
+        "<strikethrough><text>strike</text></strikethrough>");
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(1,11,10)));
+
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.parse_paragraph()("H~2~O is ~~not~~ water").unwrap().1.render_debug_format(),
+        "<paragraph><text>H</text><subscript><text>2</text></subscript><text>O is </text><strikethrough><text>not</text></strikethrough><text> water</text></paragraph>"
+        );
+
+        // ~~ は下付き文字として扱わない
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.parse_subscript()("~~strike~~"),
+        Err(Err::Error(Error::new("~~strike~~", ErrorKind::Char ))));
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(1,1,0)));
+    }
+
+    #[test]
+    fn test_parse_superscript(){
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.parse_paragraph()("2^10^ is 1024").unwrap().1.render_debug_format(),
+        "<paragraph><text>2</text><superscript><text>10</text></superscript><text> is 1024</text></paragraph>"
+        );
+
+        // 上付き文字は改行を含まない
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.parse_superscript()("^a\nb^"),
+        Err(Err::Error(Error::new("^a\nb^", ErrorKind::Char ))));
+        assert_eq!(parser.parse_superscript()("^ a^"),
+        Err(Err::Error(Error::new("^ a^", ErrorKind::Char ))));
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(1,1,0)));
+    }
+
+    #[test]
+    fn test_parse_highlight(){
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.parse_highlight()("==mark *this*\nline==").unwrap().1.render_debug_format(),
+        "<highlight><text>mark </text><emphasis><text>this</text></emphasis><softbreak /><text>line</text></highlight>");
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(2,7,20)));
+    }
+
+    #[test]
+    fn test_parse_emoji(){
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        let node = parser.parse_emoji()(":smile:").unwrap().1;
+        assert_eq!(node.render_debug_format(), "<emoji>😄</emoji>");
+        assert_eq!(node.raw_value(), ":smile:");
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(1,8,7)));
+
+        // テーブルに無いショートコードは記号として扱う
+        let parser = Parser::with_options(MdParseOptions::all_extensions());
+        assert_eq!(parser.parse_paragraph()("time 10:30:00 :+1:").unwrap().1.render_debug_format(),
+        "<paragraph><text>time 10</text><text>:</text><text>30</text><text>:</text><text>00 </text><emoji>👍</emoji></paragraph>"
+        );
+    }
+
+    #[test]
+    fn test_parse_options_from_json(){
+        // 書かなかった拡張は無効のまま
+        let options: MdParseOptions = serde_json::from_str(r#"{"emoji": true, "strikethrough": true}"#).unwrap();
+        assert_eq!(options, MdParseOptions { emoji: true, strikethrough: true, ..Default::default() });
+        let node = md_parse_with_options("~~a~~ ==b== :smile:", ASTNode::new( ASTElm::new_document() ), options);
+        assert_eq!(node.render_debug_format(),
+        "<document><paragraph><strikethrough><text>a</text></strikethrough><text> </text><text>=</text><text>=</text><text>b</text><text>=</text><text>=</text><text> </text><emoji>😄</emoji></paragraph></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_escape(){
+        let parser = Parser::new();
+        let node = parser.parse_escape()("\\*").unwrap().1;
+        assert_eq!(node.render_debug_format(), "<text>*</text>");
+        assert_eq!(node.raw_value(), "\\*");
+        assert_eq!(parser.pos_get_range(), ASTRange::new( ASTPos::new(1,1,0), ASTPos::new(1,3,2)));
+
+        // エスケープした記号は強調にならない
+        let parser = Parser::new();
+        assert_eq!(parser.parse_paragraph()("\\*a\\* \\a").unwrap().1.render_debug_format(),
+        "<paragraph><text>*</text><text>a</text><text>*</text><text> </text><text>\\</text><text>a</text></paragraph>"
+        );
+    }
 }
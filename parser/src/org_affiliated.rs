@@ -0,0 +1,202 @@
+use crate::arena::{ASTArena, NodeId, NodeRef};
+use crate::ast::*;
+use crate::org_parser::split_parameters;
+
+use std::collections::BTreeMap;
+
+// 直後の要素に付くキーワード (ATTR_ で始まるものを含む)
+const AFFILIATED_KEYWORDS: &[&str] = &["CAPTION", "HEADER", "NAME", "RESULTS"];
+
+pub fn is_affiliated_keyword(key: &str) -> bool {
+    AFFILIATED_KEYWORDS.contains(&key) || key.starts_with("ATTR_")
+}
+
+/*
+ * 付加キーワードを直後の要素の AffiliatedMeta にする
+ * 要素の直前の行に続けて書かれたものが対象。空行を挟む場合や、直後が見出し・キーワードなど
+ * 付加キーワードを持てない要素の場合はキーワードのまま残す。
+ * 付けたキーワードは子の並びから取り除く (記述は AffiliatedMeta.raw_value に残す)
+ */
+pub fn attach_affiliated_keywords(document: &mut ASTArena) {
+    let parents: Vec<NodeId> = document.pre_order(document.root()).collect();
+    for parent in parents {
+        let mut keywords: Vec<NodeId> = vec![];
+        let children: Vec<NodeId> = document.children(parent).collect();
+        for child in children {
+            let node = document.node(child);
+            let adjacent = keywords
+                .last()
+                .is_some_and(|&last| document.node(last).range().end.line() + 1 == node.range().begin.line());
+            if !adjacent {
+                keywords.clear();
+            }
+            match node.meta() {
+                ASTMetaData::Keyword { key, .. } if is_affiliated_keyword(key) => keywords.push(child),
+                _ if !keywords.is_empty() && can_have_affiliated(node) => {
+                    let affiliated = build_affiliated(keywords.iter().map(|&keyword| document.node(keyword)).collect());
+                    document.data_mut(child).affiliated = Some(affiliated);
+                    for keyword in keywords.drain(..) {
+                        document.detach(keyword);
+                    }
+                }
+                _ => keywords.clear(),
+            }
+        }
+    }
+}
+
+fn can_have_affiliated(node: NodeRef) -> bool {
+    !matches!(
+        node.node_type(),
+        ASTType::Headline
+            | ASTType::Section
+            | ASTType::Planning
+            | ASTType::PropertyDrawer
+            | ASTType::NodeProperty
+            | ASTType::Clock
+            | ASTType::Keyword
+            | ASTType::Include
+            | ASTType::Item
+    )
+}
+
+fn build_affiliated(keywords: Vec<NodeRef>) -> AffiliatedMeta {
+    let mut meta = AffiliatedMeta::default();
+    for &keyword in &keywords {
+        let (key, option) = match keyword.meta() {
+            ASTMetaData::Keyword { key, option } => (key.as_str(), option.clone()),
+            _ => continue,
+        };
+        let value = keyword.value().trim();
+        match key {
+            "NAME" => meta.name = Some(value.to_string()),
+            // 複数行の CAPTION は空白で繋ぐ
+            "CAPTION" => {
+                meta.caption = Some(match meta.caption.take() {
+                    Some(caption) => caption + " " + value,
+                    None => value.to_string(),
+                });
+                if option.is_some() {
+                    meta.short_caption = option;
+                }
+            }
+            "HEADER" => meta.headers.push(value.to_string()),
+            "RESULTS" => {
+                let name = Some(value.to_string()).filter(|name| !name.is_empty());
+                meta.results = Some(ResultsMeta { hash: option, name, source: None });
+            }
+            _ => {
+                let backend = key.trim_start_matches("ATTR_").to_lowercase();
+                let attributes = meta.attributes.entry(backend).or_default();
+                let mut current: Option<String> = None;
+                for token in split_parameters(value) {
+                    if let Some(name) = token.strip_prefix(':') {
+                        attributes.insert(name.to_string(), String::new());
+                        current = Some(name.to_string());
+                    } else if let Some(entry) = current.as_ref().and_then(|name| attributes.get_mut(name)) {
+                        if !entry.is_empty() {
+                            entry.push(' ');
+                        }
+                        entry.push_str(token.trim_matches('"'));
+                    }
+                }
+            }
+        }
+    }
+    let raw_values: Vec<&str> = keywords.iter().map(|keyword| keyword.raw_value().as_str()).collect();
+    meta.raw_value = raw_values.join("\n");
+    if let (Some(first), Some(last)) = (keywords.first(), keywords.last()) {
+        meta.range = ASTRange::new(first.range().begin, last.range().end);
+    }
+    meta
+}
+
+/*
+ * #+RESULTS: を出力元に結び付ける (ResultsMeta.source)
+ *   #+RESULTS: name -> #+NAME: name のソースブロック・#+CALL:
+ *   #+RESULTS:      -> 直前の要素 (ソースブロック・#+CALL: の場合)
+ */
+pub fn link_results(document: &mut ASTArena) {
+    let mut sources = BTreeMap::new();
+    for node in document.root_node().descendants() {
+        if let Some(name) = node.affiliated().and_then(|affiliated| affiliated.name.as_ref()) {
+            if is_results_source(node) && !sources.contains_key(name) {
+                sources.insert(name.clone(), *node.range());
+            }
+        }
+    }
+
+    let parents: Vec<NodeId> = document.pre_order(document.root()).collect();
+    for parent in parents {
+        let mut previous: Option<ASTRange> = None;
+        let children: Vec<NodeId> = document.children(parent).collect();
+        for child in children {
+            if let Some(affiliated) = document.data_mut(child).affiliated.as_mut() {
+                if let Some(results) = affiliated.results.as_mut() {
+                    results.source = match &results.name {
+                        Some(name) => sources.get(name).cloned(),
+                        None => previous,
+                    };
+                }
+            }
+            let node = document.node(child);
+            previous = Some(*node.range()).filter(|_| is_results_source(node));
+        }
+    }
+}
+
+fn is_results_source(node: NodeRef) -> bool {
+    matches!(node.node_type(), ASTType::SrcBlock | ASTType::BabelCall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org_parser::org_parse;
+
+    #[test]
+    fn test_attach_affiliated_keywords() {
+        let src = "#+NAME: fig:cat\n\
+                   #+CAPTION[Cat]: A cat\n\
+                   #+CAPTION: on a mat.\n\
+                   #+ATTR_HTML: :width 300 :alt a \"black cat\"\n\
+                   [[./cat.png]]\n\
+                   \n\
+                   #+NAME: alone\n\
+                   \n\
+                   #+NAME: table\n\
+                   #+TITLE: Not an element\n\
+                   | a |";
+        let document = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let children: Vec<(String, Option<AffiliatedMeta>)> = document
+            .root_node()
+            .children()
+            .map(|child| (child.render_debug_format(), child.affiliated().cloned()))
+            .collect();
+        assert_eq!(children.len(), 5);
+
+        let (paragraph, affiliated) = &children[0];
+        assert_eq!(paragraph, "<paragraph><link>./cat.png</link></paragraph>");
+        let affiliated = affiliated.as_ref().unwrap();
+        assert_eq!(affiliated.name.as_deref(), Some("fig:cat"));
+        assert_eq!(affiliated.caption.as_deref(), Some("A cat on a mat."));
+        assert_eq!(affiliated.short_caption.as_deref(), Some("Cat"));
+        let html = &affiliated.attributes["html"];
+        assert_eq!((html["width"].as_str(), html["alt"].as_str()), ("300", "a black cat"));
+        assert_eq!((affiliated.range.begin.line(), affiliated.range.end.line()), (1, 4));
+        assert!(affiliated.raw_value.starts_with("#+NAME: fig:cat\n#+CAPTION[Cat]: A cat\n"));
+
+        // 空行を挟むもの、直後がキーワードのものはキーワードのまま
+        let rest: Vec<(&str, bool)> =
+            children[1..].iter().map(|(rendered, affiliated)| (rendered.as_str(), affiliated.is_some())).collect();
+        assert_eq!(
+            rest,
+            vec![
+                ("<keyword>alone</keyword>", false),
+                ("<keyword>table</keyword>", false),
+                ("<keyword>Not an element</keyword>", false),
+                ("<table><row><cell><text>a</text></cell></row></table>", false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use crate::arena::{ASTArena, NodeRef};
+use crate::ast::*;
+
+use serde::Serialize;
+
+/*
+ * アジェンダ (org-agenda の日・週表示に相当)
+ *
+ * 複数の文書の見出しから、期間内の各日に表示する項目を集める。
+ *   SCHEDULED:  予定日に表示。未完了のまま過ぎたものは今日に表示 (Sched.2x:)
+ *   DEADLINE:   期限日に表示。警告期間 (-3d、既定は 14 日) に入ったものと期限切れは今日に表示
+ *   <timestamp> 見出し・本文のアクティブなタイムスタンプ。日付の範囲は各日に表示
+ * 繰り返し (+1w など) は期間内のすべての回を表示する。完了した見出しは当日のみ表示する。
+ * COMMENT の見出し、:ARCHIVE: タグの見出しとその配下は対象外。
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgendaOptions {
+    pub start: OrgDate,
+    pub span: u32,                  // 日数 (1: 日, 7: 週)
+    pub today: OrgDate,             // 予定超過・期限切れ・期限の予告を表示する日
+    pub todo_keywords: Vec<String>, // この TODO キーワードを持つ見出しのみ (空ならすべて)
+    pub skip_done: bool,            // 完了した見出しを表示しない
+    pub deadline_warning_days: u32, // 警告期間を指定していない期限の既定値
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum AgendaItemKind {
+    Deadline,
+    Scheduled,
+    Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgendaItem {
+    pub kind: AgendaItemKind,
+    pub date: OrgDate,                 // タイムスタンプの日付 (繰り返しはその回の日付)
+    pub days: i64,                     // 表示する日からの日数 (期限の予告は正、予定超過・期限切れは負)
+    pub time: Option<OrgTime>,
+    pub end_time: Option<OrgTime>,
+    pub range_day: Option<(u32, u32)>, // 日付の範囲の何日目か (1/3)
+    pub title: String,
+    pub todo_keyword: Option<String>,
+    pub todo_type: Option<TodoType>,
+    pub priority: Option<String>,
+    pub tags: Vec<String>,             // 継承したタグと #+FILETAGS: を含む
+    pub category: Option<String>,      // CATEGORY プロパティ (継承する) または #+CATEGORY:
+    pub document: usize,               // 何番目の文書か
+    pub range: ASTRange,               // 見出しの範囲
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgendaDay {
+    pub date: OrgDate,
+    pub items: Vec<AgendaItem>, // 時刻のあるものを時刻順に先に並べ、残りは期限・予定・タイムスタンプの順
+}
+
+impl AgendaOptions {
+    pub fn new(start: OrgDate, span: u32) -> Self {
+        AgendaOptions {
+            start,
+            span,
+            today: start,
+            todo_keywords: vec![],
+            skip_done: false,
+            deadline_warning_days: 14,
+        }
+    }
+}
+
+pub fn agenda(documents: &[ASTArena], options: &AgendaOptions) -> Vec<AgendaDay> {
+    let mut days: Vec<AgendaDay> = (0..options.span as i64)
+        .map(|i| AgendaDay { date: options.start.add_days(i), items: vec![] })
+        .collect();
+    for (index, document) in documents.iter().enumerate() {
+        let document = document.root_node();
+        let (tags, category) = match document.meta() {
+            ASTMetaData::Document(meta) => (meta.filetags.clone(), meta.category.clone()),
+            _ => (vec![], None),
+        };
+        let context = Context { options, document: index, tags, category };
+        collect_sections(document, &context, &mut days);
+    }
+    for day in days.iter_mut() {
+        day.items.sort_by_key(|item| (item.time.is_none(), item.time, item.kind as u32));
+    }
+    days
+}
+
+// 見出しから継承するもの
+struct Context<'a> {
+    options: &'a AgendaOptions,
+    document: usize,
+    tags: Vec<String>,
+    category: Option<String>,
+}
+
+fn collect_sections(node: NodeRef, context: &Context, days: &mut [AgendaDay]) {
+    for child in node.children() {
+        if child.node_type() == &ASTType::Section {
+            collect_section(child, context, days);
+        }
+    }
+}
+
+fn collect_section(section: NodeRef, context: &Context, days: &mut [AgendaDay]) {
+    let headline = match section.first_child() {
+        Some(headline) => headline,
+        None => return,
+    };
+    let meta = match headline.meta() {
+        // COMMENT の見出し、:ARCHIVE: の見出しは配下を含めて表示しない
+        ASTMetaData::Headline(meta) if !meta.commented && !meta.tags.iter().any(|tag| tag == "ARCHIVE") => {
+            meta.clone()
+        }
+        _ => return,
+    };
+    let mut tags = context.tags.clone();
+    for tag in &meta.tags {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    let category = meta.properties.get("CATEGORY").cloned().or_else(|| context.category.clone());
+    let inner = Context { options: context.options, document: context.document, tags, category };
+
+    let options = context.options;
+    let selected = options.todo_keywords.is_empty()
+        || meta.todo_keyword.as_ref().is_some_and(|keyword| options.todo_keywords.contains(keyword));
+    let done = meta.todo_type == Some(TodoType::Done);
+    if selected && !(done && options.skip_done) {
+        let item = |kind: AgendaItemKind, timestamp: &TimestampMeta, date: OrgDate, days: i64| AgendaItem {
+            kind,
+            date,
+            days,
+            time: timestamp.time,
+            end_time: timestamp.end_time.filter(|_| timestamp.end_date == Some(timestamp.date)),
+            range_day: None,
+            title: headline.value().to_string(),
+            todo_keyword: meta.todo_keyword.clone(),
+            todo_type: meta.todo_type.clone(),
+            priority: meta.priority.clone(),
+            tags: inner.tags.clone(),
+            category: inner.category.clone(),
+            document: context.document,
+            range: *headline.range(),
+        };
+
+        for day in days.iter_mut() {
+            let today = day.date == options.today;
+            if let Some(scheduled) = &meta.planning.scheduled {
+                if occurs(scheduled, &day.date) {
+                    day.items.push(item(AgendaItemKind::Scheduled, scheduled, day.date, 0));
+                } else if today && !done && scheduled.date < day.date {
+                    let days = scheduled.date.to_days() - day.date.to_days();
+                    day.items.push(item(AgendaItemKind::Scheduled, scheduled, scheduled.date, days));
+                }
+            }
+            if let Some(deadline) = &meta.planning.deadline {
+                let days = deadline.date.to_days() - day.date.to_days();
+                let warning = match deadline.delay {
+                    Some(delay) => interval_days(delay.value, delay.unit),
+                    None => options.deadline_warning_days as i64,
+                };
+                if occurs(deadline, &day.date) {
+                    day.items.push(item(AgendaItemKind::Deadline, deadline, day.date, 0));
+                } else if today && !done && (days < 0 || days <= warning) {
+                    day.items.push(item(AgendaItemKind::Deadline, deadline, deadline.date, days));
+                }
+            }
+        }
+
+        let mut timestamps = vec![];
+        collect_timestamps(section, &mut timestamps);
+        for timestamp in &timestamps {
+            for day in days.iter_mut() {
+                match timestamp.end_date.filter(|end_date| end_date > &timestamp.date) {
+                    // 日付の範囲 <2024-01-02>--<2024-01-04>
+                    Some(end_date) if timestamp.date <= day.date && day.date <= end_date => {
+                        let nth = (day.date.to_days() - timestamp.date.to_days()) as u32 + 1;
+                        let total = (end_date.to_days() - timestamp.date.to_days()) as u32 + 1;
+                        let mut range_item = item(AgendaItemKind::Timestamp, timestamp, timestamp.date, 0);
+                        range_item.time = timestamp.time.filter(|_| nth == 1);
+                        range_item.end_time = timestamp.end_time.filter(|_| nth == total);
+                        range_item.range_day = Some((nth, total));
+                        day.items.push(range_item);
+                    }
+                    Some(_) => {}
+                    None if occurs(timestamp, &day.date) => {
+                        day.items.push(item(AgendaItemKind::Timestamp, timestamp, day.date, 0));
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+    collect_sections(section, &inner, days);
+}
+
+// 節のアクティブなタイムスタンプ (計画行と下位の節は除く)
+fn collect_timestamps(node: NodeRef, timestamps: &mut Vec<TimestampMeta>) {
+    for child in node.children() {
+        match (child.node_type(), child.meta()) {
+            (ASTType::Section, _) | (ASTType::Planning, _) => {}
+            (_, ASTMetaData::Timestamp(meta)) => {
+                if meta.kind == TimestampKind::Active || meta.kind == TimestampKind::ActiveRange {
+                    timestamps.push(meta.clone());
+                }
+            }
+            _ => collect_timestamps(child, timestamps),
+        }
+    }
+}
+
+// タイムスタンプがその日に当たるか (繰り返しは最初の日付以降のすべての回)
+fn occurs(timestamp: &TimestampMeta, date: &OrgDate) -> bool {
+    if date == &timestamp.date {
+        return true;
+    }
+    let repeater = match timestamp.repeater {
+        Some(repeater) if repeater.value > 0 && date > &timestamp.date => repeater,
+        _ => return false,
+    };
+    let months = match repeater.unit {
+        TimeUnit::Month => repeater.value as i64,
+        TimeUnit::Year => repeater.value as i64 * 12,
+        // 時間単位の繰り返しは毎日当たる
+        TimeUnit::Hour => return true,
+        unit => return (date.to_days() - timestamp.date.to_days()) % interval_days(repeater.value, unit) == 0,
+    };
+    (1..)
+        .map(|n| timestamp.date.add_months(n * months))
+        .take_while(|occurrence| occurrence <= date)
+        .any(|occurrence| &occurrence == date)
+}
+
+fn interval_days(value: u32, unit: TimeUnit) -> i64 {
+    let value = value as i64;
+    match unit {
+        TimeUnit::Hour => 0,
+        TimeUnit::Day => value,
+        TimeUnit::Week => value * 7,
+        TimeUnit::Month => value * 30,
+        TimeUnit::Year => value * 365,
+    }
+}
+
+const WEEKDAYS: &[&str] = &["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+/*
+ * アジェンダを文字列にする
+ * Monday 2024-01-01
+ *   work:      10:00-11:00 Scheduled:  TODO Meeting :tag:
+ *   work:      In   3 d.:  TODO Report
+ */
+pub fn render_agenda(days: &[AgendaDay]) -> String {
+    let mut lines = vec![];
+    for day in days {
+        let date = day.date;
+        lines.push(format!(
+            "{} {:04}-{:02}-{:02}",
+            WEEKDAYS[date.weekday() as usize],
+            date.year,
+            date.month,
+            date.day
+        ));
+        for item in &day.items {
+            let category = format!("{}:", item.category.as_deref().unwrap_or(""));
+            let time = match (item.time, item.end_time) {
+                (Some(time), Some(end_time)) => format!("{}-{} ", format_time(time), format_time(end_time)),
+                (Some(time), None) => format!("{} ", format_time(time)),
+                _ => String::new(),
+            };
+            let leader = match (item.kind, item.days, item.range_day) {
+                (AgendaItemKind::Timestamp, _, Some((nth, total))) => format!("({}/{}): ", nth, total),
+                (AgendaItemKind::Timestamp, _, None) => String::new(),
+                (AgendaItemKind::Scheduled, 0, _) => "Scheduled:  ".to_string(),
+                (AgendaItemKind::Scheduled, days, _) => format!("Sched.{:>2}x: ", -days),
+                (AgendaItemKind::Deadline, 0, _) => "Deadline:   ".to_string(),
+                (AgendaItemKind::Deadline, days, _) if days > 0 => format!("In {:>3} d.: ", days),
+                (AgendaItemKind::Deadline, days, _) => format!("{:>2} d. ago: ", -days),
+            };
+            let mut title = vec![];
+            title.extend(item.todo_keyword.clone());
+            title.extend(item.priority.as_ref().map(|priority| format!("[#{}]", priority)));
+            title.push(item.title.clone());
+            if !item.tags.is_empty() {
+                title.push(format!(":{}:", item.tags.join(":")));
+            }
+            lines.push(format!("  {:<10} {}{}{}", category, time, leader, title.join(" ")));
+        }
+    }
+    lines.join("\n")
+}
+
+fn format_time(time: OrgTime) -> String {
+    format!("{:>2}:{:02}", time.hour, time.minute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org_parser::org_parse;
+
+    fn date(year: i32, month: u32, day: u32) -> OrgDate {
+        OrgDate::new(year, month, day).unwrap()
+    }
+
+    const SRC: &str = "#+CATEGORY: work\n\
+                       #+TODO: TODO NEXT | DONE\n\
+                       * TODO Weekly review :review:\n\
+                       SCHEDULED: <2024-01-01 Mon 10:00 +1w>\n\
+                       * TODO Report\n\
+                       DEADLINE: <2024-01-10 Wed -5d>\n\
+                       * TODO Tax\n\
+                       DEADLINE: <2024-01-20 Sat>\n\
+                       * NEXT Old task\n\
+                       SCHEDULED: <2023-12-30 Sat>\n\
+                       * DONE Finished\n\
+                       SCHEDULED: <2023-12-29 Fri>\n\
+                       * Conference\n\
+                       :PROPERTIES:\n\
+                       :CATEGORY: travel\n\
+                       :END:\n\
+                       <2024-01-04 Thu>--<2024-01-06 Sat>\n\
+                       ** Talk\n\
+                       <2024-01-05 Fri 14:00-15:00>\n\
+                       * Notes [2024-01-03 Wed]\n\
+                       * COMMENT Draft\n\
+                       ** TODO Hidden\n\
+                       SCHEDULED: <2024-01-05 Fri>\n\
+                       * TODO Archived :ARCHIVE:\n\
+                       SCHEDULED: <2024-01-05 Fri>";
+
+    #[test]
+    fn test_agenda() {
+        let document = org_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let mut options = AgendaOptions::new(date(2024, 1, 1), 14);
+        options.today = date(2024, 1, 5);
+        let days = agenda(&[document], &options);
+        assert_eq!(days.len(), 14);
+        let summary = |day: &AgendaDay| -> Vec<(String, AgendaItemKind, i64)> {
+            day.items.iter().map(|item| (item.title.clone(), item.kind, item.days)).collect()
+        };
+        let review = || ("Weekly review".to_string(), AgendaItemKind::Scheduled, 0);
+
+        // 繰り返しは毎週表示する
+        assert_eq!(summary(&days[0]), vec![review()]);
+        assert_eq!(summary(&days[7]), vec![review()]);
+        assert_eq!(days[7].items[0].date, date(2024, 1, 8));
+        assert_eq!(days[7].items[0].time, Some(OrgTime { hour: 10, minute: 0 }));
+        assert_eq!(days[7].items[0].category, Some("work".to_string()));
+        assert_eq!(days[7].items[0].tags, vec!["review".to_string()]);
+        assert!(summary(&days[1]).is_empty());
+
+        // 今日には予定超過と警告期間に入った期限を表示する (完了したものは表示しない)
+        assert_eq!(
+            summary(&days[4]),
+            vec![
+                ("Weekly review".to_string(), AgendaItemKind::Scheduled, -4),
+                ("Talk".to_string(), AgendaItemKind::Timestamp, 0),
+                ("Report".to_string(), AgendaItemKind::Deadline, 5),
+                ("Old task".to_string(), AgendaItemKind::Scheduled, -6),
+                ("Conference".to_string(), AgendaItemKind::Timestamp, 0),
+            ]
+        );
+        assert_eq!(days[4].items[4].range_day, Some((2, 3)));
+        assert_eq!(days[4].items[4].category, Some("travel".to_string()));
+        assert_eq!(summary(&days[9]), vec![("Report".to_string(), AgendaItemKind::Deadline, 0)]);
+
+        // TODO キーワードで絞り込む
+        options.todo_keywords = vec!["NEXT".to_string()];
+        let days = agenda(&[org_parse(SRC, ASTNode::new(ASTElm::new_document()))], &options);
+        let titles: Vec<&str> = days.iter().flat_map(|day| &day.items).map(|item| item.title.as_str()).collect();
+        assert_eq!(titles, vec!["Old task"]);
+    }
+
+    #[test]
+    fn test_repeaters() {
+        let timestamp = |s: &str| crate::org_timestamp::parse_timestamp(s).unwrap().1;
+        let monthly = timestamp("<2024-01-31 +1m>");
+        assert!(occurs(&monthly, &date(2024, 2, 29)));
+        assert!(occurs(&monthly, &date(2024, 3, 31)));
+        assert!(!occurs(&monthly, &date(2024, 3, 29)));
+        assert!(!occurs(&monthly, &date(2023, 12, 31)));
+        let biweekly = timestamp("<2024-01-01 ++2w>");
+        assert!(occurs(&biweekly, &date(2024, 1, 15)));
+        assert!(!occurs(&biweekly, &date(2024, 1, 8)));
+        assert!(occurs(&timestamp("<2024-02-29 +1y>"), &date(2025, 2, 28)));
+    }
+
+    #[test]
+    fn test_render_agenda() {
+        let document = org_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let mut options = AgendaOptions::new(date(2024, 1, 4), 2);
+        options.today = date(2024, 1, 5);
+        assert_eq!(
+            render_agenda(&agenda(&[document], &options)),
+            "Thursday 2024-01-04\n\
+             \x20 travel:    (1/3): Conference\n\
+             Friday 2024-01-05\n\
+             \x20 work:      10:00 Sched. 4x: TODO Weekly review :review:\n\
+             \x20 travel:    14:00-15:00 Talk\n\
+             \x20 work:      In   5 d.: TODO Report\n\
+             \x20 work:      Sched. 6x: NEXT Old task\n\
+             \x20 travel:    (2/3): Conference"
+        );
+    }
+}
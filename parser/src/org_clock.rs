@@ -0,0 +1,284 @@
+use crate::arena::NodeRef;
+use crate::ast::*;
+use crate::org_parser::split_parameters;
+use crate::org_timestamp::parse_timestamp;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/*
+ * 作業時間の集計 (clocktable 相当)
+ *
+ * :LOGBOOK: ドロワーなどの CLOCK: [..]--[..] => h:mm 行を見出しごと・タグごとに集計する。
+ * 時間は分で数える。終わりの無い(計測中の)行は数えない。
+ * 期間を指定した場合は、期間からはみ出した部分を除いて数える。
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClockFilter {
+    pub from: Option<OrgDate>,   // この日を含む
+    pub to: Option<OrgDate>,     // この日を含む
+    pub maxlevel: Option<usize>, // 表に載せる見出しの深さ (深い見出しの時間は親に含める)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClockReport {
+    pub total: u32,
+    pub headlines: Vec<ClockRow>,    // 文書の順
+    pub tags: BTreeMap<String, u32>, // 見出しの時間をそのタグ(継承したタグと #+FILETAGS: を含む)ごとに足したもの
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClockRow {
+    pub level: usize, // 入れ子の深さ (1から)
+    pub title: String,
+    pub tags: Vec<String>, // 継承したタグを含む
+    pub own: u32,          // この見出しの時間
+    pub total: u32,        // 下位の見出しを含む時間
+    pub range: ASTRange,
+}
+
+impl ClockFilter {
+    // #+BEGIN: clocktable :maxlevel 2 :tstart "<2024-01-01>" :tend "<2024-01-07>"
+    pub fn from_parameters(parameters: &str) -> Self {
+        let mut filter = ClockFilter::default();
+        let mut tokens = split_parameters(parameters).into_iter();
+        while let Some(key) = tokens.next() {
+            let value = match tokens.next() {
+                Some(value) => value,
+                None => break,
+            };
+            let date = || parse_timestamp(value.trim_matches('"')).ok().map(|(_, timestamp)| timestamp.date);
+            match key.as_str() {
+                ":maxlevel" => filter.maxlevel = value.parse().ok(),
+                ":tstart" => filter.from = date(),
+                ":tend" => filter.to = date(),
+                _ => {}
+            }
+        }
+        filter
+    }
+
+    // 期間と重なる分数 (開始・終了は 1970-01-01 00:00 からの分)
+    fn clip(&self, start: i64, end: i64) -> u32 {
+        let from = self.from.map(|date| date.to_days() * 1440).unwrap_or(i64::MIN);
+        let to = self.to.map(|date| (date.to_days() + 1) * 1440).unwrap_or(i64::MAX);
+        (end.min(to) - start.max(from)).max(0) as u32
+    }
+}
+
+pub fn clock_report(document: NodeRef, filter: &ClockFilter) -> ClockReport {
+    let filetags = match document.meta() {
+        ASTMetaData::Document(meta) => meta.filetags.clone(),
+        _ => vec![],
+    };
+    let mut report = ClockReport::default();
+    let mut rows = vec![];
+    let (own, sections) = collect_clocks(document, filter, &filetags, 0, &mut rows);
+    report.total = own + sections;
+    for row in &rows {
+        for tag in &row.tags {
+            *report.tags.entry(tag.clone()).or_default() += row.own;
+        }
+    }
+    report.headlines = rows.into_iter().filter(|row| filter.maxlevel.is_none_or(|max| row.level <= max)).collect();
+    report
+}
+
+/*
+ * node の下の時間 (node 自身の時間, 下位の節の時間)
+ * 節は行を作って数える。depth: node の入れ子の深さ
+ */
+fn collect_clocks(
+    node: NodeRef,
+    filter: &ClockFilter,
+    tags: &[String],
+    depth: usize,
+    rows: &mut Vec<ClockRow>,
+) -> (u32, u32) {
+    let (mut own, mut sections) = (0, 0);
+    for child in node.children() {
+        match child.node_type() {
+            ASTType::Section => sections += collect_section(child, filter, tags, depth + 1, rows),
+            ASTType::Clock => own += clock_minutes(child, filter),
+            _ => {
+                let (o, s) = collect_clocks(child, filter, tags, depth, rows);
+                own += o;
+                sections += s;
+            }
+        }
+    }
+    (own, sections)
+}
+
+fn collect_section(
+    section: NodeRef,
+    filter: &ClockFilter,
+    tags: &[String],
+    level: usize,
+    rows: &mut Vec<ClockRow>,
+) -> u32 {
+    let mut tags = tags.to_vec();
+    let index = rows.len();
+    match section.first_child() {
+        Some(headline) => {
+            if let ASTMetaData::Headline(meta) = headline.meta() {
+                for tag in &meta.tags {
+                    if !tags.contains(tag) {
+                        tags.push(tag.clone());
+                    }
+                }
+            }
+            let title = headline.value().to_string();
+            let range = *headline.range();
+            rows.push(ClockRow { level, title, tags: tags.clone(), own: 0, total: 0, range });
+        }
+        None => return 0,
+    }
+
+    let (own, sections) = collect_clocks(section, filter, &tags, level, rows);
+    rows[index].own = own;
+    rows[index].total = own + sections;
+    own + sections
+}
+
+fn clock_minutes(clock: NodeRef, filter: &ClockFilter) -> u32 {
+    let timestamp = clock.children().find_map(|child| match child.meta() {
+        ASTMetaData::Timestamp(meta) => Some(meta.clone()),
+        _ => None,
+    });
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return 0,
+    };
+    let minutes = |date: &OrgDate, time: &Option<OrgTime>| {
+        let time = time.unwrap_or(OrgTime { hour: 0, minute: 0 });
+        date.to_days() * 1440 + (time.hour * 60 + time.minute) as i64
+    };
+    match timestamp.end_date {
+        Some(end_date) => {
+            filter.clip(minutes(&timestamp.date, &timestamp.time), minutes(&end_date, &timestamp.end_time))
+        }
+        None => 0,
+    }
+}
+
+// 分を h:mm にする
+pub fn format_duration(minutes: u32) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/*
+ * 集計結果を Org の表にする (clocktable の出力と同じ形)
+ * | Headline     | Time   |      |
+ * |--------------+--------+------|
+ * | *Total time* | *1:30* |      |
+ * |--------------+--------+------|
+ * | Task         | 1:30   |      |
+ * | \_  Sub      |        | 1:00 |
+ */
+pub fn render_clock_table(report: &ClockReport) -> String {
+    let columns = report.headlines.iter().map(|row| row.level).max().unwrap_or(1);
+    let mut rows: Vec<Vec<String>> = vec![];
+    let header = |first: &str, time: &str| {
+        let mut row = vec![first.to_string(), time.to_string()];
+        row.resize(columns + 1, String::new());
+        row
+    };
+    rows.push(header("Headline", "Time"));
+    rows.push(vec![]);
+    rows.push(header("*Total time*", &format!("*{}*", format_duration(report.total))));
+    rows.push(vec![]);
+    for headline in report.headlines.iter().filter(|row| row.total > 0) {
+        let title = match headline.level {
+            1 => headline.title.clone(),
+            level => format!("\\_{} {}", " ".repeat((level - 1) * 2 - 1), headline.title),
+        };
+        let mut row = vec![title];
+        row.resize(columns + 1, String::new());
+        row[headline.level] = format_duration(headline.total);
+        rows.push(row);
+    }
+
+    let widths: Vec<usize> = (0..=columns)
+        .map(|i| rows.iter().filter_map(|row| row.get(i)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .map(|row| {
+            if row.is_empty() {
+                let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+                format!("|{}|", rule.join("+"))
+            } else {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!(" {}{} ", cell, " ".repeat(width - cell.chars().count())))
+                    .collect();
+                format!("|{}|", cells.join("|"))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org_parser::org_parse;
+
+    const SRC: &str = "#+FILETAGS: :work:\n\
+                       * Project :proj:\n\
+                       :LOGBOOK:\n\
+                       CLOCK: [2024-01-01 Mon 09:00]--[2024-01-01 Mon 10:00] =>  1:00\n\
+                       :END:\n\
+                       ** Design :design:\n\
+                       :LOGBOOK:\n\
+                       CLOCK: [2024-01-02 Tue 23:00]--[2024-01-03 Wed 01:30] =>  2:30\n\
+                       CLOCK: [2024-01-04 Thu 10:00]\n\
+                       :END:\n\
+                       *** Deep\n\
+                       CLOCK: [2024-01-08 Mon 10:00]--[2024-01-08 Mon 10:45] =>  0:45\n\
+                       * Other\n\
+                       :LOGBOOK:\n\
+                       CLOCK: [2024-01-05 Fri 13:00]--[2024-01-05 Fri 13:20] =>  0:20\n\
+                       :END:";
+
+    #[test]
+    fn test_clock_report() {
+        let document = org_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let report = clock_report(document.root_node(), &ClockFilter::default());
+        assert_eq!(report.total, 60 + 150 + 45 + 20);
+        let rows: Vec<(usize, &str, u32, u32)> =
+            report.headlines.iter().map(|row| (row.level, row.title.as_str(), row.own, row.total)).collect();
+        assert_eq!(
+            rows,
+            vec![(1, "Project", 60, 255), (2, "Design", 150, 195), (3, "Deep", 45, 45), (1, "Other", 20, 20)]
+        );
+        assert_eq!(report.headlines[2].tags, vec!["work".to_string(), "proj".to_string(), "design".to_string()]);
+        let tags: Vec<(&str, u32)> = report.tags.iter().map(|(tag, minutes)| (tag.as_str(), *minutes)).collect();
+        assert_eq!(tags, vec![("design", 195), ("proj", 255), ("work", 275)]);
+
+        // 期間からはみ出した部分は数えない
+        let filter = ClockFilter::from_parameters(":maxlevel 2 :tstart \"<2024-01-02>\" :tend \"<2024-01-02>\"");
+        assert_eq!(filter.maxlevel, Some(2));
+        let report = clock_report(document.root_node(), &filter);
+        assert_eq!(report.total, 60);
+        let rows: Vec<(&str, u32)> = report.headlines.iter().map(|row| (row.title.as_str(), row.total)).collect();
+        assert_eq!(rows, vec![("Project", 60), ("Design", 60), ("Other", 0)]);
+    }
+
+    #[test]
+    fn test_render_clock_table() {
+        let document = org_parse(SRC, ASTNode::new(ASTElm::new_document()));
+        let filter = ClockFilter { maxlevel: Some(2), ..Default::default() };
+        assert_eq!(
+            render_clock_table(&clock_report(document.root_node(), &filter)),
+            "| Headline     | Time   |      |\n\
+             |--------------+--------+------|\n\
+             | *Total time* | *4:35* |      |\n\
+             |--------------+--------+------|\n\
+             | Project      | 4:15   |      |\n\
+             | \\_  Design   |        | 3:15 |\n\
+             | Other        | 0:20   |      |"
+        );
+    }
+}
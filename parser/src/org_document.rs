@@ -0,0 +1,436 @@
+use crate::arena::{ASTArena, NodeId, NodeRef};
+use crate::ast::*;
+
+use std::collections::BTreeMap;
+
+// Org の既定値 (H:3 num:t toc:t ^:t arch:headline)
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            headline_levels: 3,
+            section_numbers: true,
+            section_number_depth: None,
+            toc: true,
+            toc_depth: None,
+            sub_superscript: SubSuperscript::On,
+            archived_trees: ArchivedTrees::HeadlineOnly,
+            raw: BTreeMap::new(),
+        }
+    }
+}
+
+impl ExportOptions {
+    // 文書中の #+OPTIONS: 行をすべて集める (後の行が優先)
+    // パースの結果に影響するので、TODOキーワードと同じくパースの前に走査する
+    pub fn from_source(s: &str) -> Self {
+        let mut options = ExportOptions::default();
+        for line in s.lines() {
+            let line = line.trim_start();
+            if line.get(..10).is_some_and(|key| key.eq_ignore_ascii_case("#+OPTIONS:")) {
+                options.apply(&line[10..]);
+            }
+        }
+        options
+    }
+
+    // toc:2 num:nil ^:{}
+    pub fn apply(&mut self, value: &str) {
+        for item in value.split_whitespace() {
+            let (key, value) = match item.split_once(':') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            self.raw.insert(key.to_string(), value.to_string());
+            match key {
+                "H" => {
+                    if let Ok(levels) = value.parse() {
+                        self.headline_levels = levels;
+                    }
+                }
+                "num" => (self.section_numbers, self.section_number_depth) = parse_depth(value),
+                "toc" => (self.toc, self.toc_depth) = parse_depth(value),
+                "^" => {
+                    self.sub_superscript = match value {
+                        "{}" => SubSuperscript::Braces,
+                        "nil" => SubSuperscript::Off,
+                        _ => SubSuperscript::On,
+                    }
+                }
+                "arch" => {
+                    self.archived_trees = match value {
+                        "nil" => ArchivedTrees::Drop,
+                        "headline" => ArchivedTrees::HeadlineOnly,
+                        _ => ArchivedTrees::Keep,
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn section_number_depth(&self) -> usize {
+        self.section_number_depth.unwrap_or(self.headline_levels)
+    }
+
+    pub fn toc_depth(&self) -> usize {
+        self.toc_depth.unwrap_or(self.headline_levels)
+    }
+}
+
+// t -> (true, None), nil -> (false, None), 2 -> (true, Some(2))
+fn parse_depth(value: &str) -> (bool, Option<usize>) {
+    match value {
+        "nil" => (false, None),
+        _ => match value.parse::<usize>() {
+            Ok(depth) => (depth > 0, Some(depth)),
+            Err(_) => (true, None),
+        },
+    }
+}
+
+/*
+ * 文書の付加情報を集めて Document ノードに設定する
+ * 見出し番号と目次は #+OPTIONS: の num:, toc: に従う。
+ * COMMENT, :noexport: の見出しは番号・目次の対象外、UNNUMBERED プロパティを持つ見出しは番号を付けない。
+ * 脚注は最初に参照された順に番号を付ける (ラベルのないインライン脚注はそれぞれ別の番号)。
+ */
+pub fn build_document_meta(document: &mut ASTArena, options: ExportOptions) {
+    let mut meta = DocumentMeta { options, ..Default::default() };
+    collect_keywords(document.root_node(), &mut meta);
+    let mut toc = vec![];
+    let root = document.root();
+    number_headlines(document, root, &meta.options, &mut toc, &[], true);
+    meta.toc = toc;
+    meta.footnotes = number_footnotes(document);
+    collect_targets(document.root_node(), &mut meta.targets);
+    document.data_mut(root).elm_meta = ASTMetaData::Document(meta);
+}
+
+fn collect_keywords(document: NodeRef, meta: &mut DocumentMeta) {
+    for node in document.pre_order() {
+        if let ASTMetaData::Keyword { key, .. } = node.meta() {
+            let value = node.value().trim();
+            match key.as_str() {
+                // 複数行の TITLE は空白で繋ぐ
+                "TITLE" => {
+                    meta.title = Some(match meta.title.take() {
+                        Some(title) => title + " " + value,
+                        None => value.to_string(),
+                    })
+                }
+                "AUTHOR" => meta.author = Some(value.to_string()),
+                "DATE" => meta.date = Some(value.to_string()),
+                "LANGUAGE" => meta.language = Some(value.to_string()),
+                "FILETAGS" => {
+                    meta.filetags.extend(value.split(':').map(str::trim).filter(|t| !t.is_empty()).map(String::from))
+                }
+                "CATEGORY" => meta.category = Some(value.to_string()),
+                "SETUPFILE" => meta.setupfile = Some(value.trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+    }
+}
+
+// 見出し番号は節の入れ子の深さで数える (* の下の *** は 1.1)
+fn number_headlines(
+    document: &mut ASTArena,
+    id: NodeId,
+    options: &ExportOptions,
+    toc: &mut Vec<TocEntry>,
+    prefix: &[usize],
+    numbered: bool,
+) {
+    let depth = prefix.len() + 1;
+    let mut counter = 0;
+    let sections: Vec<NodeId> =
+        document.node(id).children().filter(|c| c.node_type() == &ASTType::Section).map(|c| c.id()).collect();
+    for section in sections {
+        let headline = match document.node(section).first_child() {
+            Some(headline) => headline,
+            None => continue,
+        };
+        let (excluded, unnumbered) = match headline.meta() {
+            ASTMetaData::Headline(meta) => (
+                meta.commented || meta.tags.iter().any(|t| t == "noexport"),
+                meta.properties.get("UNNUMBERED").is_some_and(|v| v != "nil"),
+            ),
+            _ => continue,
+        };
+        if excluded {
+            continue;
+        }
+
+        let numbered = numbered && !unnumbered;
+        let number = if numbered && options.section_numbers && depth <= options.section_number_depth() {
+            counter += 1;
+            Some([prefix, &[counter]].concat())
+        } else {
+            None
+        };
+        let number_text =
+            number.as_ref().map(|n| n.iter().map(|i| i.to_string()).collect::<Vec<String>>().join("."));
+
+        if options.toc && depth <= options.toc_depth() {
+            toc.push(TocEntry {
+                level: depth,
+                title: headline.value().to_string(),
+                number: number_text.clone(),
+                range: *headline.range(),
+            });
+        }
+        let headline = headline.id();
+        if let ASTMetaData::Headline(meta) = &mut document.data_mut(headline).elm_meta {
+            meta.number = number_text;
+        }
+        let prefix = number.unwrap_or_else(|| [prefix, &[0]].concat());
+        number_headlines(document, section, options, toc, &prefix, numbered);
+    }
+}
+
+fn number_footnotes(document: &mut ASTArena) -> Vec<Footnote> {
+    let mut footnotes = vec![];
+    let ids: Vec<NodeId> = document.pre_order(document.root()).collect();
+    for &id in &ids {
+        collect_footnote_reference(document.data_mut(id), &mut footnotes);
+    }
+    for &id in &ids {
+        fill_footnote_definition(document.data_mut(id), &mut footnotes);
+    }
+    footnotes
+}
+
+fn collect_footnote_reference(node: &mut ASTElm, footnotes: &mut Vec<Footnote>) {
+    if node.elm_type == ASTType::FootnoteReference {
+        let range = node.range;
+        if let ASTMetaData::Footnote(meta) = &mut node.elm_meta {
+            let found = match &meta.label {
+                Some(label) => footnotes.iter_mut().find(|f| f.label.as_ref() == Some(label)),
+                None => None,
+            };
+            let footnote = match found {
+                Some(footnote) => footnote,
+                None => {
+                    footnotes.push(Footnote {
+                        label: meta.label.clone(),
+                        number: footnotes.len() + 1,
+                        definition: None,
+                        references: vec![],
+                    });
+                    footnotes.last_mut().unwrap()
+                }
+            };
+            if meta.inline && footnote.definition.is_none() {
+                footnote.definition = Some(range);
+            }
+            footnote.references.push(range);
+            meta.number = Some(footnote.number);
+        }
+    }
+}
+
+fn fill_footnote_definition(node: &mut ASTElm, footnotes: &mut [Footnote]) {
+    if node.elm_type == ASTType::FootnoteDefinition {
+        let range = node.range;
+        if let ASTMetaData::Footnote(meta) = &mut node.elm_meta {
+            if let Some(footnote) = footnotes.iter_mut().find(|f| f.label.is_some() && f.label == meta.label) {
+                meta.number = Some(footnote.number);
+                footnote.definition.get_or_insert(range);
+            }
+        }
+    }
+}
+
+fn collect_targets(document: NodeRef, targets: &mut BTreeMap<String, LinkResolution>) {
+    for node in document.pre_order() {
+        let kind = match node.node_type() {
+            ASTType::Target => LinkResolutionKind::Target,
+            ASTType::RadioTarget => LinkResolutionKind::RadioTarget,
+            _ => continue,
+        };
+        targets.entry(node.value().to_lowercase()).or_insert_with(|| LinkResolution {
+            kind,
+            name: node.value().to_string(),
+            range: *node.range(),
+        });
+    }
+}
+
+/*
+ * エクスポートしない要素を取り除く
+ *   # コメント行, #+BEGIN_COMMENT ... #+END_COMMENT
+ *   COMMENT の見出し, :noexport: タグの見出し (配下の見出しを含む)
+ *   :ARCHIVE: タグの見出しは #+OPTIONS: arch: に従う (arch:headline は見出しの行のみ残す)
+ */
+pub fn prune_for_export(document: &mut ASTArena) {
+    let archived_trees = match document.root_node().meta() {
+        ASTMetaData::Document(meta) => meta.options.archived_trees,
+        _ => ArchivedTrees::HeadlineOnly,
+    };
+    let root = document.root();
+    prune_children(document, root, archived_trees);
+}
+
+fn prune_children(document: &mut ASTArena, id: NodeId, archived_trees: ArchivedTrees) {
+    let children: Vec<NodeId> = document.children(id).collect();
+    for child in children {
+        let node = document.node(child);
+        let tags = match node.node_type() {
+            ASTType::Comment | ASTType::CommentBlock => None,
+            ASTType::Section => match node.first_child().map(|h| h.meta()) {
+                Some(ASTMetaData::Headline(meta)) if meta.commented => None,
+                Some(ASTMetaData::Headline(meta)) => Some(meta.tags.clone()),
+                _ => Some(vec![]),
+            },
+            _ => Some(vec![]),
+        };
+        let tags = match tags {
+            Some(tags) if !tags.iter().any(|tag| tag == "noexport") => tags,
+            _ => {
+                document.detach(child);
+                continue;
+            }
+        };
+        if tags.iter().any(|tag| tag == "ARCHIVE") {
+            match archived_trees {
+                ArchivedTrees::Drop => {
+                    document.detach(child);
+                    continue;
+                }
+                ArchivedTrees::HeadlineOnly => {
+                    let rest: Vec<NodeId> = document.children(child).skip(1).collect();
+                    for id in rest {
+                        document.detach(id);
+                    }
+                }
+                ArchivedTrees::Keep => {}
+            }
+        }
+        prune_children(document, child, archived_trees);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org_parser::org_parse;
+
+    #[test]
+    fn test_export_options() {
+        let options = ExportOptions::from_source("#+OPTIONS: toc:2 num:nil\n#+options: ^:{} H:4 pri:t foo:bar\n");
+        assert_eq!((options.toc, options.toc_depth()), (true, 2));
+        assert!(!options.section_numbers);
+        assert_eq!(options.section_number_depth(), 4);
+        assert_eq!(options.sub_superscript, SubSuperscript::Braces);
+        assert_eq!(options.raw.get("pri"), Some(&"t".to_string()));
+        assert_eq!(options.raw.get("foo"), Some(&"bar".to_string()));
+        assert_eq!(ExportOptions::from_source("no options"), ExportOptions::default());
+    }
+
+    #[test]
+    fn test_build_document_meta() {
+        let src = "#+TITLE: My\n#+title: Notes\n#+AUTHOR: Alice\n#+DATE: 2024-01-02\n#+FILETAGS: :work:notes:\n\
+                   #+LANGUAGE: ja\n#+SETUPFILE: \"setup.org\"\n#+OPTIONS: toc:2 num:1\n\
+                   * Intro\n** Detail\n*** Deep\n* COMMENT Hidden\n* Plain\n:PROPERTIES:\n:UNNUMBERED: t\n:END:\n** Child";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let meta = match node.root_node().meta() {
+            ASTMetaData::Document(meta) => meta.clone(),
+            _ => panic!("not a document"),
+        };
+        assert_eq!(meta.title, Some("My Notes".to_string()));
+        assert_eq!(meta.author, Some("Alice".to_string()));
+        assert_eq!(meta.date, Some("2024-01-02".to_string()));
+        assert_eq!(meta.language, Some("ja".to_string()));
+        assert_eq!(meta.filetags, vec!["work".to_string(), "notes".to_string()]);
+        assert_eq!(meta.setupfile, Some("setup.org".to_string()));
+
+        let toc: Vec<(usize, &str, Option<&str>)> =
+            meta.toc.iter().map(|e| (e.level, e.title.as_str(), e.number.as_deref())).collect();
+        assert_eq!(toc, vec![(1, "Intro", Some("1")), (2, "Detail", None), (1, "Plain", None), (2, "Child", None)]);
+        assert_eq!(meta.toc[0].range.begin.line(), 9);
+    }
+
+    #[test]
+    fn test_section_numbers() {
+        let node = org_parse("* a\n*** b\n** c\n* d", ASTNode::new(ASTElm::new_document()));
+        let mut numbers = vec![];
+        for node in node.root_node().pre_order() {
+            if let ASTMetaData::Headline(meta) = node.meta() {
+                numbers.push(meta.number.clone());
+            }
+        }
+        let expected: Vec<Option<String>> = vec![Some("1"), Some("1.1"), Some("1.2"), Some("2")]
+            .into_iter()
+            .map(|n| n.map(String::from))
+            .collect();
+        assert_eq!(numbers, expected);
+    }
+
+    #[test]
+    fn test_footnotes_and_targets() {
+        let src = "a[fn:n2] b[fn::anon] c[fn:n1:inline] d[fn:n2]\n\n[fn:n2] second\n\n[fn:unused] x\n\n<<Here>> <<<Radio>>>";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let meta = match node.root_node().meta() {
+            ASTMetaData::Document(meta) => meta.clone(),
+            _ => panic!("not a document"),
+        };
+        let footnotes: Vec<(Option<&str>, usize, usize, Option<u32>)> = meta
+            .footnotes
+            .iter()
+            .map(|f| (f.label.as_deref(), f.number, f.references.len(), f.definition.as_ref().map(|d| d.begin.line())))
+            .collect();
+        assert_eq!(footnotes, vec![(Some("n2"), 1, 2, Some(3)), (None, 2, 1, Some(1)), (Some("n1"), 3, 1, Some(1))]);
+
+        let numbers: Vec<Option<usize>> = node
+            .root_node()
+            .children()
+            .filter(|c| c.node_type() == &ASTType::FootnoteDefinition)
+            .map(|c| match c.meta() {
+                ASTMetaData::Footnote(meta) => meta.number,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(numbers, vec![Some(1), None]);
+
+        let targets: Vec<(&str, LinkResolutionKind)> =
+            meta.targets.iter().map(|(k, v)| (k.as_str(), v.kind.clone())).collect();
+        assert_eq!(targets, vec![("here", LinkResolutionKind::Target), ("radio", LinkResolutionKind::RadioTarget)]);
+    }
+
+    #[test]
+    fn test_prune_for_export() {
+        let src = "# note\nText\n#+BEGIN_COMMENT\nhidden\n#+END_COMMENT\n\
+                   * COMMENT Draft\n** Child\n* Private :noexport:\n* Public\n# inner\nBody";
+        let mut node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        prune_for_export(&mut node);
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>Text</text></paragraph><section><headline><text>Public</text></headline>\
+             <paragraph><text>Body</text></paragraph></section></document>"
+        );
+    }
+
+    #[test]
+    fn test_prune_archived_trees() {
+        let src = "* Old :ARCHIVE:\nBody\n** Child\n* Current";
+        let pruned = |options: &str| {
+            let mut node = org_parse(&format!("{}{}", options, src), ASTNode::new(ASTElm::new_document()));
+            prune_for_export(&mut node);
+            let sections: Vec<String> = node
+                .root_node()
+                .children()
+                .filter(|c| c.node_type() == &ASTType::Section)
+                .map(|section| section.render_debug_format())
+                .collect();
+            sections
+        };
+        let current = "<section><headline><text>Current</text></headline></section>";
+        assert_eq!(pruned(""), vec!["<section><headline><text>Old</text></headline></section>", current]);
+        assert_eq!(pruned("#+OPTIONS: arch:nil\n"), vec![current]);
+        assert_eq!(
+            pruned("#+OPTIONS: arch:t\n")[0],
+            "<section><headline><text>Old</text></headline><paragraph><text>Body</text></paragraph>\
+             <section><headline><text>Child</text></headline></section></section>"
+        );
+    }
+}
@@ -0,0 +1,813 @@
+use crate::arena::NodeRef;
+use crate::ast::*;
+use crate::org_parser::split_parameters;
+
+/*
+ * org-element 形式の S 式 (Emacs の org-element-parse-buffer と突き合わせる用)
+ *
+ * "Some *text*\n\n* Title\n" は
+ * (org-data (:begin 1 :end 22)
+ *   (section (:begin 1 :end 14 :post-blank 1)
+ *     (paragraph (:begin 1 :end 14 :post-blank 1) "Some " (bold (:begin 6 :end 12) "text")))
+ *   (headline (:begin 14 :end 22 :raw-value "Title" :level 1 ... :title ("Title"))))
+ *
+ * 要素名・プロパティ名は org-element に合わせる。位置は 1 から数えた文字位置 (Emacs の point)。
+ * 要素の :end は直後の空行を含めた位置 (次の要素の :begin), :post-blank はその空行の数。
+ * 付加キーワードのある要素は :begin がキーワードの先頭、:post-affiliated が要素の本体の先頭になる。
+ * :contents-begin / :contents-end や :parent など、ここで出さないプロパティは比較の対象外にする。
+ * 要素は一行に一つ(深さに応じて字下げ)、オブジェクトは親と同じ行に並べる。
+ */
+pub fn render_org_element(document: NodeRef) -> String {
+    let end = document.range().end;
+    render_element(document, &end, 0)
+}
+
+struct Sexp {
+    name: &'static str,
+    properties: Vec<(String, String)>,
+    contents: Vec<String>,
+    inline: bool, // 中身を同じ行に並べる (オブジェクト)
+}
+
+impl Sexp {
+    fn new(name: &'static str, inline: bool) -> Self {
+        Sexp { name, properties: vec![], contents: vec![], inline }
+    }
+
+    fn property(&mut self, key: &str, value: String) {
+        self.properties.push((key.to_string(), value));
+    }
+
+    fn render(&self, depth: usize) -> String {
+        let properties: Vec<String> =
+            self.properties.iter().map(|(key, value)| format!(":{} {}", key, value)).collect();
+        let mut result = format!("({} ({})", self.name, properties.join(" "));
+        for content in &self.contents {
+            if self.inline {
+                result.push(' ');
+            } else {
+                result.push('\n');
+                result.push_str(&"  ".repeat(depth + 1));
+            }
+            result.push_str(content);
+        }
+        result.push(')');
+        result
+    }
+}
+
+/* ---------- 値 ---------- */
+
+fn string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn optional(s: Option<&str>) -> String {
+    s.map(string).unwrap_or_else(|| "nil".to_string())
+}
+
+fn boolean(b: bool) -> String {
+    if b { "t" } else { "nil" }.to_string()
+}
+
+fn list(items: Vec<String>) -> String {
+    if items.is_empty() {
+        "nil".to_string()
+    } else {
+        format!("({})", items.join(" "))
+    }
+}
+
+fn point(pos: &ASTPos) -> String {
+    (pos.pos() + 1).to_string()
+}
+
+/* ---------- 要素 ---------- */
+
+// 付加キーワードを含めた要素の先頭
+fn begin_of<'a>(node: NodeRef<'a>) -> &'a ASTPos {
+    match node.affiliated() {
+        Some(affiliated) => &affiliated.range.begin,
+        None => &node.range().begin,
+    }
+}
+
+// 閉じる行の無い要素 (最後の子が要素の後ろの空行まで続く)
+fn is_open_ended(node: NodeRef) -> bool {
+    matches!(
+        node.node_type(),
+        ASTType::Document | ASTType::Section | ASTType::PlainList | ASTType::Item | ASTType::FootnoteDefinition
+    )
+}
+
+/*
+ * 要素を S 式にする
+ * end: 直後の空行を含めた終わりの位置 (次の要素の先頭)
+ */
+fn render_element(node: NodeRef, end: &ASTPos, depth: usize) -> String {
+    let children: Vec<NodeRef> = node.children().collect();
+    // 閉じる行のある要素の中身は、最後の子の行末まで
+    let last_line_end = children.last().map(|last| {
+        let end = &last.range().end;
+        ASTPos::new(end.line() + 1, 1, end.pos() + 1)
+    });
+    let contents_end = match &last_line_end {
+        Some(last_line_end) if !is_open_ended(node) => last_line_end,
+        _ => end,
+    };
+
+    let mut sexp = match node.node_type() {
+        ASTType::Document => {
+            let mut sexp = Sexp::new("org-data", false);
+            sexp.property("begin", "1".to_string());
+            sexp.property("end", point(end));
+            sexp.contents = render_sections(&children, end, depth);
+            return sexp.render(depth);
+        }
+        ASTType::Section => return render_headline(node, &children, end, depth),
+        ASTType::Headline | ASTType::Headers => {
+            let mut sexp = Sexp::new("headline", false);
+            headline_properties(&mut sexp, node, None);
+            sexp
+        }
+        ASTType::Inlinetask => {
+            let mut sexp = Sexp::new("inlinetask", false);
+            let headline = children.iter().find(|child| *child.node_type() == ASTType::Headline).copied();
+            let planning = children.iter().find(|child| *child.node_type() == ASTType::Planning).copied();
+            if let Some(headline) = headline {
+                headline_properties(&mut sexp, headline, planning);
+            }
+            let contents: Vec<NodeRef> =
+                children.iter().copied().filter(|child| *child.node_type() != ASTType::Headline).collect();
+            sexp.contents = render_elements(&contents, contents_end, depth);
+            sexp
+        }
+        ASTType::Paragraph => {
+            let mut sexp = Sexp::new("paragraph", true);
+            sexp.contents = render_objects(&children);
+            sexp
+        }
+        ASTType::Keyword => {
+            let mut sexp = Sexp::new("keyword", false);
+            if let ASTMetaData::Keyword { key, .. } = node.meta() {
+                sexp.property("key", string(key));
+            }
+            sexp.property("value", string(node.value().trim()));
+            sexp
+        }
+        ASTType::Include => {
+            // Emacs では #+INCLUDE: もキーワード
+            let mut sexp = Sexp::new("keyword", false);
+            let value = node.raw_value().split_once(':').map(|(_, value)| value.trim()).unwrap_or_default();
+            sexp.property("key", string("INCLUDE"));
+            sexp.property("value", string(value));
+            sexp
+        }
+        ASTType::BabelCall => {
+            let mut sexp = Sexp::new("babel-call", false);
+            babel_call_properties(&mut sexp, node);
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::SrcBlock => {
+            let mut sexp = Sexp::new("src-block", false);
+            if let ASTMetaData::Block(meta) = node.meta() {
+                sexp.property("language", optional(meta.language.as_deref()));
+                sexp.property("switches", switches(meta));
+                // 言語・スイッチより後ろのヘッダー引数
+                let parameters = split_parameters(&meta.parameters);
+                let parameters = match parameters.iter().position(|token| token.starts_with(':')) {
+                    Some(index) => Some(parameters[index..].join(" ")),
+                    None => None,
+                };
+                sexp.property("parameters", optional(parameters.as_deref()));
+            }
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::ExampleBlock => {
+            let mut sexp = Sexp::new("example-block", false);
+            if let ASTMetaData::Block(meta) = node.meta() {
+                sexp.property("switches", switches(meta));
+            }
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::ExportBlock => {
+            let mut sexp = Sexp::new("export-block", false);
+            if let ASTMetaData::Block(meta) = node.meta() {
+                let backend = meta.language.as_ref().map(|language| language.to_uppercase());
+                sexp.property("type", optional(backend.as_deref()));
+            }
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::CommentBlock => {
+            let mut sexp = Sexp::new("comment-block", false);
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::VerseBlock => {
+            let mut sexp = Sexp::new("verse-block", true);
+            sexp.contents = render_objects(&children);
+            sexp
+        }
+        ASTType::QuoteBlock | ASTType::CenterBlock | ASTType::SpecialBlock => {
+            let name = match node.node_type() {
+                ASTType::QuoteBlock => "quote-block",
+                ASTType::CenterBlock => "center-block",
+                _ => "special-block",
+            };
+            let mut sexp = Sexp::new(name, false);
+            if let (ASTType::SpecialBlock, ASTMetaData::Block(meta)) = (node.node_type(), node.meta()) {
+                sexp.property("type", string(&meta.name));
+            }
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::DynamicBlock => {
+            let mut sexp = Sexp::new("dynamic-block", false);
+            if let ASTMetaData::Block(meta) = node.meta() {
+                sexp.property("block-name", string(&meta.name));
+                sexp.property("arguments", optional(Some(meta.parameters.as_str()).filter(|p| !p.is_empty())));
+            }
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::Drawer => {
+            let mut sexp = Sexp::new("drawer", false);
+            if let ASTMetaData::Drawer { name } = node.meta() {
+                sexp.property("drawer-name", string(name));
+            }
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::PropertyDrawer => {
+            let mut sexp = Sexp::new("property-drawer", false);
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::NodeProperty => {
+            let mut sexp = Sexp::new("node-property", false);
+            if let ASTMetaData::Property { key } = node.meta() {
+                sexp.property("key", string(key));
+            }
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::Planning => {
+            let mut sexp = Sexp::new("planning", false);
+            for (key, timestamp) in planning_timestamps(node) {
+                sexp.property(key, timestamp);
+            }
+            sexp
+        }
+        ASTType::Clock => {
+            let mut sexp = Sexp::new("clock", false);
+            let timestamp = children.iter().find(|child| *child.node_type() == ASTType::Timestamp).copied();
+            let duration = node.value().split_once("=>").map(|(_, duration)| duration.trim());
+            sexp.property("status", if duration.is_some() { "closed" } else { "running" }.to_string());
+            let timestamp = timestamp.map(|timestamp| render_object(timestamp));
+            sexp.property("value", timestamp.unwrap_or_else(|| "nil".to_string()));
+            sexp.property("duration", optional(duration));
+            sexp
+        }
+        ASTType::Table => {
+            let mut sexp = Sexp::new("table", false);
+            sexp.property("type", "org".to_string());
+            if let ASTMetaData::Table(meta) = node.meta() {
+                let tblfm = Some(meta.formulas.join("::")).filter(|formulas| !formulas.is_empty());
+                sexp.property("tblfm", list(tblfm.iter().map(|formulas| string(formulas)).collect()));
+            }
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::TableRow => {
+            let mut sexp = Sexp::new("table-row", true);
+            let kind = match node.meta() {
+                ASTMetaData::TableRow { kind: TableRowKind::Rule } => "rule",
+                _ => "standard",
+            };
+            sexp.property("type", kind.to_string());
+            sexp.contents = render_objects(&children);
+            sexp
+        }
+        ASTType::PlainList => {
+            let mut sexp = Sexp::new("plain-list", false);
+            let kind = match node.meta() {
+                ASTMetaData::List { kind: ListKind::Ordered } => "ordered",
+                ASTMetaData::List { kind: ListKind::Descriptive } => "descriptive",
+                _ => "unordered",
+            };
+            sexp.property("type", kind.to_string());
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::Item => {
+            let mut sexp = Sexp::new("item", false);
+            if let ASTMetaData::Item(meta) = node.meta() {
+                sexp.property("bullet", string(&format!("{} ", meta.bullet)));
+                let checkbox = match meta.checkbox {
+                    Some(Checkbox::On) => "on",
+                    Some(Checkbox::Off) => "off",
+                    Some(Checkbox::Trans) => "trans",
+                    None => "nil",
+                };
+                sexp.property("checkbox", checkbox.to_string());
+                let counter = meta.counter.map(|counter| counter.to_string());
+                sexp.property("counter", counter.unwrap_or_else(|| "nil".to_string()));
+            }
+            let tag = children.iter().find(|child| *child.node_type() == ASTType::ItemTag).copied();
+            let tag = tag.map(|tag| {
+                list(render_objects(&tag.children().collect::<Vec<_>>()))
+            });
+            sexp.property("tag", tag.unwrap_or_else(|| "nil".to_string()));
+            let contents: Vec<NodeRef> =
+                children.iter().copied().filter(|child| *child.node_type() != ASTType::ItemTag).collect();
+            sexp.contents = render_elements(&contents, contents_end, depth);
+            sexp
+        }
+        ASTType::FootnoteDefinition => {
+            let mut sexp = Sexp::new("footnote-definition", false);
+            if let ASTMetaData::Footnote(meta) = node.meta() {
+                sexp.property("label", optional(meta.label.as_deref()));
+            }
+            sexp.contents = render_elements(&children, contents_end, depth);
+            sexp
+        }
+        ASTType::LatexEnvironment => {
+            let mut sexp = Sexp::new("latex-environment", false);
+            sexp.property("value", string(node.raw_value()));
+            sexp
+        }
+        ASTType::FixedWidth | ASTType::Comment => {
+            let name = if *node.node_type() == ASTType::Comment { "comment" } else { "fixed-width" };
+            let mut sexp = Sexp::new(name, false);
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::HorizontalRule => Sexp::new("horizontal-rule", false),
+        // 要素の並びに紛れたオブジェクト
+        _ => return render_object(node),
+    };
+
+    let mut properties = vec![("begin".to_string(), point(begin_of(node))), ("end".to_string(), point(end))];
+    properties.append(&mut sexp.properties);
+    let post_blank = (end.line() as i64 - node.range().end.line() as i64 - 1).max(0);
+    properties.push(("post-blank".to_string(), post_blank.to_string()));
+    if let Some(affiliated) = node.affiliated() {
+        properties.push(("post-affiliated".to_string(), point(&node.range().begin)));
+        properties.append(&mut affiliated_properties(affiliated));
+    }
+    sexp.properties = properties;
+    sexp.render(depth)
+}
+
+/*
+ * 要素の並び
+ * 各要素の終わりは次の要素の先頭、最後の要素は end まで
+ */
+fn render_elements(children: &[NodeRef], end: &ASTPos, depth: usize) -> Vec<String> {
+    children
+        .iter()
+        .enumerate()
+        .map(|(i, child)| {
+            let child_end = children.get(i + 1).map(|&next| begin_of(next)).unwrap_or(end);
+            render_element(*child, child_end, depth + 1)
+        })
+        .collect()
+}
+
+/*
+ * 文書・節の中身
+ * 最初の見出しより前の要素は section にまとめる (org-element と同じ)
+ */
+fn render_sections(children: &[NodeRef], end: &ASTPos, depth: usize) -> Vec<String> {
+    let split = children.iter().position(|child| *child.node_type() == ASTType::Section).unwrap_or(children.len());
+    let (elements, sections) = children.split_at(split);
+    let mut contents = vec![];
+    if let Some(first) = elements.first() {
+        let section_end = sections.first().map(|&section| begin_of(section)).unwrap_or(end);
+        let mut sexp = Sexp::new("section", false);
+        sexp.property("begin", point(begin_of(*first)));
+        sexp.property("end", point(section_end));
+        let last = elements.last().map(|last| last.range().end.line()).unwrap_or_default();
+        sexp.property("post-blank", (section_end.line() as i64 - last as i64 - 1).max(0).to_string());
+        sexp.contents = render_elements(elements, section_end, depth + 1);
+        contents.push(sexp.render(depth + 1));
+    }
+    contents.extend(render_elements(sections, end, depth));
+    contents
+}
+
+// 節 (見出しと中身、下位の見出し)
+fn render_headline(section: NodeRef, children: &[NodeRef], end: &ASTPos, depth: usize) -> String {
+    let (headline, rest) = match children.split_first() {
+        Some((headline, rest)) if *headline.node_type() == ASTType::Headline => (*headline, rest),
+        _ => (section, children),
+    };
+    let planning = rest.iter().find(|child| *child.node_type() == ASTType::Planning);
+
+    let mut sexp = Sexp::new("headline", false);
+    sexp.property("begin", point(&section.range().begin));
+    sexp.property("end", point(end));
+    headline_properties(&mut sexp, headline, planning.copied());
+    sexp.contents = render_sections(rest, end, depth);
+    sexp.render(depth)
+}
+
+fn headline_properties(sexp: &mut Sexp, headline: NodeRef, planning: Option<NodeRef>) {
+    sexp.property("raw-value", string(headline.value()));
+    sexp.property("level", headline.heading_level().unwrap_or(1).to_string());
+    if let ASTMetaData::Headline(meta) = headline.meta() {
+        let priority = meta.priority.as_ref().and_then(|priority| priority.chars().next());
+        sexp.property("priority", priority.map(|c| (c as u32).to_string()).unwrap_or_else(|| "nil".to_string()));
+        sexp.property("tags", list(meta.tags.iter().map(|tag| string(tag)).collect()));
+        sexp.property("todo-keyword", optional(meta.todo_keyword.as_deref()));
+        let todo_type = match meta.todo_type {
+            Some(TodoType::Todo) => "todo",
+            Some(TodoType::Done) => "done",
+            None => "nil",
+        };
+        sexp.property("todo-type", todo_type.to_string());
+        sexp.property("commentedp", boolean(meta.commented));
+        sexp.property("archivedp", boolean(meta.tags.iter().any(|tag| tag == "ARCHIVE")));
+        for (key, timestamp) in planning.map(planning_timestamps).unwrap_or_default() {
+            sexp.property(key, timestamp);
+        }
+        // プロパティドロワーの値 (キーは大文字)
+        for (key, value) in &meta.properties {
+            sexp.property(key, string(value));
+        }
+    }
+    sexp.property("title", list(render_objects(&headline.children().collect::<Vec<_>>())));
+}
+
+// 計画行のタイムスタンプ (:closed :deadline :scheduled)
+fn planning_timestamps(planning: NodeRef) -> Vec<(&'static str, String)> {
+    let meta = match planning.meta() {
+        ASTMetaData::Planning(meta) => meta,
+        _ => return vec![],
+    };
+    let mut timestamps = vec![];
+    for (key, timestamp) in [("closed", &meta.closed), ("deadline", &meta.deadline), ("scheduled", &meta.scheduled)] {
+        let node = planning.children().find(|child| match child.meta() {
+            ASTMetaData::Timestamp(child_meta) => Some(child_meta) == timestamp.as_ref(),
+            _ => false,
+        });
+        if let Some(node) = node {
+            timestamps.push((key, render_object(node)));
+        }
+    }
+    timestamps
+}
+
+fn switches(meta: &BlockMeta) -> String {
+    optional(Some(meta.switches.join(" ")).filter(|switches| !switches.is_empty()).as_deref())
+}
+
+fn babel_call_properties(sexp: &mut Sexp, node: NodeRef) {
+    if let ASTMetaData::BabelCall(meta) = node.meta() {
+        sexp.property("call", string(&meta.name));
+        sexp.property("inside-header", optional(meta.inside_header.as_deref()));
+        sexp.property("arguments", optional(Some(meta.arguments.as_str()).filter(|arguments| !arguments.is_empty())));
+        sexp.property("end-header", optional(meta.end_header.as_deref()));
+    }
+}
+
+/*
+ * 付加キーワード (:name :caption :header :results :attr_html など)
+ * 記述から読み直して、org-element と同じく行ごとの値にする
+ *   #+CAPTION[short]: long -> :caption ((("long") "short"))
+ *   #+RESULTS[hash]:       -> :results ("" . "hash")
+ */
+fn affiliated_properties(affiliated: &AffiliatedMeta) -> Vec<(String, String)> {
+    let mut keys: Vec<String> = vec![];
+    let mut values: Vec<Vec<String>> = vec![];
+    for line in affiliated.raw_value.lines() {
+        let (key, value) = match line.trim_start().strip_prefix("#+").and_then(|line| line.split_once(':')) {
+            Some((key, value)) => (key, value.trim()),
+            None => continue,
+        };
+        let (key, option) = match key.split_once('[') {
+            Some((key, option)) => (key, Some(option.trim_end_matches(']'))),
+            None => (key, None),
+        };
+        let key = key.to_uppercase();
+        let value = match key.as_str() {
+            "CAPTION" => match option {
+                Some(option) => format!("(({}) {})", string(value), string(option)),
+                None => format!("(({}))", string(value)),
+            },
+            "RESULTS" => match option {
+                Some(option) => format!("({} . {})", string(value), string(option)),
+                None => format!("({})", string(value)),
+            },
+            _ => string(value),
+        };
+        match keys.iter().position(|k| *k == key) {
+            Some(index) => values[index].push(value),
+            None => {
+                keys.push(key);
+                values.push(vec![value]);
+            }
+        }
+    }
+    keys.into_iter()
+        .zip(values)
+        .map(|(key, mut values)| {
+            let value = match key.as_str() {
+                // 一つだけ持つもの
+                "NAME" | "RESULTS" => values.pop().unwrap_or_default(),
+                _ => list(values),
+            };
+            (key.to_lowercase(), value)
+        })
+        .collect()
+}
+
+/* ---------- オブジェクト ---------- */
+
+// 続いたテキストと改行は一つの文字列にする
+fn render_objects(children: &[NodeRef]) -> Vec<String> {
+    let mut contents = vec![];
+    let mut text = String::new();
+    for child in children {
+        match child.node_type() {
+            ASTType::Text | ASTType::Emoji => text.push_str(child.value()),
+            ASTType::SoftBreak => text.push('\n'),
+            _ => {
+                if !text.is_empty() {
+                    contents.push(string(&text));
+                    text.clear();
+                }
+                contents.push(render_object(*child));
+            }
+        }
+    }
+    if !text.is_empty() {
+        contents.push(string(&text));
+    }
+    contents
+}
+
+fn render_object(node: NodeRef) -> String {
+    let children: Vec<NodeRef> = node.children().collect();
+    let raw_value = node.raw_value();
+
+    let mut sexp = match node.node_type() {
+        ASTType::Text | ASTType::Emoji => return string(node.value()),
+        ASTType::SoftBreak => return string("\n"),
+        ASTType::Emphasis => Sexp::new("italic", true),
+        ASTType::Strong => Sexp::new("bold", true),
+        ASTType::Underline => Sexp::new("underline", true),
+        ASTType::Strikethrough => Sexp::new("strike-through", true),
+        // Markdown の ==highlight== (Org には無い)
+        ASTType::Highlight => Sexp::new("highlight", true),
+        ASTType::Verbatim | ASTType::Code => {
+            let mut sexp = Sexp::new(if *node.node_type() == ASTType::Code { "code" } else { "verbatim" }, true);
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::Superscript | ASTType::Subscript => {
+            let name = if *node.node_type() == ASTType::Superscript { "superscript" } else { "subscript" };
+            let mut sexp = Sexp::new(name, true);
+            sexp.property("use-brackets-p", boolean(raw_value.contains('{')));
+            sexp
+        }
+        ASTType::HardBreak => Sexp::new("line-break", true),
+        ASTType::Link => {
+            let mut sexp = Sexp::new("link", true);
+            if let ASTMetaData::Link(meta) = node.meta() {
+                let (format, raw_link) = match meta.format {
+                    LinkFormat::Bracket => {
+                        let inner = raw_value.trim_start_matches("[[");
+                        ("bracket", inner.split("][").next().unwrap_or(inner).trim_end_matches("]]"))
+                    }
+                    LinkFormat::Angle => ("angle", raw_value.trim_start_matches('<').trim_end_matches('>')),
+                    LinkFormat::Plain => ("plain", raw_value.as_str()),
+                };
+                sexp.property("type", string(&meta.link_type));
+                sexp.property("path", string(&meta.path));
+                sexp.property("format", format.to_string());
+                sexp.property("raw-link", string(raw_link));
+                sexp.property("search-option", optional(meta.search_option.as_deref()));
+            }
+            sexp
+        }
+        ASTType::Target | ASTType::RadioTarget => {
+            let name = if *node.node_type() == ASTType::Target { "target" } else { "radio-target" };
+            let mut sexp = Sexp::new(name, true);
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::FootnoteReference => {
+            let mut sexp = Sexp::new("footnote-reference", true);
+            if let ASTMetaData::Footnote(meta) = node.meta() {
+                sexp.property("label", optional(meta.label.as_deref()));
+                sexp.property("type", if meta.inline { "inline" } else { "standard" }.to_string());
+            }
+            sexp
+        }
+        ASTType::Timestamp => {
+            let mut sexp = Sexp::new("timestamp", true);
+            if let ASTMetaData::Timestamp(meta) = node.meta() {
+                timestamp_properties(&mut sexp, meta);
+            }
+            sexp.property("raw-value", string(raw_value));
+            sexp
+        }
+        ASTType::StatisticsCookie => {
+            let mut sexp = Sexp::new("statistics-cookie", true);
+            sexp.property("value", string(raw_value));
+            sexp
+        }
+        ASTType::LatexFragment => {
+            let mut sexp = Sexp::new("latex-fragment", true);
+            sexp.property("value", string(raw_value));
+            sexp
+        }
+        ASTType::Entity => {
+            let mut sexp = Sexp::new("entity", true);
+            if let ASTMetaData::Entity(meta) = node.meta() {
+                sexp.property("name", string(&meta.name));
+                sexp.property("latex", string(&meta.latex));
+                sexp.property("latex-math-p", boolean(meta.math));
+                sexp.property("html", string(&meta.html));
+                sexp.property("utf-8", string(&meta.utf8));
+            }
+            sexp.property("use-brackets-p", boolean(raw_value.ends_with("{}")));
+            sexp
+        }
+        ASTType::Macro => {
+            let mut sexp = Sexp::new("macro", true);
+            if let ASTMetaData::Macro(meta) = node.meta() {
+                sexp.property("key", string(&meta.name));
+                sexp.property("value", string(raw_value));
+                sexp.property("args", list(meta.arguments.iter().map(|argument| string(argument)).collect()));
+            }
+            sexp
+        }
+        ASTType::InlineBabelCall => {
+            let mut sexp = Sexp::new("inline-babel-call", true);
+            babel_call_properties(&mut sexp, node);
+            sexp.property("value", string(raw_value));
+            sexp
+        }
+        ASTType::InlineSrcBlock => {
+            let mut sexp = Sexp::new("inline-src-block", true);
+            if let ASTMetaData::Block(meta) = node.meta() {
+                sexp.property("language", optional(meta.language.as_deref()));
+                sexp.property("parameters", optional(Some(meta.parameters.as_str()).filter(|p| !p.is_empty())));
+            }
+            sexp.property("value", string(node.value()));
+            sexp
+        }
+        ASTType::TableCell => Sexp::new("table-cell", true),
+        // オブジェクトの並びに紛れた要素
+        _ => return render_element(node, &node.range().end, 0),
+    };
+
+    let position = vec![
+        ("begin".to_string(), point(&node.range().begin)),
+        ("end".to_string(), point(&node.range().end)),
+    ];
+    sexp.properties.splice(0..0, position);
+    // マクロは展開した結果を出さない
+    if *node.node_type() != ASTType::Macro {
+        sexp.contents = render_objects(&children);
+    }
+    sexp.render(0)
+}
+
+fn timestamp_properties(sexp: &mut Sexp, meta: &TimestampMeta) {
+    let kind = match meta.kind {
+        TimestampKind::Active => "active",
+        TimestampKind::Inactive => "inactive",
+        TimestampKind::ActiveRange => "active-range",
+        TimestampKind::InactiveRange => "inactive-range",
+    };
+    sexp.property("type", kind.to_string());
+    // 終わりが無い場合は始まりと同じ
+    let end_date = meta.end_date.unwrap_or(meta.date);
+    let end_time = meta.end_time.or(meta.time);
+    let hour = |time: Option<OrgTime>| time.map(|time| time.hour.to_string()).unwrap_or_else(|| "nil".to_string());
+    let minute = |time: Option<OrgTime>| time.map(|time| time.minute.to_string()).unwrap_or_else(|| "nil".to_string());
+    sexp.property("year-start", meta.date.year.to_string());
+    sexp.property("month-start", meta.date.month.to_string());
+    sexp.property("day-start", meta.date.day.to_string());
+    sexp.property("hour-start", hour(meta.time));
+    sexp.property("minute-start", minute(meta.time));
+    sexp.property("year-end", end_date.year.to_string());
+    sexp.property("month-end", end_date.month.to_string());
+    sexp.property("day-end", end_date.day.to_string());
+    sexp.property("hour-end", hour(end_time));
+    sexp.property("minute-end", minute(end_time));
+    let unit = |unit: &TimeUnit| {
+        match unit {
+            TimeUnit::Hour => "hour",
+            TimeUnit::Day => "day",
+            TimeUnit::Week => "week",
+            TimeUnit::Month => "month",
+            TimeUnit::Year => "year",
+        }
+        .to_string()
+    };
+    if let Some(repeater) = &meta.repeater {
+        let kind = match repeater.kind {
+            RepeaterKind::Cumulate => "cumulate",
+            RepeaterKind::CatchUp => "catch-up",
+            RepeaterKind::Restart => "restart",
+        };
+        sexp.property("repeater-type", kind.to_string());
+        sexp.property("repeater-value", repeater.value.to_string());
+        sexp.property("repeater-unit", unit(&repeater.unit));
+    }
+    if let Some(delay) = &meta.delay {
+        let kind = match delay.kind {
+            DelayKind::All => "all",
+            DelayKind::First => "first",
+        };
+        sexp.property("warning-type", kind.to_string());
+        sexp.property("warning-value", delay.value.to_string());
+        sexp.property("warning-unit", unit(&delay.unit));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org_parser::org_parse;
+
+    fn render(src: &str) -> String {
+        render_org_element(org_parse(src, ASTNode::new(ASTElm::new_document())).root_node())
+    }
+
+    #[test]
+    fn test_render_org_element() {
+        let src = "Some *bold* text\n\
+                   \n\
+                   * TODO [#A] Title :work:\n\
+                   :PROPERTIES:\n\
+                   :ID: abc\n\
+                   :END:\n\
+                   ** Sub\n\
+                   - [X] one\n\
+                   - two\n";
+        let rendered = render(src);
+        // 要素は深さに応じて字下げする
+        assert!(rendered.contains(")\n  (section (") && rendered.contains(")\n    (paragraph ("));
+        let lines: Vec<&str> = rendered.lines().map(str::trim_start).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "(org-data (:begin 1 :end 95)",
+                "(section (:begin 1 :end 19 :post-blank 1)",
+                "(paragraph (:begin 1 :end 19 :post-blank 1) \"Some \" (bold (:begin 6 :end 12) \"bold\") \" text\"))",
+                "(headline (:begin 19 :end 95 :raw-value \"Title\" :level 1 :priority 65 :tags (\"work\") \
+                 :todo-keyword \"TODO\" :todo-type todo :commentedp nil :archivedp nil :ID \"abc\" :title (\"Title\"))",
+                "(section (:begin 44 :end 72 :post-blank 0)",
+                "(property-drawer (:begin 44 :end 72 :post-blank 0)",
+                "(node-property (:begin 57 :end 66 :key \"ID\" :value \"abc\" :post-blank 0))))",
+                "(headline (:begin 72 :end 95 :raw-value \"Sub\" :level 2 :priority nil :tags nil :todo-keyword nil \
+                 :todo-type nil :commentedp nil :archivedp nil :title (\"Sub\"))",
+                "(section (:begin 79 :end 95 :post-blank 0)",
+                "(plain-list (:begin 79 :end 95 :type unordered :post-blank 0)",
+                "(item (:begin 79 :end 89 :bullet \"- \" :checkbox on :counter nil :tag nil :post-blank 0)",
+                "(paragraph (:begin 85 :end 89 :post-blank 0) \"one\"))",
+                "(item (:begin 89 :end 95 :bullet \"- \" :checkbox nil :counter nil :tag nil :post-blank 0)",
+                "(paragraph (:begin 91 :end 95 :post-blank 0) \"two\")))))))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_org_element_properties() {
+        let src = "* Task\n\
+                   SCHEDULED: <2024-01-05 Fri 10:00-11:30 ++1w -2d>\n\
+                   #+NAME: answer\n\
+                   #+CAPTION[Short]: Long \"quoted\"\n\
+                   #+ATTR_HTML: :width 300\n\
+                   #+BEGIN_SRC emacs-lisp -n :results silent\n\
+                   (+ 1 2)\n\
+                   #+END_SRC\n\
+                   | a | b |\n\
+                   |---+---|\n\
+                   #+TBLFM: $2=$1*2\n";
+        let rendered = render(src);
+        let timestamp = "(timestamp (:begin 19 :end 56 :type active-range :year-start 2024 :month-start 1 :day-start 5 \
+                         :hour-start 10 :minute-start 0 :year-end 2024 :month-end 1 :day-end 5 :hour-end 11 \
+                         :minute-end 30 :repeater-type catch-up :repeater-value 1 :repeater-unit week \
+                         :warning-type all :warning-value 2 :warning-unit day \
+                         :raw-value \"<2024-01-05 Fri 10:00-11:30 ++1w -2d>\"))";
+        assert!(rendered.contains(&format!(":archivedp nil :scheduled {} :title (\"Task\")", timestamp)));
+        assert!(rendered.contains(&format!("(planning (:begin 8 :end 57 :scheduled {} :post-blank 0))", timestamp)));
+        assert!(rendered.contains(
+            "(src-block (:begin 57 :end 188 :language \"emacs-lisp\" :switches \"-n\" :parameters \":results silent\" \
+             :value \"(+ 1 2)\n\" :post-blank 0 :post-affiliated 128 :name \"answer\" \
+             :caption (((\"Long \\\"quoted\\\"\") \"Short\")) :attr_html (\":width 300\"))"
+        ));
+        assert!(rendered.contains("(table (:begin 188 :end 225 :type org :tblfm (\"$2=$1*2\") :post-blank 0)"));
+        assert!(rendered.contains("(table-row (:begin 198 :end 208 :type rule :post-blank 0))"));
+    }
+}
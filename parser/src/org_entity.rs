@@ -0,0 +1,436 @@
+use crate::ast::EntityMeta;
+
+/*
+ * Org のエンティティ (\alpha, \rarr, \nbsp など) の変換テーブル
+ * (名前, LaTeX, LaTeX の数式モードで使うか, HTML, UTF-8)
+ * NOTE: 二分探索を行うので、名前のバイト順でソートしておくこと
+ */
+static ENTITY_TABLE: &[(&str, &str, bool, &str, &str)] = &[
+    ("AA", "\\AA{}", false, "&Aring;", "Å"),
+    ("AElig", "\\AE{}", false, "&AElig;", "Æ"),
+    ("Aacute", "\\'{A}", false, "&Aacute;", "Á"),
+    ("Acirc", "\\^{A}", false, "&Acirc;", "Â"),
+    ("Agrave", "\\`{A}", false, "&Agrave;", "À"),
+    ("Alpha", "A", true, "&Alpha;", "Α"),
+    ("Amacr", "\\={A}", false, "&Amacr;", "Ā"),
+    ("Aring", "\\AA{}", false, "&Aring;", "Å"),
+    ("Atilde", "\\~{A}", false, "&Atilde;", "Ã"),
+    ("Auml", "\\\"{A}", false, "&Auml;", "Ä"),
+    ("Beta", "B", true, "&Beta;", "Β"),
+    ("COPY", "\\textcopyright{}", false, "&COPY;", "©"),
+    ("Ccedil", "\\c{C}", false, "&Ccedil;", "Ç"),
+    ("Chi", "X", true, "&Chi;", "Χ"),
+    ("Dagger", "\\textdaggerdbl{}", false, "&Dagger;", "‡"),
+    ("Delta", "\\Delta", true, "&Delta;", "Δ"),
+    ("Diamond", "\\diamondsuit", true, "&Diamond;", "⋄"),
+    ("Downarrow", "\\Downarrow", true, "&Downarrow;", "⇓"),
+    ("ETH", "\\DH{}", false, "&ETH;", "Ð"),
+    ("EUR", "\\texteuro{}", false, "&euro;", "€"),
+    ("Eacute", "\\'{E}", false, "&Eacute;", "É"),
+    ("Ecirc", "\\^{E}", false, "&Ecirc;", "Ê"),
+    ("Egrave", "\\`{E}", false, "&Egrave;", "È"),
+    ("Epsilon", "E", true, "&Epsilon;", "Ε"),
+    ("Eta", "H", true, "&Eta;", "Η"),
+    ("Euml", "\\\"{E}", false, "&Euml;", "Ë"),
+    ("Gamma", "\\Gamma", true, "&Gamma;", "Γ"),
+    ("Gg", "\\ggg", true, "&Gg;", "⋙"),
+    ("Iacute", "\\'{I}", false, "&Iacute;", "Í"),
+    ("Icirc", "\\^{I}", false, "&Icirc;", "Î"),
+    ("Idot", "\\.{I}", false, "&Idot;", "İ"),
+    ("Igrave", "\\`{I}", false, "&Igrave;", "Ì"),
+    ("Iota", "I", true, "&Iota;", "Ι"),
+    ("Iuml", "\\\"{I}", false, "&Iuml;", "Ï"),
+    ("Kappa", "K", true, "&Kappa;", "Κ"),
+    ("Lambda", "\\Lambda", true, "&Lambda;", "Λ"),
+    ("Leftarrow", "\\Leftarrow", true, "&Leftarrow;", "⇐"),
+    ("Leftrightarrow", "\\Leftrightarrow", true, "&Leftrightarrow;", "⇔"),
+    ("Ll", "\\lll", true, "&Ll;", "⋘"),
+    ("Mu", "M", true, "&Mu;", "Μ"),
+    ("Ntilde", "\\~{N}", false, "&Ntilde;", "Ñ"),
+    ("Nu", "N", true, "&Nu;", "Ν"),
+    ("OElig", "\\OE{}", false, "&OElig;", "Œ"),
+    ("Oacute", "\\'{O}", false, "&Oacute;", "Ó"),
+    ("Ocirc", "\\^{O}", false, "&Ocirc;", "Ô"),
+    ("Ograve", "\\`{O}", false, "&Ograve;", "Ò"),
+    ("Omega", "\\Omega", true, "&Omega;", "Ω"),
+    ("Omicron", "O", true, "&Omicron;", "Ο"),
+    ("Oslash", "\\O", false, "&Oslash;", "Ø"),
+    ("Otilde", "\\~{O}", false, "&Otilde;", "Õ"),
+    ("Ouml", "\\\"{O}", false, "&Ouml;", "Ö"),
+    ("Phi", "\\Phi", true, "&Phi;", "Φ"),
+    ("Pi", "\\Pi", true, "&Pi;", "Π"),
+    ("Pr", "\\Pr", true, "Pr", "Pr"),
+    ("Prime", "\\prime{}\\prime", true, "&Prime;", "″"),
+    ("Psi", "\\Psi", true, "&Psi;", "Ψ"),
+    ("Rho", "P", true, "&Rho;", "Ρ"),
+    ("Rightarrow", "\\Rightarrow", true, "&Rightarrow;", "⇒"),
+    ("S", "\\S", false, "&sect;", "§"),
+    ("Scaron", "\\v{S}", false, "&Scaron;", "Š"),
+    ("Sigma", "\\Sigma", true, "&Sigma;", "Σ"),
+    ("THORN", "\\TH{}", false, "&THORN;", "Þ"),
+    ("Tau", "T", true, "&Tau;", "Τ"),
+    ("Theta", "\\Theta", true, "&Theta;", "Θ"),
+    ("USD", "\\$", false, "&dollar;", "$"),
+    ("Uacute", "\\'{U}", false, "&Uacute;", "Ú"),
+    ("Ucirc", "\\^{U}", false, "&Ucirc;", "Û"),
+    ("Ugrave", "\\`{U}", false, "&Ugrave;", "Ù"),
+    ("Uparrow", "\\Uparrow", true, "&Uparrow;", "⇑"),
+    ("Upsilon", "\\Upsilon", true, "&Upsilon;", "Υ"),
+    ("Uuml", "\\\"{U}", false, "&Uuml;", "Ü"),
+    ("Xi", "\\Xi", true, "&Xi;", "Ξ"),
+    ("Yacute", "\\'{Y}", false, "&Yacute;", "Ý"),
+    ("Yuml", "\\\"{Y}", false, "&Yuml;", "Ÿ"),
+    ("Zeta", "Z", true, "&Zeta;", "Ζ"),
+    ("aacute", "\\'{a}", false, "&aacute;", "á"),
+    ("acirc", "\\^{a}", false, "&acirc;", "â"),
+    ("acute", "\\textasciiacute{}", false, "&acute;", "´"),
+    ("aelig", "\\ae{}", false, "&aelig;", "æ"),
+    ("agrave", "\\`{a}", false, "&agrave;", "à"),
+    ("alefsym", "\\aleph", true, "&alefsym;", "ℵ"),
+    ("aleph", "\\aleph", true, "&aleph;", "ℵ"),
+    ("alpha", "\\alpha", true, "&alpha;", "α"),
+    ("amacr", "\\={a}", false, "&amacr;", "ā"),
+    ("amp", "\\&", false, "&amp;", "&"),
+    ("ang", "\\angle", true, "&ang;", "∠"),
+    ("angle", "\\angle", true, "&angle;", "∠"),
+    ("approx", "\\approx", true, "&approx;", "≈"),
+    ("arccos", "\\arccos", true, "arccos", "arccos"),
+    ("arcsin", "\\arcsin", true, "arcsin", "arcsin"),
+    ("arctan", "\\arctan", true, "arctan", "arctan"),
+    ("arg", "\\arg", true, "arg", "arg"),
+    ("aring", "\\aa{}", false, "&aring;", "å"),
+    ("asciicirc", "\\textasciicircum{}", false, "^", "^"),
+    ("ast", "\\ast", true, "&ast;", "*"),
+    ("asymp", "\\asymp", true, "&asymp;", "≈"),
+    ("atilde", "\\~{a}", false, "&atilde;", "ã"),
+    ("auml", "\\\"{a}", false, "&auml;", "ä"),
+    ("bdquo", "\\quotedblbase{}", false, "&bdquo;", "„"),
+    ("because", "\\because", true, "&because;", "∵"),
+    ("beta", "\\beta", true, "&beta;", "β"),
+    ("beth", "\\beth", true, "&#x5D1;", "ב"),
+    ("blacksmile", "\\ddot\\smile", true, "&#x263B;", "☻"),
+    ("brvbar", "\\textbrokenbar{}", false, "&brvbar;", "¦"),
+    ("bull", "\\textbullet{}", false, "&bull;", "•"),
+    ("bullet", "\\textbullet{}", false, "&bullet;", "•"),
+    ("cap", "\\cap", true, "&cap;", "∩"),
+    ("ccedil", "\\c{c}", false, "&ccedil;", "ç"),
+    ("cdot", "\\cdot", true, "&sdot;", "⋅"),
+    ("cdots", "\\cdots{}", true, "&#x22EF;", "⋯"),
+    ("cedil", "\\c{}", false, "&cedil;", "¸"),
+    ("cent", "\\textcent{}", false, "&cent;", "¢"),
+    ("check", "\\checkmark", true, "&check;", "✓"),
+    ("checkmark", "\\checkmark", true, "&checkmark;", "✓"),
+    ("chi", "\\chi", true, "&chi;", "χ"),
+    ("circ", "\\^{}", false, "&circ;", "ˆ"),
+    ("clubs", "\\clubsuit", true, "&clubs;", "♣"),
+    ("clubsuit", "\\clubsuit", true, "&clubsuit;", "♣"),
+    ("colon", "\\colon", true, "&#x2236;", "∶"),
+    ("cong", "\\cong", true, "&cong;", "≅"),
+    ("copy", "\\textcopyright{}", false, "&copy;", "©"),
+    ("cos", "\\cos", true, "cos", "cos"),
+    ("cosh", "\\cosh", true, "cosh", "cosh"),
+    ("cot", "\\cot", true, "cot", "cot"),
+    ("coth", "\\coth", true, "coth", "coth"),
+    ("crarr", "\\hookleftarrow", true, "&crarr;", "↵"),
+    ("csc", "\\csc", true, "csc", "csc"),
+    ("cup", "\\cup", true, "&cup;", "∪"),
+    ("curren", "\\textcurrency{}", false, "&curren;", "¤"),
+    ("dArr", "\\Downarrow", true, "&dArr;", "⇓"),
+    ("dag", "\\dag{}", false, "&dagger;", "†"),
+    ("dagger", "\\textdagger{}", false, "&dagger;", "†"),
+    ("dalet", "\\daleth", true, "&#x5D3;", "ד"),
+    ("darr", "\\downarrow", true, "&darr;", "↓"),
+    ("ddag", "\\ddag{}", false, "&Dagger;", "‡"),
+    ("deg", "\\textdegree{}", false, "&deg;", "°"),
+    ("delta", "\\delta", true, "&delta;", "δ"),
+    ("det", "\\det", true, "det", "det"),
+    ("diamond", "\\diamondsuit", true, "&diamond;", "⋄"),
+    ("diamondsuit", "\\diamondsuit", true, "&#x25C6;", "◆"),
+    ("diams", "\\diamondsuit", true, "&#x25C6;", "◆"),
+    ("dim", "\\dim", true, "dim", "dim"),
+    ("div", "\\textdiv{}", false, "&div;", "÷"),
+    ("dollar", "\\$", false, "&dollar;", "$"),
+    ("dots", "\\dots{}", false, "&hellip;", "…"),
+    ("downarrow", "\\downarrow", true, "&downarrow;", "↓"),
+    ("eacute", "\\'{e}", false, "&eacute;", "é"),
+    ("ecirc", "\\^{e}", false, "&ecirc;", "ê"),
+    ("egrave", "\\`{e}", false, "&egrave;", "è"),
+    ("ell", "\\ell", true, "&ell;", "ℓ"),
+    ("empty", "\\emptyset", true, "&empty;", "∅"),
+    ("emptyset", "\\emptyset", true, "&emptyset;", "∅"),
+    ("emsp", "\\hspace*{1em}", false, "&emsp;", "\u{2003}"),
+    ("ensp", "\\hspace*{.5em}", false, "&ensp;", "\u{2002}"),
+    ("epsilon", "\\epsilon", true, "&epsilon;", "ε"),
+    ("equal", "=", false, "=", "="),
+    ("equiv", "\\equiv", true, "&equiv;", "≡"),
+    ("eta", "\\eta", true, "&eta;", "η"),
+    ("eth", "\\dh{}", false, "&eth;", "ð"),
+    ("euml", "\\\"{e}", false, "&euml;", "ë"),
+    ("euro", "\\texteuro{}", false, "&euro;", "€"),
+    ("exist", "\\exists", true, "&exist;", "∃"),
+    ("exists", "\\exists", true, "&exist;", "∃"),
+    ("exp", "\\exp", true, "exp", "exp"),
+    ("fnof", "\\textit{f}", false, "&fnof;", "ƒ"),
+    ("forall", "\\forall", true, "&forall;", "∀"),
+    ("frac12", "\\textonehalf{}", false, "&frac12;", "½"),
+    ("frac14", "\\textonequarter{}", false, "&frac14;", "¼"),
+    ("frac34", "\\textthreequarters{}", false, "&frac34;", "¾"),
+    ("frasl", "/", false, "&frasl;", "⁄"),
+    ("frown", "\\frown", true, "&frown;", "⌢"),
+    ("frowny", "\\ddot\\frown", true, "&#x2639;", "☹"),
+    ("gamma", "\\gamma", true, "&gamma;", "γ"),
+    ("gcd", "\\gcd", true, "gcd", "gcd"),
+    ("ge", "\\ge", true, "&ge;", "≥"),
+    ("geq", "\\ge", true, "&geq;", "≥"),
+    ("gets", "\\gets", true, "&larr;", "←"),
+    ("gg", "\\gg", true, "&gg;", "≫"),
+    ("ggg", "\\ggg", true, "&ggg;", "⋙"),
+    ("gimel", "\\gimel", true, "&gimel;", "ℷ"),
+    ("gt", "\\textgreater{}", false, "&gt;", ">"),
+    ("hArr", "\\Leftrightarrow", true, "&hArr;", "⇔"),
+    ("harr", "\\leftrightarrow", true, "&harr;", "↔"),
+    ("hbar", "\\hbar", true, "&hbar;", "ℏ"),
+    ("hearts", "\\heartsuit", true, "&hearts;", "♥"),
+    ("heartsuit", "\\heartsuit", true, "&heartsuit;", "♥"),
+    ("hellip", "\\dots{}", false, "&hellip;", "…"),
+    ("hom", "\\hom", true, "hom", "hom"),
+    ("hookleftarrow", "\\hookleftarrow", true, "&crarr;", "↵"),
+    ("iacute", "\\'{i}", false, "&iacute;", "í"),
+    ("icirc", "\\^{i}", false, "&icirc;", "î"),
+    ("iexcl", "!`", false, "&iexcl;", "¡"),
+    ("igrave", "\\`{i}", false, "&igrave;", "ì"),
+    ("image", "\\Im", true, "&image;", "ℑ"),
+    ("imath", "\\imath", true, "&imath;", "ı"),
+    ("in", "\\in", true, "&in;", "∈"),
+    ("inf", "\\inf", true, "inf", "inf"),
+    ("infin", "\\infty", true, "&infin;", "∞"),
+    ("infty", "\\infty", true, "&infin;", "∞"),
+    ("inodot", "\\i", false, "&inodot;", "ı"),
+    ("int", "\\int", true, "&int;", "∫"),
+    ("iota", "\\iota", true, "&iota;", "ι"),
+    ("iquest", "?`", false, "&iquest;", "¿"),
+    ("isin", "\\in", true, "&isin;", "∈"),
+    ("iuml", "\\\"{i}", false, "&iuml;", "ï"),
+    ("jmath", "\\jmath", true, "&jmath;", "ȷ"),
+    ("kappa", "\\kappa", true, "&kappa;", "κ"),
+    ("ker", "\\ker", true, "ker", "ker"),
+    ("lArr", "\\Leftarrow", true, "&lArr;", "⇐"),
+    ("lambda", "\\lambda", true, "&lambda;", "λ"),
+    ("land", "\\land", true, "&wedge;", "∧"),
+    ("lang", "\\langle", true, "&lang;", "⟨"),
+    ("langle", "\\langle", true, "&langle;", "⟨"),
+    ("laquo", "\\guillemotleft{}", false, "&laquo;", "«"),
+    ("larr", "\\leftarrow", true, "&larr;", "←"),
+    ("lceil", "\\lceil", true, "&lceil;", "⌈"),
+    ("ldquo", "\\textquotedblleft{}", false, "&ldquo;", "“"),
+    ("le", "\\le", true, "&le;", "≤"),
+    ("leftarrow", "\\leftarrow", true, "&leftarrow;", "←"),
+    ("leftrightarrow", "\\leftrightarrow", true, "&leftrightarrow;", "↔"),
+    ("leq", "\\le", true, "&leq;", "≤"),
+    ("lesseqgtr", "\\lesseqgtr", true, "&lesseqgtr;", "⋚"),
+    ("lessgtr", "\\lessgtr", true, "&lessgtr;", "≶"),
+    ("lfloor", "\\lfloor", true, "&lfloor;", "⌊"),
+    ("lg", "\\lg", true, "lg", "lg"),
+    ("lim", "\\lim", true, "lim", "lim"),
+    ("liminf", "\\liminf", true, "liminf", "liminf"),
+    ("limsup", "\\limsup", true, "limsup", "limsup"),
+    ("ll", "\\ll", true, "&ll;", "≪"),
+    ("lll", "\\lll", true, "&Ll;", "⋘"),
+    ("ln", "\\ln", true, "ln", "ln"),
+    ("log", "\\log", true, "log", "log"),
+    ("lor", "\\lor", true, "&vee;", "∨"),
+    ("lowast", "\\ast", true, "&lowast;", "∗"),
+    ("loz", "\\lozenge", true, "&loz;", "◊"),
+    ("lrm", "", false, "&lrm;", "\u{200e}"),
+    ("lsaquo", "\\guilsinglleft{}", false, "&lsaquo;", "‹"),
+    ("lsquo", "\\textquoteleft{}", false, "&lsquo;", "‘"),
+    ("lt", "\\textless{}", false, "&lt;", "<"),
+    ("macr", "\\textasciimacron{}", false, "&macr;", "¯"),
+    ("max", "\\max", true, "max", "max"),
+    ("mdash", "---", false, "&mdash;", "—"),
+    ("mho", "\\mho", true, "&mho;", "℧"),
+    ("micro", "\\textmu{}", false, "&micro;", "µ"),
+    ("middot", "\\textperiodcentered{}", false, "&middot;", "·"),
+    ("min", "\\min", true, "min", "min"),
+    ("minus", "-", true, "&minus;", "−"),
+    ("mu", "\\mu", true, "&mu;", "μ"),
+    ("nabla", "\\nabla", true, "&nabla;", "∇"),
+    ("nbsp", "~", false, "&nbsp;", "\u{a0}"),
+    ("ndash", "--", false, "&ndash;", "–"),
+    ("ne", "\\ne", true, "&ne;", "≠"),
+    ("neg", "\\neg", true, "&not;", "¬"),
+    ("neq", "\\neq", true, "&ne;", "≠"),
+    ("nexist", "\\nexists", true, "&nexist;", "∄"),
+    ("nexists", "\\nexists", true, "&nexists;", "∄"),
+    ("ni", "\\ni", true, "&ni;", "∋"),
+    ("not", "\\textlnot{}", false, "&not;", "¬"),
+    ("notin", "\\notin", true, "&notin;", "∉"),
+    ("nsub", "\\not\\subset", true, "&nsub;", "⊄"),
+    ("nsup", "\\not\\supset", true, "&nsup;", "⊅"),
+    ("ntilde", "\\~{n}", false, "&ntilde;", "ñ"),
+    ("nu", "\\nu", true, "&nu;", "ν"),
+    ("oacute", "\\'{o}", false, "&oacute;", "ó"),
+    ("ocirc", "\\^{o}", false, "&ocirc;", "ô"),
+    ("odot", "\\odot", true, "&#x298;", "ʘ"),
+    ("oelig", "\\oe{}", false, "&oelig;", "œ"),
+    ("ograve", "\\`{o}", false, "&ograve;", "ò"),
+    ("oline", "\\overline{~}", true, "&oline;", "‾"),
+    ("omega", "\\omega", true, "&omega;", "ω"),
+    ("omicron", "\\textit{o}", true, "&omicron;", "ο"),
+    ("oplus", "\\oplus", true, "&oplus;", "⊕"),
+    ("ordf", "\\textordfeminine{}", false, "&ordf;", "ª"),
+    ("ordm", "\\textordmasculine{}", false, "&ordm;", "º"),
+    ("oslash", "\\o{}", false, "&oslash;", "ø"),
+    ("otilde", "\\~{o}", false, "&otilde;", "õ"),
+    ("otimes", "\\otimes", true, "&otimes;", "⊗"),
+    ("ouml", "\\\"{o}", false, "&ouml;", "ö"),
+    ("para", "\\P{}", false, "&para;", "¶"),
+    ("parallel", "\\parallel", true, "&parallel;", "∥"),
+    ("partial", "\\partial", true, "&#x2202;", "∂"),
+    ("permil", "\\textperthousand{}", false, "&permil;", "‰"),
+    ("perp", "\\perp", true, "&perp;", "⊥"),
+    ("phi", "\\phi", true, "&phi;", "ɸ"),
+    ("pi", "\\pi", true, "&pi;", "π"),
+    ("piv", "\\varpi", true, "&piv;", "ϖ"),
+    ("plus", "+", false, "&plus;", "+"),
+    ("plusmn", "\\textpm{}", false, "&plusmn;", "±"),
+    ("pm", "\\textpm{}", false, "&pm;", "±"),
+    ("pound", "\\pounds{}", false, "&pound;", "£"),
+    ("prec", "\\prec", true, "&prec;", "≺"),
+    ("preccurlyeq", "\\preccurlyeq", true, "&preccurlyeq;", "≼"),
+    ("preceq", "\\preceq", true, "&preccurlyeq;", "≼"),
+    ("prime", "\\prime", true, "&prime;", "′"),
+    ("prod", "\\prod", true, "&prod;", "∏"),
+    ("prop", "\\propto", true, "&prop;", "∝"),
+    ("propto", "\\propto", true, "&propto;", "∝"),
+    ("psi", "\\psi", true, "&psi;", "ψ"),
+    ("quot", "\\textquotedbl{}", false, "&quot;", "\""),
+    ("rArr", "\\Rightarrow", true, "&rArr;", "⇒"),
+    ("radic", "\\sqrt{\\,}", true, "&radic;", "√"),
+    ("rang", "\\rangle", true, "&rang;", "⟩"),
+    ("rangle", "\\rangle", true, "&rangle;", "⟩"),
+    ("raquo", "\\guillemotright{}", false, "&raquo;", "»"),
+    ("rarr", "\\rightarrow", true, "&rarr;", "→"),
+    ("rceil", "\\rceil", true, "&rceil;", "⌉"),
+    ("rdquo", "\\textquotedblright{}", false, "&rdquo;", "”"),
+    ("real", "\\Re", true, "&real;", "ℜ"),
+    ("reg", "\\textregistered{}", false, "&reg;", "®"),
+    ("rfloor", "\\rfloor", true, "&rfloor;", "⌋"),
+    ("rho", "\\rho", true, "&rho;", "ρ"),
+    ("rightarrow", "\\rightarrow", true, "&rightarrow;", "→"),
+    ("rlm", "", false, "&rlm;", "\u{200f}"),
+    ("rsaquo", "\\guilsinglright{}", false, "&rsaquo;", "›"),
+    ("rsquo", "\\textquoteright{}", false, "&rsquo;", "’"),
+    ("sad", "\\ddot\\frown", true, "&#x2639;", "☹"),
+    ("sbquo", "\\quotesinglbase{}", false, "&sbquo;", "‚"),
+    ("scaron", "\\v{s}", false, "&scaron;", "š"),
+    ("sdot", "\\cdot", true, "&sdot;", "⋅"),
+    ("sec", "\\sec", true, "sec", "sec"),
+    ("sect", "\\S", false, "&sect;", "§"),
+    ("setminus", "\\setminus", true, "&setminus;", "∖"),
+    ("shy", "\\-", false, "&shy;", "\u{ad}"),
+    ("sigma", "\\sigma", true, "&sigma;", "σ"),
+    ("sigmaf", "\\varsigma", true, "&sigmaf;", "ς"),
+    ("sim", "\\sim", true, "&sim;", "∼"),
+    ("simeq", "\\simeq", true, "&cong;", "≅"),
+    ("sin", "\\sin", true, "sin", "sin"),
+    ("sinh", "\\sinh", true, "sinh", "sinh"),
+    ("slash", "/", false, "/", "/"),
+    ("smile", "\\smile", true, "&smile;", "⌣"),
+    ("smiley", "\\ddot\\smile", true, "&#x263A;", "☺"),
+    ("spades", "\\spadesuit", true, "&spades;", "♠"),
+    ("spadesuit", "\\spadesuit", true, "&spadesuit;", "♠"),
+    ("star", "\\star", true, "&#x22C6;", "⋆"),
+    ("sub", "\\subset", true, "&sub;", "⊂"),
+    ("sube", "\\subseteq", true, "&sube;", "⊆"),
+    ("subset", "\\subset", true, "&subset;", "⊂"),
+    ("succ", "\\succ", true, "&succ;", "≻"),
+    ("succcurlyeq", "\\succcurlyeq", true, "&succcurlyeq;", "≽"),
+    ("succeq", "\\succeq", true, "&succcurlyeq;", "≽"),
+    ("sum", "\\sum", true, "&sum;", "∑"),
+    ("sup", "\\supset", true, "&sup;", "⊃"),
+    ("sup1", "\\textonesuperior{}", false, "&sup1;", "¹"),
+    ("sup2", "\\texttwosuperior{}", false, "&sup2;", "²"),
+    ("sup3", "\\textthreesuperior{}", false, "&sup3;", "³"),
+    ("supe", "\\supseteq", true, "&supe;", "⊇"),
+    ("supset", "\\supset", true, "&supset;", "⊃"),
+    ("szlig", "\\ss{}", false, "&szlig;", "ß"),
+    ("tan", "\\tan", true, "tan", "tan"),
+    ("tanh", "\\tanh", true, "tanh", "tanh"),
+    ("tau", "\\tau", true, "&tau;", "τ"),
+    ("there4", "\\therefore", true, "&there4;", "∴"),
+    ("therefore", "\\therefore", true, "&therefore;", "∴"),
+    ("theta", "\\theta", true, "&theta;", "θ"),
+    ("thetasym", "\\vartheta", true, "&thetasym;", "ϑ"),
+    ("thinsp", "\\hspace*{.2em}", false, "&thinsp;", "\u{2009}"),
+    ("thorn", "\\th{}", false, "&thorn;", "þ"),
+    ("tilde", "\\textasciitilde{}", false, "~", "~"),
+    ("times", "\\texttimes{}", false, "&times;", "×"),
+    ("to", "\\to", true, "&rarr;", "→"),
+    ("trade", "\\texttrademark{}", false, "&trade;", "™"),
+    ("triangleq", "\\triangleq", true, "&triangleq;", "≜"),
+    ("uArr", "\\Uparrow", true, "&uArr;", "⇑"),
+    ("uacute", "\\'{u}", false, "&uacute;", "ú"),
+    ("uarr", "\\uparrow", true, "&uarr;", "↑"),
+    ("ucirc", "\\^{u}", false, "&ucirc;", "û"),
+    ("ugrave", "\\`{u}", false, "&ugrave;", "ù"),
+    ("uml", "\\textasciidieresis{}", false, "&uml;", "¨"),
+    ("under", "\\_", false, "_", "_"),
+    ("uparrow", "\\uparrow", true, "&uparrow;", "↑"),
+    ("upsih", "\\Upsilon", true, "&upsih;", "ϒ"),
+    ("upsilon", "\\upsilon", true, "&upsilon;", "υ"),
+    ("uuml", "\\\"{u}", false, "&uuml;", "ü"),
+    ("varepsilon", "\\varepsilon", true, "&epsilon;", "ε"),
+    ("varphi", "\\varphi", true, "&phi;", "φ"),
+    ("varpi", "\\varpi", true, "&varpi;", "ϖ"),
+    ("varsigma", "\\varsigma", true, "&varsigma;", "ς"),
+    ("vartheta", "\\vartheta", true, "&vartheta;", "ϑ"),
+    ("vbar", "|", false, "&vert;", "|"),
+    ("vee", "\\vee", true, "&vee;", "∨"),
+    ("vert", "\\vert{}", true, "&vert;", "|"),
+    ("wedge", "\\wedge", true, "&wedge;", "∧"),
+    ("weierp", "\\wp", true, "&weierp;", "℘"),
+    ("xi", "\\xi", true, "&xi;", "ξ"),
+    ("yacute", "\\'{y}", false, "&yacute;", "ý"),
+    ("yen", "\\textyen{}", false, "&yen;", "¥"),
+    ("yuml", "\\\"{y}", false, "&yuml;", "ÿ"),
+    ("zeta", "\\zeta", true, "&zeta;", "ζ"),
+    ("zwj", "", false, "&zwj;", "\u{200d}"),
+    ("zwnj", "\\/{}", false, "&zwnj;", "\u{200c}"),
+];
+
+// 名前(バックスラッシュを除いたもの)からエンティティを引く
+pub fn lookup_entity(name: &str) -> Option<EntityMeta> {
+    ENTITY_TABLE.binary_search_by(|(n, ..)| n.cmp(&name)).ok().map(|idx| {
+        let (name, latex, math, html, utf8) = ENTITY_TABLE[idx];
+        EntityMeta {
+            name: name.to_string(),
+            latex: latex.to_string(),
+            math,
+            html: html.to_string(),
+            utf8: utf8.to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_table_sorted() {
+        for pair in ENTITY_TABLE.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} must be placed before {}", pair[0].0, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_lookup_entity() {
+        let alpha = lookup_entity("alpha").unwrap();
+        assert_eq!((alpha.latex.as_str(), alpha.math, alpha.html.as_str(), alpha.utf8.as_str()), ("\\alpha", true, "&alpha;", "α"));
+        assert_eq!(lookup_entity("rarr").unwrap().utf8, "→");
+        assert_eq!(lookup_entity("nbsp").unwrap().html, "&nbsp;");
+        assert_eq!(lookup_entity("Alpha").unwrap().latex, "A");
+        assert_eq!(lookup_entity("not_an_entity"), None);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::ast::IncludeMeta;
+use crate::org_parser::split_parameters;
+
+use std::collections::BTreeMap;
+
+/*
+ * #+INCLUDE: で指定されたファイルを読み込む
+ * パーサーはファイルシステムに触れないので、読み込み方は呼び出し側で用意する (wasm でも使えるように)
+ * path は #+INCLUDE: に書かれたまま渡す
+ */
+pub trait FileLoader {
+    fn load(&self, path: &str) -> Result<String, String>;
+}
+
+// ファイル名と内容の対応表から読み込む
+impl FileLoader for BTreeMap<String, String> {
+    fn load(&self, path: &str) -> Result<String, String> {
+        self.get(path).cloned().ok_or_else(|| format!("{}: not found", path))
+    }
+}
+
+/*
+ * #+INCLUDE: の値
+ *   "file.org" [<ブロック名> [<引数>...]] [:lines "5-10"] [:minlevel 2]
+ * ブロック名を指定すると、内容をそのブロックで囲んだものとして扱う (src python, example, export html)
+ */
+pub fn parse_include_parameters(value: &str) -> IncludeMeta {
+    let mut meta = IncludeMeta::default();
+    let mut tokens = split_parameters(value).into_iter().peekable();
+    if let Some(file) = tokens.next() {
+        meta.file = file.trim_matches('"').to_string();
+    }
+    let mut parameters = vec![];
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            ":lines" => meta.lines = tokens.next().map(|lines| lines.trim_matches('"').to_string()),
+            ":minlevel" => meta.minlevel = tokens.next().and_then(|level| level.parse().ok()),
+            _ if token.starts_with(':') => {
+                // 未対応の引数は値ごと読み飛ばす
+                tokens.next_if(|value| !value.starts_with(':'));
+            }
+            _ if meta.block.is_none() => meta.block = Some(token.to_uppercase()),
+            _ => parameters.push(token),
+        }
+    }
+    meta.parameters = parameters.join(" ");
+    meta
+}
+
+/*
+ * :lines "5-10" の範囲を切り出す (1から数えて、終わりの行も含む)
+ * "-10" は先頭から、"10-" は末尾まで
+ */
+pub fn select_lines<'a>(text: &'a str, lines: &str) -> &'a str {
+    let (from, to) = match lines.split_once('-') {
+        Some((from, to)) => (from.trim().parse().unwrap_or(1usize).max(1), to.trim().parse::<usize>().ok()),
+        None => return text,
+    };
+    let line_start = |line: usize| -> usize {
+        if line <= 1 {
+            return 0;
+        }
+        text.match_indices('\n').nth(line - 2).map(|(idx, _)| idx + 1).unwrap_or(text.len())
+    };
+    let begin = line_start(from);
+    let end = to.map(|to| line_start(to + 1)).unwrap_or(text.len()).max(begin);
+    &text[begin..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_include_parameters() {
+        let meta = parse_include_parameters("\"code/main.py\" src python :lines \"5-10\" :minlevel 2");
+        assert_eq!(meta.file, "code/main.py");
+        assert_eq!((meta.block.as_deref(), meta.parameters.as_str()), (Some("SRC"), "python"));
+        assert_eq!((meta.lines.as_deref(), meta.minlevel), (Some("5-10"), Some(2)));
+
+        let meta = parse_include_parameters("\"chapter.org\" :only-contents t");
+        assert_eq!((meta.file.as_str(), meta.block), ("chapter.org", None));
+    }
+
+    #[test]
+    fn test_select_lines() {
+        let text = "1\n2\n3\n4\n5";
+        assert_eq!(select_lines(text, "2-4"), "2\n3\n4\n");
+        assert_eq!(select_lines(text, "-3"), "1\n2\n3\n");
+        assert_eq!(select_lines(text, "4-"), "4\n5");
+        assert_eq!(select_lines(text, "9-"), "");
+    }
+}
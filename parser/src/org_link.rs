@@ -0,0 +1,293 @@
+use crate::arena::{ASTArena, NodeId, NodeRef};
+use crate::ast::*;
+use std::collections::HashMap;
+
+// type:path の形式として扱うリンクの種類 (これ以外は fuzzy)
+pub const LINK_TYPES: &[&str] = &[
+    "attachment", "doi", "elisp", "file", "ftp", "help", "http", "https", "id", "info", "irc", "mailto", "news",
+    "shell",
+];
+
+pub fn is_link_type(s: &str) -> bool {
+    LINK_TYPES.contains(&s) || s.starts_with("file+")
+}
+
+/*
+ * リンク先の文字列を種類・パス・検索オプションに分解する
+ *   #custom-id          -> custom-id
+ *   (ref)               -> coderef
+ *   ./file.org, /path   -> file
+ *   type:path           -> type
+ *   *Headline, target   -> fuzzy
+ */
+pub fn parse_link_path(raw: &str, format: LinkFormat) -> LinkMeta {
+    // 角括弧リンクは改行・連続した空白を一つの空白として扱う
+    let raw = match format {
+        LinkFormat::Bracket => raw.split_whitespace().collect::<Vec<&str>>().join(" "),
+        _ => raw.to_string(),
+    };
+
+    let (link_type, path) = if let Some(id) = raw.strip_prefix('#') {
+        ("custom-id".to_string(), id.to_string())
+    } else if raw.len() > 2 && raw.starts_with('(') && raw.ends_with(')') {
+        ("coderef".to_string(), raw[1..raw.len() - 1].to_string())
+    } else if raw.starts_with('/') || raw.starts_with("./") || raw.starts_with("../") || raw.starts_with("~/") {
+        ("file".to_string(), raw.clone())
+    } else {
+        match raw.find(':') {
+            Some(idx) if is_link_type(&raw[..idx]) => {
+                let link_type = if raw.starts_with("file+") { "file" } else { &raw[..idx] };
+                (link_type.to_string(), raw[idx + 1..].to_string())
+            }
+            _ => ("fuzzy".to_string(), raw.clone()),
+        }
+    };
+
+    // file:x.org::*Heading
+    let (path, search_option) = match (link_type.as_str(), path.find("::")) {
+        ("file", Some(idx)) => (path[..idx].to_string(), Some(path[idx + 2..].to_string())),
+        _ => (path, None),
+    };
+
+    LinkMeta { link_type, path, search_option, format, resolved: None }
+}
+
+/*
+ * 文書内リンクの解決
+ *   custom-id     -> CUSTOM_ID プロパティを持つ見出し
+ *   fuzzy *Title  -> タイトルが一致する見出し
+ *   fuzzy         -> <<target>>、見つからなければタイトルが一致する見出し
+ */
+pub fn resolve_link(document: NodeRef, link: &LinkMeta) -> Option<LinkResolution> {
+    LinkIndex::new(document).resolve(link)
+}
+
+/*
+ * 電波ターゲット <<<name>>>
+ * 文書中に現れる同じ文字列(大文字小文字を区別しない)を radio リンクにする
+ * リンク・ターゲット・verbatim・code の中は対象外。単語の途中には一致しない。
+ */
+pub fn link_radio_targets(document: &mut ASTArena) {
+    let mut names = collect_radio_targets(document.root_node());
+    if names.is_empty() {
+        return;
+    }
+    // 長い名前を優先する
+    names.sort_by_key(|name| std::cmp::Reverse(name.chars().count()));
+    let root = document.root();
+    replace_radio_links(document, root, &names);
+}
+
+fn collect_radio_targets(document: NodeRef) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for node in document.pre_order() {
+        if node.node_type() == &ASTType::RadioTarget && !names.contains(node.value()) {
+            names.push(node.value().to_string());
+        }
+    }
+    names
+}
+
+fn replace_radio_links(document: &mut ASTArena, id: NodeId, names: &[String]) {
+    match document.node(id).node_type() {
+        ASTType::Link | ASTType::Target | ASTType::RadioTarget | ASTType::Verbatim | ASTType::Code => return,
+        _ => {}
+    }
+    let children: Vec<NodeId> = document.children(id).collect();
+    for child in children {
+        if document.node(child).node_type() != &ASTType::Text {
+            replace_radio_links(document, child, names);
+        } else if let Some(nodes) = split_radio_links(document.node(child), names) {
+            for node in nodes {
+                document.insert_before(child, node);
+            }
+            document.detach(child);
+        }
+    }
+}
+
+// テキストを radio リンクとその前後のテキストに分ける (テキストは改行を含まない, 一致しなければ None)
+fn split_radio_links(text: NodeRef, names: &[String]) -> Option<Vec<ASTNode>> {
+    let chars: Vec<char> = text.value().chars().collect();
+    let begin = &text.range().begin;
+    let slice = |from: usize, to: usize| -> (String, ASTRange) {
+        let pos = |i: usize| ASTPos::new(begin.line(), begin.ch() + i as u32, begin.pos() + i as u32);
+        (chars[from..to].iter().collect(), ASTRange::new(pos(from), pos(to)))
+    };
+
+    let mut nodes = vec![];
+    let mut text_begin = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let at_boundary = i == 0 || !chars[i - 1].is_alphanumeric();
+        let matched = names.iter().map(|name| name.chars().count()).zip(names).find(|(len, name)| {
+            at_boundary
+                && i + len <= chars.len()
+                && chars[i..i + len].iter().zip(name.chars()).all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+                && chars.get(i + len).is_none_or(|c| !c.is_alphanumeric())
+        });
+        match matched {
+            Some((len, name)) => {
+                if text_begin < i {
+                    let (value, range) = slice(text_begin, i);
+                    nodes.push(ASTNode::new(ASTElm::new_text(&value, range)));
+                }
+                let (value, range) = slice(i, i + len);
+                let meta = LinkMeta {
+                    link_type: "radio".to_string(),
+                    path: name.clone(),
+                    search_option: None,
+                    format: LinkFormat::Plain,
+                    resolved: None,
+                };
+                let mut link = ASTNode::new(ASTElm::new_link(meta, &value, &value, range));
+                link.append(ASTElm::new_text(&value, range));
+                nodes.push(link);
+                i += len;
+                text_begin = i;
+            }
+            None => i += 1,
+        }
+    }
+    if text_begin == 0 {
+        return None;
+    }
+    if text_begin < chars.len() {
+        let (value, range) = slice(text_begin, chars.len());
+        nodes.push(ASTNode::new(ASTElm::new_text(&value, range)));
+    }
+    Some(nodes)
+}
+
+// 文書内のすべてのリンクを解決し、LinkMeta.resolved に格納する
+pub fn resolve_links(document: &mut ASTArena) {
+    let index = LinkIndex::new(document.root_node());
+    let ids: Vec<NodeId> = document.pre_order(document.root()).collect();
+    for id in ids {
+        if let ASTMetaData::Link(meta) = &mut document.data_mut(id).elm_meta {
+            meta.resolved = index.resolve(meta);
+        }
+    }
+}
+
+// 見出し・ターゲットを名前で引く表 (文書を一度だけ辿って作る。同じ名前は最初のものを使う)
+struct LinkIndex {
+    headlines: HashMap<String, (String, ASTRange)>,                   // 正規化したタイトル -> (タイトル, 範囲)
+    custom_ids: HashMap<String, (String, ASTRange)>,                  // CUSTOM_ID -> (タイトル, 範囲)
+    targets: HashMap<String, (String, LinkResolutionKind, ASTRange)>, // 正規化した名前 -> <<target>>, <<<radio>>>
+}
+
+impl LinkIndex {
+    fn new(document: NodeRef) -> Self {
+        let mut index = LinkIndex { headlines: HashMap::new(), custom_ids: HashMap::new(), targets: HashMap::new() };
+        for node in document.pre_order() {
+            index.collect(node);
+        }
+        index
+    }
+
+    fn collect(&mut self, node: NodeRef) {
+        let name = node.value().to_string();
+        match node.meta() {
+            ASTMetaData::Headline(meta) => {
+                if let Some(custom_id) = meta.properties.get("CUSTOM_ID") {
+                    self.custom_ids.entry(custom_id.clone()).or_insert((name.clone(), *node.range()));
+                }
+                self.headlines.entry(normalize_title(&name)).or_insert((name, *node.range()));
+            }
+            _ if matches!(node.node_type(), ASTType::Target | ASTType::RadioTarget) => {
+                let kind = match node.node_type() {
+                    ASTType::Target => LinkResolutionKind::Target,
+                    _ => LinkResolutionKind::RadioTarget,
+                };
+                self.targets.entry(normalize_target(&name)).or_insert((name, kind, *node.range()));
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve(&self, link: &LinkMeta) -> Option<LinkResolution> {
+        match link.link_type.as_str() {
+            "custom-id" => self.custom_ids.get(&link.path).map(headline_resolution),
+            "radio" => self.find_target(&link.path),
+            "fuzzy" => match link.path.strip_prefix('*') {
+                Some(title) => self.find_headline(title),
+                None => self.find_target(&link.path).or_else(|| self.find_headline(&link.path)),
+            },
+            _ => None,
+        }
+    }
+
+    fn find_headline(&self, title: &str) -> Option<LinkResolution> {
+        self.headlines.get(&normalize_title(title)).map(headline_resolution)
+    }
+
+    // ターゲット名は大文字小文字・空白の違いを区別しない
+    fn find_target(&self, name: &str) -> Option<LinkResolution> {
+        self.targets.get(&normalize_target(name)).map(|(target, kind, range)| LinkResolution {
+            kind: kind.clone(),
+            name: target.clone(),
+            range: *range,
+        })
+    }
+}
+
+fn headline_resolution((title, range): &(String, ASTRange)) -> LinkResolution {
+    LinkResolution { kind: LinkResolutionKind::Headline, name: title.clone(), range: *range }
+}
+
+// 見出しのタイトルは空白の違いだけを区別しない
+fn normalize_title(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn normalize_target(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org_parser::org_parse;
+
+    #[test]
+    fn test_parse_link_path() {
+        let meta = parse_link_path("file:x.org::*Heading", LinkFormat::Bracket);
+        assert_eq!(meta.link_type, "file");
+        assert_eq!(meta.path, "x.org");
+        assert_eq!(meta.search_option, Some("*Heading".to_string()));
+
+        let meta = parse_link_path("https://example.com/a?b=c", LinkFormat::Plain);
+        assert_eq!((meta.link_type.as_str(), meta.path.as_str()), ("https", "//example.com/a?b=c"));
+
+        let cases = vec![
+            ("#my-id", "custom-id", "my-id"),
+            ("(ref)", "coderef", "ref"),
+            ("./notes.org", "file", "./notes.org"),
+            ("id:6A2B-11", "id", "6A2B-11"),
+            ("*Some\n  Headline", "fuzzy", "*Some Headline"),
+            ("unknown:thing", "fuzzy", "unknown:thing"),
+        ];
+        for (raw, link_type, path) in cases {
+            let meta = parse_link_path(raw, LinkFormat::Bracket);
+            assert_eq!((meta.link_type.as_str(), meta.path.as_str()), (link_type, path), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_link_index() {
+        let src = "* Same\n* Same\n* Two  words\n:PROPERTIES:\n:CUSTOM_ID: two\n:END:\n<<Here>>";
+        let document = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let index = LinkIndex::new(document.root_node());
+        let line = |path: &str| {
+            let link = parse_link_path(path, LinkFormat::Bracket);
+            index.resolve(&link).map(|resolution| resolution.range.begin.line())
+        };
+        // 同じタイトルは最初の見出し
+        assert_eq!(line("*Same"), Some(1));
+        assert_eq!(line("*Two words"), Some(3));
+        assert_eq!(line("#two"), Some(3));
+        assert_eq!(line("here"), Some(7));
+        assert_eq!(line("*Missing"), None);
+    }
+}
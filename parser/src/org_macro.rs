@@ -0,0 +1,316 @@
+use crate::arena::{ASTArena, NodeId, NodeRef};
+use crate::ast::*;
+use crate::org_timestamp::parse_timestamp;
+
+use std::collections::BTreeMap;
+
+// マクロ・noweb 参照の展開の深さの上限 (循環した定義への対策)
+const MAX_EXPANSION_DEPTH: usize = 16;
+
+/*
+ * マクロ {{{name(arg1, arg2)}}} の展開
+ *
+ * #+MACRO: name replacement $1 $2 で定義したマクロと、次の組み込みのマクロを展開する。
+ *   {{{title}}} {{{author}}} {{{email}}}  同じ名前のキーワードの値
+ *   {{{date}}} {{{date(%Y-%m-%d)}}}       #+DATE: の値 (タイムスタンプなら書式を指定できる)
+ *   {{{keyword(NAME)}}}                    キーワードの値
+ *   {{{property(NAME)}}}                   その位置の見出しのプロパティ
+ *   {{{n}}} {{{n(name, action)}}}          カウンター (action: "-" は増やさない, 数値はその値にする)
+ * 展開した文字列は parse でオブジェクトとして解釈し、その位置情報はすべてマクロの位置にする。
+ * 未定義のマクロは空になる。
+ */
+pub fn expand_macros<F>(document: &mut ASTArena, parse: F)
+where
+    F: Fn(&str) -> Vec<ASTNode>,
+{
+    let mut context = MacroContext { templates: BTreeMap::new(), keywords: BTreeMap::new(), counters: BTreeMap::new() };
+    context.collect(document.root_node());
+    let root = document.root();
+    context.expand(document, root, &BTreeMap::new(), &parse, 0);
+}
+
+struct MacroContext {
+    templates: BTreeMap<String, String>,
+    keywords: BTreeMap<String, String>,
+    counters: BTreeMap<String, usize>,
+}
+
+impl MacroContext {
+    // 同じキーワードが複数あれば空白で繋ぐ
+    fn collect(&mut self, document: NodeRef) {
+        for node in document.pre_order() {
+            self.collect_keyword(node);
+        }
+    }
+
+    fn collect_keyword(&mut self, node: NodeRef) {
+        if let ASTMetaData::Keyword { key, .. } = node.meta() {
+            let value = node.value().trim();
+            if key == "MACRO" {
+                let (name, template) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+                self.templates.insert(name.to_lowercase(), template.trim().to_string());
+            } else {
+                let entry = self.keywords.entry(key.clone()).or_default();
+                if !entry.is_empty() {
+                    entry.push(' ');
+                }
+                entry.push_str(value);
+            }
+        }
+    }
+
+    fn expand<F>(
+        &mut self, document: &mut ASTArena, id: NodeId, properties: &BTreeMap<String, String>, parse: &F, depth: usize,
+    ) where
+        F: Fn(&str) -> Vec<ASTNode>,
+    {
+        let node = document.node(id);
+        if let ASTMetaData::Macro(meta) = node.meta() {
+            let meta = meta.clone();
+            let range = *node.range();
+            let text = self.expansion(&meta, properties).unwrap_or_default();
+            let children = if depth < MAX_EXPANSION_DEPTH { parse(&text) } else { vec![] };
+            document.data_mut(id).value = text;
+            document.take_children(id);
+            for mut child in children {
+                set_range(&mut child, &range);
+                let child = document.append(id, child);
+                self.expand(document, child, properties, parse, depth + 1);
+            }
+            return;
+        }
+
+        // 節の中では、その見出しのプロパティを使う
+        let section_properties = match node.node_type() {
+            ASTType::Section => node.first_child().and_then(|headline| match headline.meta() {
+                ASTMetaData::Headline(meta) => Some(meta.properties.clone()),
+                _ => None,
+            }),
+            _ => None,
+        };
+        let properties = section_properties.as_ref().unwrap_or(properties);
+        let children: Vec<NodeId> = document.children(id).collect();
+        for child in children {
+            self.expand(document, child, properties, parse, depth);
+        }
+    }
+
+    fn expansion(&mut self, meta: &MacroMeta, properties: &BTreeMap<String, String>) -> Option<String> {
+        if let Some(template) = self.templates.get(&meta.name) {
+            return Some(substitute_arguments(template, &meta.arguments));
+        }
+        let argument = |idx: usize| meta.arguments.get(idx).map(|arg| arg.trim()).unwrap_or("");
+        match meta.name.as_str() {
+            "title" | "author" | "email" => self.keywords.get(&meta.name.to_uppercase()).cloned(),
+            "date" => {
+                let date = self.keywords.get("DATE")?;
+                match parse_timestamp(date) {
+                    Ok((_, timestamp)) if !argument(0).is_empty() => Some(format_timestamp(&timestamp, argument(0))),
+                    _ => Some(date.clone()),
+                }
+            }
+            "keyword" => self.keywords.get(&argument(0).to_uppercase()).cloned(),
+            "property" => properties.get(&argument(0).to_uppercase()).cloned(),
+            "n" => {
+                let counter = self.counters.entry(argument(0).to_string()).or_insert(0);
+                match argument(1) {
+                    "-" => {}
+                    "" => *counter += 1,
+                    action => *counter = action.parse().unwrap_or(*counter + 1),
+                }
+                Some(counter.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+// $1 $2 ... を引数で置き換える (無い引数は空)
+fn substitute_arguments(template: &str, arguments: &[String]) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(idx) = rest.find('$') {
+        result.push_str(&rest[..idx]);
+        let digits: String = rest[idx + 1..].chars().take_while(|c| c.is_ascii_digit()).collect();
+        match digits.parse::<usize>() {
+            Ok(n) if n > 0 => result.push_str(arguments.get(n - 1).map(String::as_str).unwrap_or("")),
+            _ => result.push_str(&rest[idx..idx + 1 + digits.len()]),
+        }
+        rest = &rest[idx + 1 + digits.len()..];
+    }
+    result + rest
+}
+
+// %Y %m %d %e %H %M %a %A %b %B %y %%
+fn format_timestamp(timestamp: &TimestampMeta, format: &str) -> String {
+    const DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+    const MONTHS: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+        "December",
+    ];
+    let date = &timestamp.date;
+    let time = timestamp.time.unwrap_or(OrgTime { hour: 0, minute: 0 });
+    let mut result = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => result += &date.year.to_string(),
+            Some('y') => result += &format!("{:02}", date.year.rem_euclid(100)),
+            Some('m') => result += &format!("{:02}", date.month),
+            Some('d') => result += &format!("{:02}", date.day),
+            Some('e') => result += &format!("{:>2}", date.day),
+            Some('H') => result += &format!("{:02}", time.hour),
+            Some('M') => result += &format!("{:02}", time.minute),
+            Some('A') => result += DAYS[date.weekday() as usize],
+            Some('a') => result += &DAYS[date.weekday() as usize][..3],
+            Some('B') => result += MONTHS[date.month as usize - 1],
+            Some('b') => result += &MONTHS[date.month as usize - 1][..3],
+            Some('%') => result.push('%'),
+            Some(c) => {
+                result.push('%');
+                result.push(c);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+fn set_range(node: &mut ASTNode, range: &ASTRange) {
+    node.set_range(*range);
+    for child in node.children_mut() {
+        set_range(child, range);
+    }
+}
+
+/*
+ * マクロの引数
+ * 全体の前後の空白を除き、連続する空白は一つにする。',' で区切り、'\,' は ',' にする。
+ */
+pub fn split_macro_arguments(s: &str) -> Vec<String> {
+    let s = s.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let mut arguments = vec![];
+    let mut argument = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&',') => argument.push(chars.next().unwrap_or(',')),
+            ',' => arguments.push(std::mem::take(&mut argument)),
+            _ => argument.push(c),
+        }
+    }
+    arguments.push(argument);
+    arguments
+}
+
+/*
+ * noweb 参照 <<name>> の展開
+ *
+ * :noweb yes (strip-tangle) の SRC ブロックの中の参照を、#+NAME: name の直後のブロック
+ * または :noweb-ref name を持つブロック(複数あれば改行で繋ぐ)の内容で置き換えて BlockMeta.expanded に格納する。
+ * 参照より前にある同じ行の文字列は、展開した各行の先頭に付ける。
+ * :noweb strip-export では参照を取り除く。見つからない参照はそのまま残す。
+ */
+pub fn expand_noweb(document: &mut ASTArena) {
+    let blocks = collect_named_blocks(document.root_node());
+    let ids: Vec<NodeId> = document.pre_order(document.root()).collect();
+    for id in ids {
+        fill_noweb_expansion(document, id, &blocks);
+    }
+}
+
+fn collect_named_blocks(document: NodeRef) -> BTreeMap<String, String> {
+    let mut blocks = BTreeMap::new();
+    for child in document.descendants() {
+        if let (ASTType::SrcBlock, ASTMetaData::Block(meta)) = (child.node_type(), child.meta()) {
+            let name = child.affiliated().and_then(|affiliated| affiliated.name.as_ref());
+            let names = name.into_iter().chain(meta.header_args.get("noweb-ref"));
+            for name in names {
+                let entry: &mut String = blocks.entry(name.clone()).or_default();
+                if !entry.is_empty() {
+                    entry.push('\n');
+                }
+                entry.push_str(child.value().trim_end_matches('\n'));
+            }
+        }
+    }
+    blocks
+}
+
+fn fill_noweb_expansion(document: &mut ASTArena, id: NodeId, blocks: &BTreeMap<String, String>) {
+    let node = document.data_mut(id);
+    if node.elm_type == ASTType::SrcBlock {
+        let value = node.value.clone();
+        if let ASTMetaData::Block(meta) = &mut node.elm_meta {
+            meta.expanded = match meta.header_args.get("noweb").map(String::as_str) {
+                Some("yes") | Some("strip-tangle") => Some(expand_noweb_references(&value, blocks, false, 0)),
+                Some("strip-export") => Some(expand_noweb_references(&value, blocks, true, 0)),
+                _ => None,
+            };
+        }
+    }
+}
+
+fn expand_noweb_references(body: &str, blocks: &BTreeMap<String, String>, strip: bool, depth: usize) -> String {
+    let mut result = String::new();
+    let mut rest = body;
+    while let Some(begin) = rest.find("<<") {
+        let name = rest[begin + 2..].find(">>").map(|end| &rest[begin + 2..begin + 2 + end]);
+        let name = match name {
+            Some(name) if !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == '(') => name,
+            _ => {
+                result.push_str(&rest[..begin + 2]);
+                rest = &rest[begin + 2..];
+                continue;
+            }
+        };
+        let end = begin + name.len() + 4;
+        result.push_str(&rest[..begin]);
+        let prefix = result[result.rfind('\n').map(|idx| idx + 1).unwrap_or(0)..].to_string();
+        match blocks.get(name) {
+            _ if strip => {}
+            Some(block) if depth < MAX_EXPANSION_DEPTH => {
+                let expansion = expand_noweb_references(block, blocks, strip, depth + 1);
+                result.push_str(&expansion.replace('\n', &format!("\n{}", prefix)));
+            }
+            _ => result.push_str(&rest[begin..end]),
+        }
+        rest = &rest[end..];
+    }
+    result + rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute_arguments() {
+        let arguments = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(substitute_arguments("$2-$1-$3 $$ $0", &arguments), "b-a- $$ $0");
+        assert_eq!(split_macro_arguments(" x,  y\\, z ,"), vec!["x".to_string(), " y, z ".to_string(), "".to_string()]);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let timestamp = parse_timestamp("<2024-01-02 Tue 09:05>").unwrap().1;
+        assert_eq!(format_timestamp(&timestamp, "%Y/%m/%d %H:%M %a %B %%"), "2024/01/02 09:05 Tue January %");
+    }
+
+    #[test]
+    fn test_expand_noweb_references() {
+        let mut blocks = BTreeMap::new();
+        blocks.insert("init".to_string(), "a = 1\nb = <<inner>>".to_string());
+        blocks.insert("inner".to_string(), "2".to_string());
+        let body = "def f():\n    <<init>>\n    return <<missing>> <<call()>>\n";
+        assert_eq!(
+            expand_noweb_references(body, &blocks, false, 0),
+            "def f():\n    a = 1\n    b = 2\n    return <<missing>> <<call()>>\n"
+        );
+        assert_eq!(expand_noweb_references(body, &blocks, true, 0), "def f():\n    \n    return  <<call()>>\n");
+    }
+}
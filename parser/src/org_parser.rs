@@ -0,0 +1,3135 @@
+extern crate nom;
+
+use crate::arena::{ASTArena, NodeId, NodeRef};
+use crate::ast::*;
+use crate::org_affiliated::{attach_affiliated_keywords, link_results};
+use crate::org_document::build_document_meta;
+use crate::org_entity::lookup_entity;
+use crate::org_include::{parse_include_parameters, select_lines, FileLoader};
+use crate::org_link::{is_link_type, link_radio_targets, parse_link_path, resolve_links};
+use crate::org_macro::{expand_macros, expand_noweb, split_macro_arguments};
+use crate::org_table::{parse_formulas, recalculate, TableLine};
+use crate::org_timestamp::parse_timestamp;
+use crate::transform::sectionize;
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case, take_till, take_until, take_while, take_while1};
+use nom::character::complete::{char, digit0, digit1, line_ending, not_line_ending, one_of, space0, space1};
+use nom::combinator::{eof, map, opt, peek, recognize};
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, tuple};
+use nom::{Err, IResult};
+
+/*
+ * TODOキーワードの定義
+ * #+TODO: TODO NEXT | DONE CANCELED
+ * '|' より前が未完了、後ろが完了状態。'|' が無い場合は最後のキーワードが完了状態になる。
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoKeywords {
+    pub todo: Vec<String>,
+    pub done: Vec<String>,
+}
+
+impl Default for TodoKeywords {
+    fn default() -> Self {
+        TodoKeywords {
+            todo: vec!["TODO".to_string()],
+            done: vec!["DONE".to_string()],
+        }
+    }
+}
+
+impl TodoKeywords {
+    fn empty() -> Self {
+        TodoKeywords { todo: vec![], done: vec![] }
+    }
+
+    // 文書中の #+TODO: / #+SEQ_TODO: / #+TYP_TODO: 行をすべて集める
+    // 位置に関係なく文書全体に適用されるので、パースの前に走査する
+    pub fn from_source(s: &str) -> Self {
+        let mut keywords = TodoKeywords::empty();
+        for line in s.lines() {
+            let line = line.trim_start();
+            let upper = line.to_uppercase();
+            for key in &["#+TODO:", "#+SEQ_TODO:", "#+TYP_TODO:"] {
+                if upper.starts_with(key) {
+                    keywords.add_sequence(&line[key.len()..]);
+                }
+            }
+        }
+        if keywords.todo.is_empty() && keywords.done.is_empty() {
+            TodoKeywords::default()
+        } else {
+            keywords
+        }
+    }
+
+    pub fn add_sequence(&mut self, value: &str) {
+        // 高速選択キー TODO(t) DONE(d!) を取り除く
+        let words: Vec<String> = value
+            .split_whitespace()
+            .map(|w| match w.find('(') {
+                Some(idx) if w.ends_with(')') => w[..idx].to_string(),
+                _ => w.to_string(),
+            })
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        match words.iter().position(|w| w == "|") {
+            Some(idx) => {
+                self.todo.extend_from_slice(&words[..idx]);
+                self.done.extend(words[idx + 1..].iter().filter(|w| *w != "|").cloned());
+            }
+            None => {
+                if let Some((last, rest)) = words.split_last() {
+                    self.todo.extend_from_slice(rest);
+                    self.done.push(last.clone());
+                }
+            }
+        }
+    }
+
+    pub fn todo_type(&self, keyword: &str) -> Option<TodoType> {
+        if self.todo.iter().any(|k| k == keyword) {
+            Some(TodoType::Todo)
+        } else if self.done.iter().any(|k| k == keyword) {
+            Some(TodoType::Done)
+        } else {
+            None
+        }
+    }
+}
+
+// この深さ以上の見出しはインラインタスクとして扱う (org-inlinetask-min-level)
+pub const INLINETASK_MIN_LEVEL: usize = 15;
+
+struct Parser<'a> {
+    source: &'a str,
+    line_starts: Vec<(usize, usize)>, // 各行の先頭位置 (バイト位置, 文字数)
+    todo_keywords: TodoKeywords,
+    options: ExportOptions,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![(0, 0)];
+        let mut chars = 0;
+        for (idx, c) in source.char_indices() {
+            chars += 1;
+            if c == '\n' {
+                line_starts.push((idx + 1, chars));
+            }
+        }
+        Parser {
+            source,
+            line_starts,
+            todo_keywords: TodoKeywords::from_source(source),
+            options: ExportOptions::from_source(source),
+        }
+    }
+
+    // 別の文字列(マクロの展開結果、#+INCLUDE: で読み込んだファイル)のパーサー
+    // TODOキーワードなどの設定は parent のものを使う
+    fn with_settings<'b>(source: &'b str, parent: &Parser) -> Parser<'b> {
+        let mut parser = Parser::new(source);
+        parser.todo_keywords = parent.todo_keywords.clone();
+        parser.options = parent.options.clone();
+        parser
+    }
+
+    /*
+     * 位置情報
+     * Org のパーサーは入力全体(source)の部分文字列のみを扱うので、
+     * 部分文字列の先頭ポインタから位置情報を逆算する。
+     */
+    fn offset(&self, s: &str) -> usize {
+        let base = self.source.as_ptr() as usize;
+        let ptr = s.as_ptr() as usize;
+        if ptr >= base && ptr <= base + self.source.len() {
+            ptr - base
+        } else {
+            0
+        }
+    }
+
+    fn pos_at(&self, offset: usize) -> ASTPos {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = match self.line_starts.binary_search_by(|(o, _)| o.cmp(&offset)) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        let (line_offset, line_chars) = self.line_starts[line];
+        let ch = self.source[line_offset..offset].chars().count();
+        ASTPos::new(line as u32 + 1, ch as u32 + 1, (line_chars + ch) as u32)
+    }
+
+    // 部分文字列の範囲
+    fn span_of(&self, s: &str) -> ASTRange {
+        let begin = self.offset(s);
+        ASTRange::new(self.pos_at(begin), self.pos_at(begin + s.len()))
+    }
+
+    // 要素の範囲 (末尾の改行は含めない)
+    fn range_of(&self, s: &str) -> ASTRange {
+        self.span_of(trim_line_ending(s))
+    }
+
+    // 部分文字列の先頭の桁 (行頭からの字下げの幅)
+    fn column(&self, s: &str) -> usize {
+        let offset = self.offset(s);
+        let line_offset = match self.line_starts.binary_search_by(|(o, _)| o.cmp(&offset)) {
+            Ok(idx) => self.line_starts[idx].0,
+            Err(idx) => self.line_starts[idx - 1].0,
+        };
+        indent_width(&self.source[line_offset..offset])
+    }
+}
+
+// UTILITIES
+
+fn trim_line_ending(s: &str) -> &str {
+    s.strip_suffix('\n').map(|s| s.strip_suffix('\r').unwrap_or(s)).unwrap_or(s)
+}
+
+// 入力の先頭から remain の直前までを切り出す
+fn consumed<'b>(s: &'b str, remain: &str) -> &'b str {
+    &s[..s.len() - remain.len()]
+}
+
+// 字下げの幅 (タブは8文字として数える)
+fn indent_width(s: &str) -> usize {
+    s.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { 8 } else { 1 })
+        .sum()
+}
+
+fn error<T>(s: &str) -> IResult<&str, T> {
+    Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Char)))
+}
+
+/* ---------- lines ---------- */
+
+impl<'a> Parser<'a> {
+    // 一行分を読む (改行は消費するが結果には含めない)
+    fn parse_line(&self) -> impl Fn(&str) -> IResult<&str, &str> + '_ {
+        move |s| {
+            if s.is_empty() {
+                return error(s);
+            }
+            let (remain, line) = not_line_ending(s)?;
+            let (remain, _) = alt((line_ending, eof))(remain)?;
+            Ok((remain, line))
+        }
+    }
+
+    // 空白のみの行
+    fn parse_blank_line(&self) -> impl Fn(&str) -> IResult<&str, &str> + '_ {
+        move |s| {
+            if s.is_empty() {
+                return error(s);
+            }
+            preceded(space0, alt((line_ending, eof)))(s)
+        }
+    }
+}
+
+/* ---------- objects ---------- */
+
+impl<'a> Parser<'a> {
+    // \\ 行末の明示的な改行 (続く改行を含む)
+    fn parse_line_break(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            if prev == Some('\\') {
+                return error(s);
+            }
+            let (remain, _) = tuple((tag("\\\\"), space0, alt((line_ending, eof))))(s)?;
+            let raw_value = consumed(s, remain);
+            let mut node = ASTNode::new(ASTElm::new_hardbreak(self.span_of(raw_value)));
+            node.set_raw_value(raw_value.to_string());
+            Ok((remain, node))
+        }
+    }
+
+    fn parse_soft_break_node(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, br) = line_ending(s)?;
+            Ok((remain, ASTNode::new(ASTElm::new_softbreak(self.span_of(br)))))
+        }
+    }
+
+    /*
+     * 強調記号
+     * *bold* /italic/ _underline_ =verbatim= ~code~ +strike-through+
+     *
+     * 開始記号の前(PRE)・終了記号の後(POST)に置ける文字が決まっている。
+     * 記号の内側に空白は隣接できず、中身に含められる改行は一つまで。
+     * verbatim, code の中身はオブジェクトとして解釈しない。
+     */
+    fn parse_emphasis_markup(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            if !is_emphasis_pre(prev) {
+                return error(s);
+            }
+            let (rest, marker) = one_of("*/_=~+")(s)?;
+            match rest.chars().next() {
+                Some(c) if !c.is_whitespace() => {}
+                _ => return error(s),
+            }
+
+            let mut newlines = 0;
+            for (idx, c) in rest.char_indices() {
+                if c == '\n' {
+                    newlines += 1;
+                    if newlines > 1 {
+                        break;
+                    }
+                    continue;
+                }
+                if c != marker || idx == 0 {
+                    continue;
+                }
+                if rest[..idx].chars().next_back().is_none_or(char::is_whitespace) {
+                    continue;
+                }
+                let remain = &rest[idx + c.len_utf8()..];
+                if !is_emphasis_post(remain.chars().next()) {
+                    continue;
+                }
+                let contents = &rest[..idx];
+                let raw_value = consumed(s, remain);
+                let range = self.span_of(raw_value);
+                let node = match marker {
+                    '=' => ASTNode::new(ASTElm::new_verbatim(contents, raw_value, range)),
+                    '~' => ASTNode::new(ASTElm::new_code(contents, raw_value, range)),
+                    _ => {
+                        let elm = match marker {
+                            '*' => ASTElm::new_strong("", raw_value, range),
+                            '/' => ASTElm::new_emphasis("", raw_value, range),
+                            '_' => ASTElm::new_underline("", raw_value, range),
+                            _ => ASTElm::new_strikethrough("", raw_value, range),
+                        };
+                        let mut node = ASTNode::new(elm);
+                        node.append_node_from_vec(self.parse_objects(contents));
+                        node
+                    }
+                };
+                return Ok((remain, node));
+            }
+            error(s)
+        }
+    }
+
+    /*
+     * 角括弧リンク
+     * [[target][description]] / [[target]]
+     * リンク先の中の ']' はバックスラッシュでエスケープできる
+     */
+    fn parse_bracket_link(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, _) = tag("[[")(s)?;
+            let mut escaped = false;
+            let mut path_end = None;
+            for (idx, c) in rest.char_indices() {
+                match c {
+                    '\\' => escaped = !escaped,
+                    '[' if !escaped => break,
+                    ']' if !escaped => {
+                        path_end = Some(idx);
+                        break;
+                    }
+                    _ => escaped = false,
+                }
+            }
+            let path = match path_end {
+                Some(idx) if idx > 0 => &rest[..idx],
+                _ => return error(s),
+            };
+            let (remain, description) = alt((
+                map(tag("]]"), |_| None),
+                map(
+                    delimited(tag("]["), verify_not_empty(take_until("]]")), tag("]]")),
+                    Some,
+                ),
+            ))(&rest[path.len()..])?;
+
+            let raw_value = consumed(s, remain);
+            let path = path.replace("\\]", "]").replace("\\[", "[");
+            let meta = parse_link_path(&path, LinkFormat::Bracket);
+            let mut node = ASTNode::new(ASTElm::new_link(meta, &path, raw_value, self.span_of(raw_value)));
+            if let Some(description) = description {
+                node.append_node_from_vec(self.parse_objects(description));
+            }
+            Ok((remain, node))
+        }
+    }
+
+    // <https://example.com>
+    fn parse_angle_link(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, link_type, _, _, _)) = tuple((
+                char('<'),
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '+' || c == '-'),
+                char(':'),
+                take_while1(|c: char| !"<>]\n".contains(c)),
+                char('>'),
+            ))(s)?;
+            if !is_link_type(link_type) {
+                return error(s);
+            }
+            let raw_value = consumed(s, remain);
+            let target = &raw_value[1..raw_value.len() - 1];
+            let meta = parse_link_path(target, LinkFormat::Angle);
+            let node = ASTNode::new(ASTElm::new_link(meta, target, raw_value, self.span_of(raw_value)));
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * 平文のリンク https://example.com
+     * 単語の途中からは始まらない。末尾の句読点はリンクに含めない。
+     */
+    fn parse_plain_link(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            if prev.is_some_and(char::is_alphanumeric) {
+                return error(s);
+            }
+            let (rest, (link_type, _)) = tuple((
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '+' || c == '-'),
+                char(':'),
+            ))(s)?;
+            if !is_link_type(link_type) {
+                return error(s);
+            }
+            let (_, path) = take_while1(|c: char| !c.is_whitespace() && !"[]<>()".contains(c))(rest)?;
+            let path = path.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '/');
+            if path.is_empty() {
+                return error(s);
+            }
+            let remain = &rest[path.len()..];
+            let raw_value = consumed(s, remain);
+            let meta = parse_link_path(raw_value, LinkFormat::Plain);
+            let node = ASTNode::new(ASTElm::new_link(meta, raw_value, raw_value, self.span_of(raw_value)));
+            Ok((remain, node))
+        }
+    }
+
+    // <<target>>
+    fn parse_target(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, name, _)) = tuple((
+                tag("<<"),
+                take_while1(|c: char| !"<>\n".contains(c)),
+                tag(">>"),
+            ))(s)?;
+            if name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace) {
+                return error(s);
+            }
+            let raw_value = consumed(s, remain);
+            Ok((remain, ASTNode::new(ASTElm::new_target(name, raw_value, self.span_of(raw_value)))))
+        }
+    }
+
+    // <<<radio target>>>
+    fn parse_radio_target(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, name, _)) = tuple((
+                tag("<<<"),
+                take_while1(|c: char| !"<>\n".contains(c)),
+                tag(">>>"),
+            ))(s)?;
+            if name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace) {
+                return error(s);
+            }
+            let raw_value = consumed(s, remain);
+            Ok((remain, ASTNode::new(ASTElm::new_radio_target(name, raw_value, self.span_of(raw_value)))))
+        }
+    }
+
+    /*
+     * 脚注の参照
+     * [fn:label]  [fn::inline definition]  [fn:label:inline definition]
+     * インラインの定義の中の角括弧は対になっていればよい
+     */
+    fn parse_footnote_reference(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, label)) = tuple((
+                tag("[fn:"),
+                take_while(|c: char| c.is_alphanumeric() || c == '-' || c == '_'),
+            ))(s)?;
+            let label = if label.is_empty() { None } else { Some(label.to_string()) };
+
+            if let Ok((remain, _)) = char::<_, nom::error::Error<&str>>(']')(rest) {
+                if label.is_none() {
+                    return error(s);
+                }
+                let raw_value = consumed(s, remain);
+                let meta = FootnoteMeta { label, inline: false, number: None };
+                let elm = ASTElm::new_footnote_reference(meta, raw_value, self.span_of(raw_value));
+                return Ok((remain, ASTNode::new(elm)));
+            }
+
+            let (rest, _) = char(':')(rest)?;
+            let mut depth = 0;
+            let end = rest.char_indices().find(|(_, c)| {
+                match c {
+                    '[' => depth += 1,
+                    ']' if depth == 0 => return true,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                false
+            });
+            let definition = match end {
+                Some((idx, _)) if idx > 0 => &rest[..idx],
+                _ => return error(s),
+            };
+            let remain = &rest[definition.len() + 1..];
+            let raw_value = consumed(s, remain);
+            let meta = FootnoteMeta { label, inline: true, number: None };
+            let mut node = ASTNode::new(ASTElm::new_footnote_reference(meta, raw_value, self.span_of(raw_value)));
+            node.append_node_from_vec(self.parse_objects(definition));
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * LaTeX の断片
+     * \(x\) \[x\] $$x$$ $x$
+     * 中身に空行は含められない。$x$ は前後の文字に制約がある ($5 and $10 は数式ではない)
+     */
+    fn parse_latex_fragment(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let delimiters = [
+                ("\\(", "\\)", LatexDelimiter::Paren),
+                ("\\[", "\\]", LatexDelimiter::Bracket),
+                ("$$", "$$", LatexDelimiter::DoubleDollar),
+                ("$", "$", LatexDelimiter::Dollar),
+            ];
+            let (open, close, delimiter) = match delimiters.iter().find(|(open, _, _)| s.starts_with(open)) {
+                Some(d) => *d,
+                None => return error(s),
+            };
+            let rest = &s[open.len()..];
+            let body = match rest.find(close) {
+                Some(idx) if idx > 0 => &rest[..idx],
+                _ => return error(s),
+            };
+            if body.lines().skip(1).any(|line| line.trim().is_empty()) {
+                return error(s);
+            }
+            let remain = &rest[body.len() + close.len()..];
+
+            if delimiter == LatexDelimiter::Dollar {
+                let first = body.chars().next().unwrap_or(' ');
+                let last = body.chars().next_back().unwrap_or(' ');
+                let border = if body.chars().count() == 1 { ".,?;\"" } else { ".,;" };
+                if prev == Some('$')
+                    || first.is_whitespace()
+                    || border.contains(first)
+                    || last.is_whitespace()
+                    || ".,".contains(last)
+                    || remain.chars().next().is_some_and(|c| c.is_alphanumeric() || c == '$')
+                {
+                    return error(s);
+                }
+            }
+
+            let raw_value = consumed(s, remain);
+            let display = matches!(delimiter, LatexDelimiter::Bracket | LatexDelimiter::DoubleDollar);
+            let meta = LatexMeta { delimiter, display, environment: None };
+            let node = ASTNode::new(ASTElm::new_latex_fragment(meta, body, raw_value, self.span_of(raw_value)));
+            Ok((remain, node))
+        }
+    }
+
+    // エンティティ \alpha \alpha{} (名前は変換テーブルにあるもののみ)
+    fn parse_entity(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, name)) = tuple((char('\\'), take_while1(|c: char| c.is_ascii_alphabetic())))(s)?;
+            let meta = match lookup_entity(name) {
+                Some(meta) => meta,
+                None => return error(s),
+            };
+            let remain = rest.strip_prefix("{}").unwrap_or(rest);
+            let raw_value = consumed(s, remain);
+            Ok((remain, ASTNode::new(ASTElm::new_entity(meta, raw_value, self.span_of(raw_value)))))
+        }
+    }
+
+    /*
+     * エンティティ以外の LaTeX のコマンド \ref{eq:1} \cite[p.~3]{key}
+     * [option], {argument} はいくつ続けてもよい (入れ子と改行は不可)
+     */
+    fn parse_latex_command(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (mut remain, _) = tuple((char('\\'), take_while1(|c: char| c.is_ascii_alphabetic())))(s)?;
+            loop {
+                let close = match remain.chars().next() {
+                    Some('[') => ']',
+                    Some('{') => '}',
+                    _ => break,
+                };
+                match remain[1..].find(|c: char| c == close || "[{\n".contains(c)) {
+                    Some(idx) if remain[1 + idx..].starts_with(close) => remain = &remain[idx + 2..],
+                    _ => break,
+                }
+            }
+            let raw_value = consumed(s, remain);
+            let meta = LatexMeta { delimiter: LatexDelimiter::Command, display: false, environment: None };
+            let node = ASTNode::new(ASTElm::new_latex_fragment(meta, raw_value, raw_value, self.span_of(raw_value)));
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * 下付き・上付き文字 a_{i} x^2 H_2O x^*
+     * 直前に空白以外の文字が必要。
+     * #+OPTIONS: ^:{} では波括弧の形式のみ、^:nil では解釈しない。
+     */
+    fn parse_sub_superscript(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            if self.options.sub_superscript == SubSuperscript::Off || prev.is_none_or(char::is_whitespace) {
+                return error(s);
+            }
+            let (rest, marker) = one_of("_^")(s)?;
+            let (script, braced) = if let Some(inner) = rest.strip_prefix('{') {
+                let mut depth = 0;
+                let end = inner.char_indices().find(|(_, c)| {
+                    match c {
+                        '{' => depth += 1,
+                        '}' if depth == 0 => return true,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    false
+                });
+                match end {
+                    Some((idx, _)) => (&inner[..idx], true),
+                    None => return error(s),
+                }
+            } else if self.options.sub_superscript == SubSuperscript::Braces {
+                return error(s);
+            } else if rest.starts_with('*') {
+                (&rest[..1], false)
+            } else {
+                // 符号に続く英数字 (途中に , . \ を含められる)
+                let sign = if rest.starts_with(['+', '-']) { 1 } else { 0 };
+                let word = &rest[sign..];
+                let word = &word[..word.find(|c: char| !c.is_alphanumeric() && !",.\\".contains(c)).unwrap_or(word.len())];
+                let word = word.trim_end_matches(|c: char| !c.is_alphanumeric());
+                if word.is_empty() {
+                    return error(s);
+                }
+                (&rest[..sign + word.len()], false)
+            };
+            let remain = &rest[script.len() + if braced { 2 } else { 0 }..];
+
+            let raw_value = consumed(s, remain);
+            let range = self.span_of(raw_value);
+            let mut node = match marker {
+                '_' => ASTNode::new(ASTElm::new_subscript("", raw_value, range)),
+                _ => ASTNode::new(ASTElm::new_superscript("", raw_value, range)),
+            };
+            if braced {
+                node.append_node_from_vec(self.parse_objects(script));
+            } else {
+                node.append(ASTElm::new_text(script, self.span_of(script)));
+            }
+            Ok((remain, node))
+        }
+    }
+
+    // {{{name}}} {{{name(arg1, arg2)}}}  展開は文書全体をパースした後に行う
+    fn parse_macro(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, name)) =
+                tuple((tag("{{{"), take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_')))(s)?;
+            if !name.starts_with(|c: char| c.is_alphabetic()) {
+                return error(s);
+            }
+            let (remain, arguments) = if let Some(r) = rest.strip_prefix("}}}") {
+                (r, vec![])
+            } else if let Some(r) = rest.strip_prefix('(') {
+                match r.find(")}}}") {
+                    Some(end) => (&r[end + 4..], split_macro_arguments(&r[..end])),
+                    None => return error(s),
+                }
+            } else {
+                return error(s);
+            };
+            let raw_value = consumed(s, remain);
+            let meta = MacroMeta { name: name.to_lowercase(), arguments };
+            Ok((remain, ASTNode::new(ASTElm::new_macro(meta, raw_value, self.span_of(raw_value)))))
+        }
+    }
+
+    // call_square(4) call_square[:session s](4)[:results raw]
+    fn parse_inline_babel_call(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            if prev.is_some_and(|c| c.is_alphanumeric()) {
+                return error(s);
+            }
+            let (remain, (_, (name, inside_header, arguments), end_header)) =
+                tuple((tag("call_"), parse_call, opt(bracketed)))(s)?;
+            let meta = BabelCallMeta {
+                name: name.to_string(),
+                inside_header: inside_header.map(String::from),
+                arguments: arguments.to_string(),
+                end_header: end_header.map(String::from),
+            };
+            let raw_value = consumed(s, remain);
+            Ok((remain, ASTNode::new(ASTElm::new_inline_babel_call(meta, raw_value, self.span_of(raw_value)))))
+        }
+    }
+
+    // src_python{1+1} src_sh[:results output]{echo hi}  内容の波括弧は対応が取れていること (改行は含まない)
+    fn parse_inline_src_block(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            if prev.is_some_and(|c| c.is_alphanumeric()) {
+                return error(s);
+            }
+            let (rest, (_, language, parameters, _)) = tuple((
+                tag("src_"),
+                take_while1(|c: char| !c.is_whitespace() && c != '[' && c != '{'),
+                opt(bracketed),
+                char('{'),
+            ))(s)?;
+            let mut depth = 0;
+            let mut end = None;
+            for (idx, c) in rest.char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' if depth == 0 => {
+                        end = Some(idx);
+                        break;
+                    }
+                    '}' => depth -= 1,
+                    '\n' => break,
+                    _ => {}
+                }
+            }
+            let end = match end {
+                Some(end) => end,
+                None => return error(s),
+            };
+            let remain = &rest[end + 1..];
+            let raw_value = consumed(s, remain);
+            let meta = parse_block_parameters("SRC", format!("{} {}", language, parameters.unwrap_or("")).trim());
+            let range = self.span_of(raw_value);
+            Ok((remain, ASTNode::new(ASTElm::new_inline_src_block(meta, &rest[..end], raw_value, range))))
+        }
+    }
+
+    // <2024-01-02 Tue 10:00 +1w> [2024-01-02]--[2024-01-03]
+    fn parse_timestamp_node(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, meta) = parse_timestamp(s)?;
+            let raw_value = consumed(s, remain);
+            Ok((remain, ASTNode::new(ASTElm::new_timestamp(meta, raw_value, self.span_of(raw_value)))))
+        }
+    }
+
+    /*
+     * 統計クッキー [2/5] [/] [40%] [%]
+     * 値は文書全体をパースした後に集計し直す
+     */
+    fn parse_statistics_cookie(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, cookie) = delimited(
+                char('['),
+                alt((recognize(tuple((digit0, char('/'), digit0))), recognize(tuple((digit0, char('%')))))),
+                char(']'),
+            )(s)?;
+            let meta = match cookie.strip_suffix('%') {
+                Some(percent) => StatisticsMeta { percent: true, done: percent.parse().unwrap_or(0), total: 100 },
+                None => {
+                    let (done, total) = cookie.split_at(cookie.find('/').unwrap_or(0));
+                    StatisticsMeta {
+                        percent: false,
+                        done: done.parse().unwrap_or(0),
+                        total: total[1..].parse().unwrap_or(0),
+                    }
+                }
+            };
+            let raw_value = consumed(s, remain);
+            let range = self.span_of(raw_value);
+            let node = ASTNode::new(ASTElm::new_statistics_cookie(meta, raw_value, raw_value, range));
+            Ok((remain, node))
+        }
+    }
+
+    // prev: 直前の文字 (オブジェクトを解析する範囲の先頭ならNone)
+    fn parse_object(&self, prev: Option<char>) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            alt((
+                self.parse_soft_break_node(),
+                self.parse_line_break(prev),
+                self.parse_emphasis_markup(prev),
+                self.parse_latex_fragment(prev),
+                self.parse_entity(),
+                self.parse_latex_command(),
+                self.parse_sub_superscript(prev),
+                self.parse_macro(),
+                self.parse_inline_babel_call(prev),
+                self.parse_inline_src_block(prev),
+                self.parse_bracket_link(),
+                self.parse_radio_target(),
+                self.parse_target(),
+                self.parse_footnote_reference(),
+                self.parse_timestamp_node(),
+                self.parse_statistics_cookie(),
+                self.parse_angle_link(),
+                self.parse_plain_link(prev),
+            ))(s)
+        }
+    }
+
+    /*
+     * オブジェクト(インライン要素)のパース
+     * どのオブジェクトにも一致しない文字はテキストとしてまとめる
+     */
+    fn parse_objects(&self, s: &str) -> Vec<ASTNode> {
+        let mut nodes = vec![];
+        let mut text_begin = s;
+        let mut remain = s;
+        let mut prev = None;
+        while let Some(c) = remain.chars().next() {
+            match self.parse_object(prev)(remain) {
+                Ok((r, node)) => {
+                    self.push_text(&mut nodes, consumed(text_begin, remain));
+                    nodes.push(node);
+                    prev = consumed(remain, r).chars().next_back();
+                    remain = r;
+                    text_begin = r;
+                }
+                Err(_) => {
+                    prev = Some(c);
+                    remain = &remain[c.len_utf8()..];
+                }
+            }
+        }
+        self.push_text(&mut nodes, text_begin);
+        nodes
+    }
+
+    fn push_text(&self, nodes: &mut Vec<ASTNode>, text: &str) {
+        if !text.is_empty() {
+            nodes.push(ASTNode::new(ASTElm::new_text(text, self.span_of(text))));
+        }
+    }
+}
+
+/* ---------- elements ---------- */
+
+impl<'a> Parser<'a> {
+    /*
+     * 見出し
+     * <Stars> <Space> [<TodoKeyword>] [<Priority>] [COMMENT] <Title> [<Tags>]
+     * 見出しのレベルに上限はない
+     */
+    fn parse_headline(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (stars, _, line, _)) = tuple((
+                take_while1(|c| c == '*'),
+                alt((space1, peek(line_ending), eof)),
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+
+            if stars.len() >= INLINETASK_MIN_LEVEL {
+                return error(s);
+            }
+
+            let (title, meta) = self.parse_headline_components(stars.len(), line);
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_headline(meta, title, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(self.parse_objects(title));
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * インラインタスク (INLINETASK_MIN_LEVEL 以上の深さの見出し)
+     * *************** TODO Task
+     * 内容
+     * *************** END
+     * END の行が無ければ見出しの行(と計画行)のみ。節を作らず、前後の要素の間に置かれる。
+     */
+    fn parse_inlinetask(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (stars, _, line, _)) = tuple((
+                take_while1(|c| c == '*'),
+                alt((space1, peek(line_ending), eof)),
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+            if stars.len() < INLINETASK_MIN_LEVEL || line.trim() == "END" {
+                return error(s);
+            }
+            let (title, meta) = self.parse_headline_components(stars.len(), line);
+            let headline_raw = trim_line_ending(consumed(s, rest));
+            let range = self.range_of(headline_raw);
+            let mut headline = ASTNode::new(ASTElm::new_headline(meta, title, headline_raw, range));
+            headline.append_node_from_vec(self.parse_objects(title));
+
+            let mut nodes = vec![];
+            let (contents_begin, planning) = match self.parse_planning()(rest) {
+                Ok((r, planning)) => (r, Some(planning)),
+                Err(_) => (rest, None),
+            };
+            if let (ASTMetaData::Headline(meta), Some(ASTMetaData::Planning(planning_meta))) =
+                (headline.meta_mut(), planning.as_ref().map(|p| p.meta()))
+            {
+                meta.planning = planning_meta.clone();
+            }
+            nodes.push(headline);
+            nodes.extend(planning);
+
+            let mut remain = contents_begin;
+            let mut end = None;
+            while let Ok((r, line)) = self.parse_line()(remain) {
+                let end_stars = line.len() - line.trim_start_matches('*').len();
+                if end_stars >= INLINETASK_MIN_LEVEL && line[end_stars..].trim() == "END" {
+                    end = Some((consumed(contents_begin, remain), r));
+                    break;
+                }
+                if self.parse_headline()(remain).is_ok() {
+                    break;
+                }
+                remain = r;
+            }
+            let remain = match end {
+                Some((contents, r)) => {
+                    nodes.extend(self.parse_elements(contents));
+                    r
+                }
+                None => contents_begin,
+            };
+
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_inlinetask(raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(nodes);
+            Ok((remain, node))
+        }
+    }
+
+    fn parse_headline_components<'b>(&self, level: usize, line: &'b str) -> (&'b str, HeadlineMeta) {
+        let mut meta = HeadlineMeta { level, ..Default::default() };
+        let mut rest = line.trim_start();
+
+        // TODOキーワード
+        if let Ok((r, word)) = take_till::<_, _, nom::error::Error<&str>>(|c: char| c.is_whitespace())(rest) {
+            if let Some(todo_type) = self.todo_keywords.todo_type(word) {
+                if r.is_empty() || r.starts_with(char::is_whitespace) {
+                    meta.todo_keyword = Some(word.to_string());
+                    meta.todo_type = Some(todo_type);
+                    rest = r.trim_start();
+                }
+            }
+        }
+
+        // 優先度 [#A]
+        if let Ok((r, (_, priority, _))) = tuple((
+            tag::<_, _, nom::error::Error<&str>>("[#"),
+            take_while1(|c: char| c.is_ascii_alphanumeric()),
+            char(']'),
+        ))(rest)
+        {
+            if r.is_empty() || r.starts_with(char::is_whitespace) {
+                meta.priority = Some(priority.to_string());
+                rest = r.trim_start();
+            }
+        }
+
+        // COMMENT
+        if let Some(r) = rest.strip_prefix("COMMENT") {
+            if r.is_empty() || r.starts_with(char::is_whitespace) {
+                meta.commented = true;
+                rest = r.trim_start();
+            }
+        }
+
+        // タグ :tag1:tag2:
+        let trimmed = rest.trim_end();
+        let tags_begin = trimmed.rfind(char::is_whitespace).map(|idx| idx + 1).unwrap_or(0);
+        let candidate = &trimmed[tags_begin..];
+        if is_tags(candidate) {
+            meta.tags = candidate.split(':').filter(|t| !t.is_empty()).map(|t| t.to_string()).collect();
+            rest = &trimmed[..tags_begin];
+        }
+
+        (rest.trim(), meta)
+    }
+
+    // #+KEY: value, #+CAPTION[short]: value, #+RESULTS[hash]:
+    fn parse_keyword(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, _, key, option, _, _, value, _)) = tuple((
+                space0,
+                tag("#+"),
+                take_while1(|c: char| !c.is_whitespace() && c != ':' && c != '['),
+                opt(bracketed),
+                char(':'),
+                space0,
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let node = ASTNode::new(ASTElm::new_keyword(
+                &key.to_uppercase(),
+                option,
+                value.trim_end(),
+                raw_value,
+                self.range_of(raw_value),
+            ));
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * プロパティドロワー
+     * :PROPERTIES:
+     * :KEY: value
+     * :END:
+     */
+    fn parse_property_drawer(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (mut remain, _) = tuple((space0, tag_no_case(":PROPERTIES:"), space0, alt((line_ending, eof))))(s)?;
+            let mut properties = vec![];
+            loop {
+                if let Ok((r, _)) = self.parse_drawer_end()(remain) {
+                    remain = r;
+                    break;
+                }
+                let (r, property) = self.parse_node_property()(remain)?;
+                properties.push(property);
+                remain = r;
+            }
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_property_drawer(raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(properties);
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * ドロワー
+     * :NAME:
+     * contents
+     * :END:
+     * :LOGBOOK: もこの形。内容は要素として解釈する。
+     */
+    fn parse_drawer(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, _, name, _, _, _)) = tuple((
+                space0,
+                char(':'),
+                take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-'),
+                char(':'),
+                space0,
+                alt((line_ending, eof)),
+            ))(s)?;
+            if name.eq_ignore_ascii_case("END") {
+                return error(s);
+            }
+            let mut remain = rest;
+            let contents = loop {
+                if let Ok((r, _)) = self.parse_drawer_end()(remain) {
+                    let contents = consumed(rest, remain);
+                    remain = r;
+                    break contents;
+                }
+                remain = self.parse_line()(remain)?.0;
+            };
+
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_drawer(name, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(self.parse_elements(contents));
+            Ok((remain, node))
+        }
+    }
+
+    fn parse_drawer_end(&self) -> impl Fn(&str) -> IResult<&str, &str> + '_ {
+        move |s| {
+            let (remain, _) = tuple((space0, tag_no_case(":END:"), space0, alt((line_ending, eof))))(s)?;
+            Ok((remain, consumed(s, remain)))
+        }
+    }
+
+    // :KEY: value
+    fn parse_node_property(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, _, key, _, _, value, _)) = tuple((
+                space0,
+                char(':'),
+                take_while1(|c: char| !c.is_whitespace() && c != ':'),
+                char(':'),
+                alt((space1, peek(line_ending), eof)),
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let node = ASTNode::new(ASTElm::new_node_property(key, value.trim(), raw_value, self.range_of(raw_value)));
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * リスト
+     * 同じ字下げの項目が続く間を一つのリストとする。
+     * 項目の間の空行は一つまで (二つ続くとリストが終わる)。
+     * 種類は最初の項目で決まる
+     *   1. 1)          -> Ordered
+     *   - term :: desc -> Descriptive
+     *   - + *          -> Unordered
+     */
+    fn parse_plain_list(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (_, (indent, _)) = self.parse_bullet()(s)?;
+            let mut remain = s;
+            let mut items = vec![];
+            let mut next_item = alt((self.parse_item(indent), preceded(self.parse_blank_line(), self.parse_item(indent))));
+            while let Ok((r, item)) = next_item(remain) {
+                items.push(item);
+                remain = r;
+            }
+            if items.is_empty() {
+                return error(s);
+            }
+
+            let kind = match (items[0].meta(), items[0].children().first()) {
+                (ASTMetaData::Item(meta), _) if meta.bullet.starts_with(|c: char| c.is_ascii_digit()) => {
+                    ListKind::Ordered
+                }
+                (_, Some(first)) if first.node_type() == &ASTType::ItemTag => ListKind::Descriptive,
+                _ => ListKind::Unordered,
+            };
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_plain_list(kind, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(items);
+            Ok((remain, node))
+        }
+    }
+
+    // 行頭記号 (字下げの幅, 記号)
+    // '*' は字下げされている場合のみ (字下げが無ければ見出し)
+    fn parse_bullet(&self) -> impl Fn(&str) -> IResult<&str, (usize, &str)> + '_ {
+        move |s| {
+            let (rest, _) = space0(s)?;
+            let indent = self.column(rest);
+            let (remain, bullet) = alt((
+                tag("-"),
+                tag("+"),
+                tag("*"),
+                recognize(tuple((digit1, one_of(".)")))),
+            ))(rest)?;
+            if bullet == "*" && indent == 0 {
+                return error(s);
+            }
+            let (remain, _) = alt((space1, peek(line_ending), eof))(remain)?;
+            Ok((remain, (indent, bullet)))
+        }
+    }
+
+    /*
+     * 項目
+     * <Bullet> [@Counter] [Checkbox] [Tag ::] Contents
+     * 内容は、字下げが行頭記号以下の行が現れるか、空行が二つ続くまで
+     */
+    fn parse_item(&self, indent: usize) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (mut rest, (bullet_indent, bullet)) = self.parse_bullet()(s)?;
+            if bullet_indent != indent {
+                return error(s);
+            }
+            // 内容の桁 (行頭記号と続く空白の後)
+            let content_column = bullet_indent + consumed(s, rest).trim_start_matches([' ', '\t']).chars().count();
+            let mut meta = ItemMeta { bullet: bullet.to_string(), ..Default::default() };
+
+            // [@5]
+            if let Ok((r, (_, counter, _, _))) = tuple((
+                tag::<_, _, nom::error::Error<&str>>("[@"),
+                digit1,
+                char(']'),
+                alt((space1, peek(line_ending), eof)),
+            ))(rest)
+            {
+                meta.counter = counter.parse().ok();
+                rest = r;
+            }
+
+            // [ ] [X] [-]
+            if let Ok((r, (_, mark, _, _))) = tuple((
+                char::<_, nom::error::Error<&str>>('['),
+                one_of(" xX-"),
+                char(']'),
+                alt((space1, peek(line_ending), eof)),
+            ))(rest)
+            {
+                meta.checkbox = Some(match mark {
+                    ' ' => Checkbox::Off,
+                    '-' => Checkbox::Trans,
+                    _ => Checkbox::On,
+                });
+                rest = r;
+            }
+
+            // 内容の終わり
+            let (mut remain, _) = alt((self.parse_line(), eof))(rest)?;
+            let mut end = remain;
+            let mut blank_lines = 0;
+            while !remain.is_empty() {
+                if let Ok((r, _)) = self.parse_blank_line()(remain) {
+                    blank_lines += 1;
+                    if blank_lines > 1 {
+                        break;
+                    }
+                    remain = r;
+                    continue;
+                }
+                if indent_width(remain) <= indent {
+                    break;
+                }
+                remain = self.parse_line()(remain)?.0;
+                end = remain;
+                blank_lines = 0;
+            }
+
+            let raw_value = trim_line_ending(consumed(s, end));
+            let mut contents = &rest[..rest.len() - end.len()];
+            let mut children = vec![];
+
+            // term :: description (番号無しリストのみ)
+            if !bullet.starts_with(|c: char| c.is_ascii_digit()) {
+                let line = not_line_ending::<_, nom::error::Error<&str>>(contents)?.1;
+                if let Some(idx) = find_item_tag_separator(line) {
+                    let item_tag = line[..idx].trim_end();
+                    let mut node = ASTNode::new(ASTElm::new_item_tag(item_tag, self.span_of(item_tag)));
+                    node.append_node_from_vec(self.parse_objects(item_tag));
+                    children.push(node);
+                    contents = contents[idx + 2..].trim_start_matches([' ', '\t']);
+                }
+            }
+            children.extend(self.parse_elements(contents));
+            for child in children.iter_mut().filter(|child| child.node_type() == &ASTType::Paragraph) {
+                strip_continuation_indent(child.children_mut(), content_column);
+            }
+
+            let mut node = ASTNode::new(ASTElm::new_item(meta, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(children);
+            Ok((end, node))
+        }
+    }
+
+    /*
+     * ブロック
+     * #+BEGIN_NAME parameters
+     * contents
+     * #+END_NAME
+     *
+     * 名前は大文字小文字を区別しない。対応する #+END_ が無ければブロックではない。
+     *   SRC, EXAMPLE, EXPORT, COMMENT -> 内容をそのまま value にする (行頭の ',' によるエスケープは取り除く)
+     *   VERSE                -> 内容をオブジェクトとして解釈する
+     *   QUOTE, CENTER, その他 -> 内容を要素として解釈する
+     */
+    fn parse_greater_block(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, _, name, parameters, _)) = tuple((
+                space0,
+                tag_no_case("#+BEGIN_"),
+                take_while1(|c: char| !c.is_whitespace()),
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+
+            let end_line = format!("#+END_{}", name);
+            let mut remain = rest;
+            let contents = loop {
+                let (r, line) = self.parse_line()(remain)?;
+                if line.trim().eq_ignore_ascii_case(&end_line) {
+                    let contents = consumed(rest, remain);
+                    remain = r;
+                    break contents;
+                }
+                remain = r;
+            };
+
+            let name = name.to_uppercase();
+            let meta = parse_block_parameters(&name, parameters.trim());
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let range = self.range_of(raw_value);
+            let block_type = block_type(&name);
+            let node = match block_type {
+                ASTType::SrcBlock | ASTType::ExampleBlock => {
+                    let value = remove_common_indent(&unescape_block_contents(contents));
+                    ASTNode::new(ASTElm::new_block(block_type, meta, &value, raw_value, range))
+                }
+                ASTType::ExportBlock | ASTType::CommentBlock => {
+                    let value = unescape_block_contents(contents);
+                    ASTNode::new(ASTElm::new_block(block_type, meta, &value, raw_value, range))
+                }
+                ASTType::VerseBlock => {
+                    let mut node = ASTNode::new(ASTElm::new_block(block_type, meta, "", raw_value, range));
+                    node.append_node_from_vec(self.parse_objects(trim_line_ending(contents)));
+                    node
+                }
+                _ => {
+                    let mut node = ASTNode::new(ASTElm::new_block(block_type, meta, "", raw_value, range));
+                    node.append_node_from_vec(self.parse_elements(contents));
+                    node
+                }
+            };
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * 動的ブロック
+     * #+BEGIN: clocktable :maxlevel 2 :scope file
+     * (生成された内容)
+     * #+END:
+     * 内容は要素として解釈する。引数は BlockMeta.header_args にも分解する
+     */
+    fn parse_dynamic_block(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, _, _, name, parameters, _)) = tuple((
+                space0,
+                tag_no_case("#+BEGIN:"),
+                space0,
+                take_while1(|c: char| !c.is_whitespace()),
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+
+            let mut remain = rest;
+            let contents = loop {
+                let (r, line) = self.parse_line()(remain)?;
+                if line.trim().eq_ignore_ascii_case("#+END:") {
+                    let contents = consumed(rest, remain);
+                    remain = r;
+                    break contents;
+                }
+                remain = r;
+            };
+
+            let meta = parse_block_parameters(name, parameters.trim());
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_dynamic_block(meta, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(self.parse_elements(contents));
+            Ok((remain, node))
+        }
+    }
+
+    // #+CALL: name[:session s](x=1) :results silent
+    fn parse_babel_call(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, _, _, value, _)) =
+                tuple((space0, tag_no_case("#+CALL:"), space0, not_line_ending, alt((line_ending, eof))))(s)?;
+            let (rest, (name, inside_header, arguments)) = parse_call(value)?;
+            let meta = BabelCallMeta {
+                name: name.to_string(),
+                inside_header: inside_header.map(String::from),
+                arguments: arguments.to_string(),
+                end_header: Some(rest.trim()).filter(|header| !header.is_empty()).map(String::from),
+            };
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let range = self.range_of(raw_value);
+            Ok((remain, ASTNode::new(ASTElm::new_babel_call(meta, value.trim_end(), raw_value, range))))
+        }
+    }
+
+    // #+INCLUDE: "file.org" :lines "5-10"  読み込みは文書全体をパースした後に行う
+    fn parse_include(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, _, _, value, _)) =
+                tuple((space0, tag_no_case("#+INCLUDE:"), space0, not_line_ending, alt((line_ending, eof))))(s)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let meta = parse_include_parameters(value.trim());
+            Ok((remain, ASTNode::new(ASTElm::new_include(meta, raw_value, self.range_of(raw_value)))))
+        }
+    }
+
+    /*
+     * LaTeX の環境
+     * \begin{equation}
+     * ...
+     * \end{equation}
+     * 対応する \end{name} の行が無ければ環境ではない
+     */
+    fn parse_latex_environment(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, _, name, _, _, _)) = tuple((
+                space0,
+                tag("\\begin{"),
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '*'),
+                char('}'),
+                not_line_ending,
+                alt((line_ending, eof)),
+            ))(s)?;
+
+            let end_line = format!("\\end{{{}}}", name);
+            let mut remain = rest;
+            loop {
+                let (r, line) = self.parse_line()(remain)?;
+                remain = r;
+                if line.trim() == end_line {
+                    break;
+                }
+            }
+
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let meta =
+                LatexMeta { delimiter: LatexDelimiter::Environment, display: true, environment: Some(name.to_string()) };
+            let value = remove_common_indent(raw_value);
+            let range = self.range_of(raw_value);
+            Ok((remain, ASTNode::new(ASTElm::new_latex_environment(meta, &value, raw_value, range))))
+        }
+    }
+
+    // : 固定幅の行 (続く行をまとめる)
+    fn parse_fixed_width(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, lines) = self.parse_prefixed_lines(':')(s)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let range = self.range_of(raw_value);
+            Ok((remain, ASTNode::new(ASTElm::new_fixed_width(&lines.join("\n"), raw_value, range))))
+        }
+    }
+
+    // # コメント (続く行をまとめる)  #+ で始まる行は含まない
+    fn parse_comment(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, lines) = self.parse_prefixed_lines('#')(s)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let range = self.range_of(raw_value);
+            Ok((remain, ASTNode::new(ASTElm::new_comment(&lines.join("\n"), raw_value, range))))
+        }
+    }
+
+    // 記号のみ、または記号と空白で始まる行が続く部分 (記号と続く空白一つを除いた内容)
+    fn parse_prefixed_lines(&self, mark: char) -> impl Fn(&str) -> IResult<&str, Vec<&str>> + '_ {
+        move |s| {
+            let mut lines = vec![];
+            let mut remain = s;
+            while let Ok((r, line)) = self.parse_line()(remain) {
+                let content = match line.trim_start_matches([' ', '\t']).strip_prefix(mark) {
+                    Some("") => "",
+                    Some(content) if content.starts_with([' ', '\t']) => &content[1..],
+                    _ => break,
+                };
+                lines.push(content);
+                remain = r;
+            }
+            if lines.is_empty() {
+                return error(s);
+            }
+            Ok((remain, lines))
+        }
+    }
+
+    // ----- (5つ以上)
+    fn parse_horizontal_rule(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, dashes)) = tuple((space0, take_while1(|c: char| c == '-')))(s)?;
+            if dashes.len() < 5 {
+                return error(s);
+            }
+            let (remain, _) = tuple((space0, alt((line_ending, eof))))(rest)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            Ok((remain, ASTNode::new(ASTElm::new_horizontal_rule(raw_value, self.range_of(raw_value)))))
+        }
+    }
+
+    // CLOCK: [2024-01-02 Tue 10:00]--[2024-01-02 Tue 11:30] =>  1:30
+    fn parse_clock(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (remain, (_, _, _, value, _)) =
+                tuple((space0, tag("CLOCK:"), space0, not_line_ending, alt((line_ending, eof))))(s)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_clock(value.trim_end(), raw_value, self.range_of(raw_value)));
+            if let Ok((_, timestamp)) = self.parse_timestamp_node()(value) {
+                node.append_node(timestamp);
+            }
+            Ok((remain, node))
+        }
+    }
+
+    /*
+     * 計画行 (見出しの直後の行のみ)
+     * SCHEDULED: <2024-01-02 Tue> DEADLINE: <2024-01-05 Fri -1d> CLOSED: [2024-01-03 Wed]
+     */
+    fn parse_planning(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (mut rest, _) = space0(s)?;
+            let mut meta = PlanningMeta::default();
+            let mut timestamps = vec![];
+            while let Ok((r, (keyword, _, timestamp, _))) = tuple((
+                alt((tag("SCHEDULED:"), tag("DEADLINE:"), tag("CLOSED:"))),
+                space0,
+                self.parse_timestamp_node(),
+                space0,
+            ))(rest)
+            {
+                let timestamp_meta = match timestamp.meta() {
+                    ASTMetaData::Timestamp(timestamp_meta) => Some(timestamp_meta.clone()),
+                    _ => None,
+                };
+                match keyword {
+                    "SCHEDULED:" => meta.scheduled = timestamp_meta,
+                    "DEADLINE:" => meta.deadline = timestamp_meta,
+                    _ => meta.closed = timestamp_meta,
+                }
+                timestamps.push(timestamp);
+                rest = r;
+            }
+            if timestamps.is_empty() {
+                return error(s);
+            }
+            let (remain, _) = alt((line_ending, eof))(rest)?;
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_planning(meta, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(timestamps);
+            Ok((remain, node))
+        }
+    }
+
+    // 見出しと直後の計画行
+    fn parse_headline_with_planning(&self) -> impl Fn(&str) -> IResult<&str, Vec<ASTNode>> + '_ {
+        move |s| {
+            let (remain, mut headline) = self.parse_headline()(s)?;
+            match self.parse_planning()(remain) {
+                Ok((remain, planning)) => {
+                    if let (ASTMetaData::Headline(meta), ASTMetaData::Planning(planning_meta)) =
+                        (headline.meta_mut(), planning.meta())
+                    {
+                        meta.planning = planning_meta.clone();
+                    }
+                    Ok((remain, vec![headline, planning]))
+                }
+                Err(_) => Ok((remain, vec![headline])),
+            }
+        }
+    }
+
+    /*
+     * 表
+     * | a | b |
+     * |---+---|
+     * #+TBLFM: $2=$1*2
+     * 表の直後の #+TBLFM: 行は表の一部とし、式で計算した値を欄の値にする
+     */
+    fn parse_table(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let mut remain = s;
+            let mut lines = vec![]; // (行, 欄) 横線の行は欄が None
+            while let Ok((r, line)) = self.parse_table_line()(remain) {
+                lines.push((trim_line_ending(consumed(remain, r)), split_table_cells(line)));
+                remain = r;
+            }
+            if lines.is_empty() {
+                return error(s);
+            }
+
+            let mut meta = TableMeta::default();
+            while let Ok((r, value)) = self.parse_tblfm_line()(remain) {
+                meta.formulas.extend(value.split("::").map(str::trim).filter(|f| !f.is_empty()).map(String::from));
+                remain = r;
+            }
+
+            let mut table: Vec<TableLine> = lines
+                .iter()
+                .map(|(_, cells)| match cells {
+                    Some(cells) => TableLine::Row {
+                        cells: cells.iter().map(|c| c.to_string()).collect(),
+                        special: is_special_table_row(cells),
+                    },
+                    None => TableLine::Rule,
+                })
+                .collect();
+            meta.columns = table_columns(&table);
+            if !meta.formulas.is_empty() {
+                recalculate(&mut table, &parse_formulas(&meta.formulas.join("::")));
+            }
+
+            let mut rows = vec![];
+            for ((raw, cells), line) in lines.iter().zip(table) {
+                let range = self.range_of(raw);
+                let row = match (cells, line) {
+                    (Some(cells), TableLine::Row { cells: values, special }) => {
+                        let kind = if special { TableRowKind::Special } else { TableRowKind::Standard };
+                        let mut row = ASTNode::new(ASTElm::new_table_row(kind, raw, range));
+                        for (idx, value) in values.iter().enumerate() {
+                            // 計算式で追加された欄は行末の位置にする
+                            let text = cells.get(idx).cloned().unwrap_or(&raw[raw.len()..]);
+                            let mut cell = ASTNode::new(ASTElm::new_table_cell(value, text, self.span_of(text)));
+                            if value == text {
+                                cell.append_node_from_vec(self.parse_objects(text));
+                            } else {
+                                cell.append(ASTElm::new_text(value, self.span_of(text)));
+                            }
+                            row.append_node(cell);
+                        }
+                        row
+                    }
+                    _ => ASTNode::new(ASTElm::new_table_row(TableRowKind::Rule, raw, range)),
+                };
+                rows.push(row);
+            }
+
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_table(meta, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(rows);
+            Ok((remain, node))
+        }
+    }
+
+    // 表の行 ('|' より後ろ)
+    fn parse_table_line(&self) -> impl Fn(&str) -> IResult<&str, &str> + '_ {
+        move |s| {
+            let (remain, (_, _, line, _)) = tuple((space0, char('|'), not_line_ending, alt((line_ending, eof))))(s)?;
+            Ok((remain, line))
+        }
+    }
+
+    // #+TBLFM: の値
+    fn parse_tblfm_line(&self) -> impl Fn(&str) -> IResult<&str, &str> + '_ {
+        move |s| {
+            let (remain, (_, _, value, _)) =
+                tuple((space0, tag_no_case("#+TBLFM:"), not_line_ending, alt((line_ending, eof))))(s)?;
+            Ok((remain, value))
+        }
+    }
+
+    /*
+     * 脚注の定義 (行頭から)
+     * [fn:label] contents
+     * 次の脚注の定義・見出しが現れるか、空行が二つ続くまで
+     */
+    fn parse_footnote_definition(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let (rest, (_, label, _)) = tuple((
+                tag("[fn:"),
+                take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_'),
+                char(']'),
+            ))(s)?;
+
+            let (mut remain, _) = alt((self.parse_line(), eof))(rest)?;
+            let mut end = remain;
+            let mut blank_lines = 0;
+            while !remain.is_empty() {
+                if let Ok((r, _)) = self.parse_blank_line()(remain) {
+                    blank_lines += 1;
+                    if blank_lines > 1 {
+                        break;
+                    }
+                    remain = r;
+                    continue;
+                }
+                if remain.starts_with("[fn:") || self.parse_headline()(remain).is_ok() {
+                    break;
+                }
+                remain = self.parse_line()(remain)?.0;
+                end = remain;
+                blank_lines = 0;
+            }
+
+            let raw_value = trim_line_ending(consumed(s, end));
+            let contents = &rest[..rest.len() - end.len()];
+            let meta = FootnoteMeta { label: Some(label.to_string()), inline: false, number: None };
+            let mut node = ASTNode::new(ASTElm::new_footnote_definition(meta, raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(self.parse_elements(contents.trim_start_matches([' ', '\t'])));
+            Ok((end, node))
+        }
+    }
+
+    // 見出し・段落以外の要素
+    fn parse_element(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            alt((
+                self.parse_greater_block(),
+                self.parse_dynamic_block(),
+                self.parse_inlinetask(),
+                self.parse_latex_environment(),
+                self.parse_footnote_definition(),
+                self.parse_include(),
+                self.parse_babel_call(),
+                self.parse_keyword(),
+                self.parse_comment(),
+                self.parse_fixed_width(),
+                self.parse_property_drawer(),
+                self.parse_drawer(),
+                self.parse_clock(),
+                self.parse_table(),
+                self.parse_horizontal_rule(),
+                self.parse_plain_list(),
+            ))(s)
+        }
+    }
+
+    /*
+     * 段落
+     * 空行・見出し・他の要素の開始まで
+     */
+    fn parse_paragraph(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| {
+            let mut remain = s;
+            loop {
+                if remain.is_empty() || self.parse_blank_line()(remain).is_ok() {
+                    break;
+                }
+                if remain.len() != s.len()
+                    && (self.parse_headline()(remain).is_ok() || self.parse_element()(remain).is_ok())
+                {
+                    break;
+                }
+                remain = self.parse_line()(remain)?.0;
+            }
+            if remain.len() == s.len() {
+                return error(s);
+            }
+
+            let raw_value = trim_line_ending(consumed(s, remain));
+            let mut node = ASTNode::new(ASTElm::new_paragraph("", raw_value, self.range_of(raw_value)));
+            node.append_node_from_vec(self.parse_objects(raw_value));
+            Ok((remain, node))
+        }
+    }
+
+    // 前の空行は読み飛ばす (再帰せずに読むので、空行がいくら続いてもよい)
+    fn parse_block(&self) -> impl Fn(&str) -> IResult<&str, Vec<ASTNode>> + '_ {
+        move |s| {
+            preceded(
+                many0(self.parse_blank_line()),
+                alt((
+                    self.parse_headline_with_planning(),
+                    map(self.parse_element(), |node| vec![node]),
+                    map(self.parse_paragraph(), |node| vec![node]),
+                )),
+            )(s)
+        }
+    }
+
+    // 見出しを含まない要素の並び (項目などの内容)
+    fn parse_elements(&self, s: &str) -> Vec<ASTNode> {
+        many0(self.parse_content_block())(s).map(|(_, nodes)| nodes).unwrap_or_default()
+    }
+
+    // 前の空行は読み飛ばす (parse_block と同じ)
+    fn parse_content_block(&self) -> impl Fn(&str) -> IResult<&str, ASTNode> + '_ {
+        move |s| preceded(many0(self.parse_blank_line()), alt((self.parse_element(), self.parse_paragraph())))(s)
+    }
+
+    fn parse_document(&self, s: &str, mut node: ASTNode) -> ASTNode {
+        node.set_node_type(ASTType::Document);
+        node.set_meta(ASTMetaData::Nil);
+        node.set_value("".to_string());
+        node.set_raw_value(s.to_string());
+
+        if let Ok((_, result)) = many0(self.parse_block())(s) {
+            node.append_node_from_vec(result.into_iter().flatten().collect());
+        }
+        node.set_range(ASTRange::new(ASTPos::new(1, 1, 0), self.pos_at(s.len())));
+
+        node
+    }
+}
+
+/* ---------- include ---------- */
+
+impl<'a> Parser<'a> {
+    /*
+     * #+INCLUDE: の読み込み
+     * 読み込んだ内容はこのファイルの設定でパースして Include の子要素にする。
+     * 子要素の位置情報は、読み込んだファイルの中の位置になる。
+     * files: 読み込み中のファイル (循環した読み込みへの対策)
+     */
+    fn resolve_includes(&self, document: &mut ASTArena, loader: &dyn FileLoader, files: &mut Vec<String>) {
+        let includes: Vec<NodeId> = document
+            .pre_order(document.root())
+            .filter(|&id| document.node(id).node_type() == &ASTType::Include)
+            .collect();
+        for include in includes {
+            let mut meta = match document.node(include).meta() {
+                ASTMetaData::Include(meta) => meta.clone(),
+                _ => continue,
+            };
+            let text = match files.contains(&meta.file) {
+                true => Err(format!("{}: recursive include", meta.file)),
+                false => loader.load(&meta.file),
+            };
+            match text {
+                Ok(text) => {
+                    files.push(meta.file.clone());
+                    let mut included = self.parse_included(&meta, &text, loader, files);
+                    files.pop();
+                    mark_source_file(&mut included, &meta.file);
+                    document.graft(include, included);
+                }
+                Err(error) => {
+                    meta.error = Some(error);
+                    document.data_mut(include).elm_meta = ASTMetaData::Include(meta);
+                }
+            }
+        }
+    }
+
+    // 読み込んだ内容を文書にする (根の子が Include の子になる)
+    fn parse_included(
+        &self,
+        meta: &IncludeMeta,
+        text: &str,
+        loader: &dyn FileLoader,
+        files: &mut Vec<String>,
+    ) -> ASTArena {
+        let parser = Parser::with_settings(text, self);
+        let contents = match &meta.lines {
+            Some(lines) => select_lines(text, lines),
+            None => text,
+        };
+        let mut document = ASTNode::new(ASTElm::new_document());
+
+        // ブロックで囲む
+        if let Some(name) = &meta.block {
+            let block_type = block_type(name);
+            let block_meta = parse_block_parameters(name, &meta.parameters);
+            let raw_value = trim_line_ending(contents);
+            let range = parser.range_of(raw_value);
+            let node = match block_type {
+                ASTType::SrcBlock | ASTType::ExampleBlock | ASTType::ExportBlock | ASTType::CommentBlock => {
+                    ASTNode::new(ASTElm::new_block(block_type, block_meta, contents, raw_value, range))
+                }
+                ASTType::VerseBlock => {
+                    let mut node = ASTNode::new(ASTElm::new_block(block_type, block_meta, "", raw_value, range));
+                    node.append_node_from_vec(parser.parse_objects(raw_value));
+                    node
+                }
+                _ => {
+                    let mut node = ASTNode::new(ASTElm::new_block(block_type, block_meta, "", raw_value, range));
+                    node.append_node_from_vec(parser.parse_elements(contents));
+                    node
+                }
+            };
+            document.append_node(node);
+            return ASTArena::new(document);
+        }
+
+        let mut nodes: Vec<ASTNode> = many0(parser.parse_block())(contents)
+            .map(|(_, result)| result.into_iter().flatten().collect())
+            .unwrap_or_default();
+        // :minlevel 最も浅い見出しがそのレベルになるようにずらす
+        if let Some(minlevel) = meta.minlevel {
+            if let Some(top) = nodes.iter().filter_map(|node| node.heading_level()).min() {
+                for node in nodes.iter_mut() {
+                    if let ASTMetaData::Headline(meta) = node.meta_mut() {
+                        meta.level = (meta.level + minlevel).saturating_sub(top).max(1);
+                    }
+                }
+            }
+        }
+        document.append_node_from_vec(nodes);
+        let mut document = ASTArena::new(document);
+        parser.resolve_includes(&mut document, loader, files);
+        sectionize(&mut document);
+        document
+    }
+}
+
+// 読み込んだファイルの要素に印を付ける (入れ子の #+INCLUDE: で先に付けたファイルはそのまま)
+fn mark_source_file(document: &mut ASTArena, file: &str) {
+    let nodes: Vec<NodeId> = document.descendants(document.root()).collect();
+    for id in nodes {
+        document.data_mut(id).source_file.get_or_insert_with(|| file.to_string());
+    }
+}
+
+// 空文字列に一致した場合は失敗させる
+fn verify_not_empty<'b, F>(mut f: F) -> impl FnMut(&'b str) -> IResult<&'b str, &'b str>
+where
+    F: FnMut(&'b str) -> IResult<&'b str, &'b str>,
+{
+    move |s| match f(s)? {
+        (_, "") => error(s),
+        r => Ok(r),
+    }
+}
+
+/*
+ * 見出し直後(計画行があればその後)のプロパティドロワーの内容を見出しの付加情報にする
+ * (節に入れ子にした後に行う)
+ */
+fn attach_properties(document: &mut ASTArena) {
+    let sections: Vec<NodeId> = document
+        .pre_order(document.root())
+        .filter(|&id| matches!(document.node(id).node_type(), ASTType::Section | ASTType::Inlinetask))
+        .collect();
+    for section in sections {
+        let node = document.node(section);
+        let drawer = node.children().skip(1).find(|c| c.node_type() != &ASTType::Planning);
+        let properties: Option<Vec<(String, String)>> = drawer.and_then(|drawer| {
+            if drawer.node_type() != &ASTType::PropertyDrawer {
+                return None;
+            }
+            Some(
+                drawer
+                    .children()
+                    .filter_map(|property| match property.meta() {
+                        ASTMetaData::Property { key } => Some((key.to_uppercase(), property.value().to_string())),
+                        _ => None,
+                    })
+                    .collect(),
+            )
+        });
+        let headline = node.first_child().map(|headline| headline.id());
+        if let (Some(properties), Some(headline)) = (properties, headline) {
+            if let ASTMetaData::Headline(meta) = &mut document.data_mut(headline).elm_meta {
+                for (key, value) in properties {
+                    // :KEY+: value は既存の値に空白区切りで追加する
+                    match key.strip_suffix('+') {
+                        Some(key) => {
+                            let entry = meta.properties.entry(key.to_string()).or_default();
+                            if !entry.is_empty() {
+                                entry.push(' ');
+                            }
+                            entry.push_str(&value);
+                        }
+                        None => {
+                            meta.properties.insert(key, value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// [...] (改行を含まない)
+fn bracketed(s: &str) -> IResult<&str, &str> {
+    delimited(char('['), take_till(|c: char| c == ']' || c == '\n'), char(']'))(s)
+}
+
+// name[inside_header](arguments)
+fn parse_call(s: &str) -> IResult<&str, (&str, Option<&str>, &str)> {
+    tuple((
+        take_while1(|c: char| !c.is_whitespace() && !"[](){}".contains(c)),
+        opt(bracketed),
+        delimited(char('('), take_till(|c: char| c == ')' || c == '\n'), char(')')),
+    ))(s)
+}
+
+fn block_type(name: &str) -> ASTType {
+    match name {
+        "SRC" => ASTType::SrcBlock,
+        "EXAMPLE" => ASTType::ExampleBlock,
+        "EXPORT" => ASTType::ExportBlock,
+        "COMMENT" => ASTType::CommentBlock,
+        "VERSE" => ASTType::VerseBlock,
+        "QUOTE" => ASTType::QuoteBlock,
+        "CENTER" => ASTType::CenterBlock,
+        _ => ASTType::SpecialBlock,
+    }
+}
+
+/*
+ * ブロックの引数
+ *   SRC:    <言語> <スイッチ> <ヘッダー引数>
+ *   EXPORT: <バックエンド>
+ *   その他: <スイッチ> <ヘッダー引数>
+ * ヘッダー引数は ':key value...' の形で、次の ':key' までを値とする
+ */
+pub fn parse_block_parameters(name: &str, parameters: &str) -> BlockMeta {
+    let mut meta = BlockMeta { name: name.to_string(), parameters: parameters.to_string(), ..Default::default() };
+    let mut tokens = split_parameters(parameters).into_iter().peekable();
+
+    if name == "SRC" || name == "EXPORT" {
+        if let Some(language) = tokens.next_if(|t| !t.starts_with(['-', '+', ':'])) {
+            meta.language = Some(language);
+        }
+    }
+    while let Some(switch) = tokens.next_if(|t| !t.starts_with(':')) {
+        // -l "(ref:%s)" は一つのスイッチとして扱う
+        match meta.switches.last_mut() {
+            Some(last) if switch.starts_with('"') => {
+                last.push(' ');
+                last.push_str(&switch);
+            }
+            _ => meta.switches.push(switch),
+        }
+    }
+    while let Some(key) = tokens.next() {
+        let mut values = vec![];
+        while let Some(value) = tokens.next_if(|t| !t.starts_with(':')) {
+            values.push(value);
+        }
+        meta.header_args.insert(key[1..].to_string(), values.join(" "));
+    }
+    meta
+}
+
+// 空白で区切る ("..." の中の空白では区切らない)
+pub(crate) fn split_parameters(s: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            _ if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+// 行頭の ",*" ",#+" のエスケープを取り除く (",,*" -> ",*")
+fn unescape_block_contents(s: &str) -> String {
+    s.split_inclusive('\n')
+        .map(|line| {
+            let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let rest = &line[indent..];
+            let escaped = rest.trim_start_matches(',');
+            if escaped.len() < rest.len() && (escaped.starts_with('*') || escaped.starts_with("#+")) {
+                format!("{}{}", &line[..indent], &rest[1..])
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+// 共通の字下げを取り除く (空行は数えない)
+fn remove_common_indent(s: &str) -> String {
+    let indent = s.lines().filter(|l| !l.trim().is_empty()).map(indent_width).min().unwrap_or(0);
+    s.split_inclusive('\n')
+        .map(|line| {
+            let mut width = 0;
+            let begin = line
+                .char_indices()
+                .find(|(_, c)| {
+                    if width >= indent || !(*c == ' ' || *c == '\t') {
+                        return true;
+                    }
+                    width += if *c == '\t' { 8 } else { 1 };
+                    false
+                })
+                .map(|(idx, _)| idx)
+                .unwrap_or(line.len());
+            &line[begin..]
+        })
+        .collect()
+}
+
+// 先頭の '|' より後ろを欄に分ける (横線の行は None)
+fn split_table_cells(line: &str) -> Option<Vec<&str>> {
+    if line.starts_with('-') {
+        return None;
+    }
+    let line = line.trim_end();
+    let line = line.strip_suffix('|').unwrap_or(line);
+    Some(line.split('|').map(str::trim).collect())
+}
+
+// 幅指定 <10> <r> <l10>
+fn parse_width_cookie(s: &str) -> Option<(Option<Alignment>, Option<u32>)> {
+    let inner = s.strip_prefix('<')?.strip_suffix('>')?;
+    let (align, width) = match inner.chars().next()? {
+        'l' => (Some(Alignment::Left), &inner[1..]),
+        'c' => (Some(Alignment::Center), &inner[1..]),
+        'r' => (Some(Alignment::Right), &inner[1..]),
+        _ => (None, inner),
+    };
+    if width.is_empty() {
+        return align.map(|align| (Some(align), None));
+    }
+    Some((align, Some(width.parse().ok()?)))
+}
+
+// 幅指定のみの行、または先頭の欄が '/' の列グループの行
+fn is_special_table_row(cells: &[&str]) -> bool {
+    cells.first() == Some(&"/")
+        || (cells.iter().any(|c| !c.is_empty())
+            && cells.iter().all(|c| c.is_empty() || parse_width_cookie(c).is_some()))
+}
+
+fn table_columns(table: &[TableLine]) -> Vec<TableColumn> {
+    let mut columns: Vec<TableColumn> = vec![];
+    for line in table {
+        if let TableLine::Row { cells, special: true } = line {
+            if columns.len() < cells.len() {
+                columns.resize(cells.len(), TableColumn::default());
+            }
+            let group_row = cells.first().map(|c| c.as_str()) == Some("/");
+            for (column, cell) in columns.iter_mut().zip(cells) {
+                if group_row {
+                    column.group_start |= cell == "<" || cell == "<>";
+                    column.group_end |= cell == ">" || cell == "<>";
+                } else if let Some((align, width)) = parse_width_cookie(cell) {
+                    column.align = align.or(column.align.take());
+                    column.width = width.or(column.width);
+                }
+            }
+        }
+    }
+    columns
+}
+
+/*
+ * 統計クッキーの集計
+ *   見出し: 節の直下のリストの項目のチェックボックス。無ければ直下の見出しのTODO
+ *   項目:   直下の子項目のチェックボックス
+ * [X] の項目・完了状態の見出しを完了として数える
+ */
+fn update_statistics(document: &mut ASTArena) {
+    let nodes: Vec<NodeId> = document.pre_order(document.root()).collect();
+    for id in nodes {
+        let node = document.node(id);
+        let (targets, done, total): (Vec<NodeId>, usize, usize) = match node.node_type() {
+            ASTType::Section => {
+                let (done, total) = match count_checkboxes(node) {
+                    (_, 0) => count_todo_headlines(node),
+                    counts => counts,
+                };
+                (node.first_child().map(|headline| headline.id()).into_iter().collect(), done, total)
+            }
+            ASTType::Item => {
+                let (done, total) = count_checkboxes(node);
+                let targets = node.children().filter(|c| c.node_type() != &ASTType::PlainList).map(|c| c.id());
+                (targets.collect(), done, total)
+            }
+            _ => continue,
+        };
+        for target in targets {
+            fill_statistics(document, target, done, total);
+        }
+    }
+}
+
+// 直下のリストの項目のチェックボックスを数える (完了, 全体)
+fn count_checkboxes(node: NodeRef) -> (usize, usize) {
+    let mut counts = (0, 0);
+    for list in node.children().filter(|c| c.node_type() == &ASTType::PlainList) {
+        for item in list.children() {
+            if let ASTMetaData::Item(ItemMeta { checkbox: Some(checkbox), .. }) = item.meta() {
+                counts.1 += 1;
+                if checkbox == &Checkbox::On {
+                    counts.0 += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+// 直下の節の見出しのTODOを数える (完了, 全体)
+fn count_todo_headlines(node: NodeRef) -> (usize, usize) {
+    let mut counts = (0, 0);
+    for section in node.children().filter(|c| c.node_type() == &ASTType::Section) {
+        if let Some(headline) = section.first_child() {
+            if let ASTMetaData::Headline(HeadlineMeta { todo_type: Some(todo_type), .. }) = headline.meta() {
+                counts.1 += 1;
+                if todo_type == &TodoType::Done {
+                    counts.0 += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+// 配下の統計クッキーに集計結果を書き込む
+fn fill_statistics(document: &mut ASTArena, id: NodeId, done: usize, total: usize) {
+    let cookies: Vec<NodeId> = document
+        .pre_order(id)
+        .filter(|&id| matches!(document.node(id).meta(), ASTMetaData::Statistics(_)))
+        .collect();
+    for cookie in cookies {
+        let data = document.data_mut(cookie);
+        if let ASTMetaData::Statistics(meta) = &mut data.elm_meta {
+            meta.done = done;
+            meta.total = total;
+            data.value = if meta.percent {
+                format!("[{}%]", (done * 100).checked_div(total).unwrap_or(0))
+            } else {
+                format!("[{}/{}]", done, total)
+            };
+        }
+    }
+}
+
+// 記述リストの区切り " :: " の位置
+fn find_item_tag_separator(line: &str) -> Option<usize> {
+    line.match_indices("::").map(|(idx, _)| idx).find(|idx| {
+        idx > &0
+            && line[..*idx].ends_with(char::is_whitespace)
+            && line[idx + 2..].chars().next().is_none_or(char::is_whitespace)
+    })
+}
+
+/*
+ * 項目の段落の継続行から、内容の桁までの字下げを取り除く
+ *   - one
+ *     continued  ->  <text>one</text><softbreak /><text>continued</text>
+ * 取り除いた分だけテキストの範囲の先頭をずらす
+ */
+fn strip_continuation_indent(nodes: &mut Vec<ASTNode>, column: usize) {
+    let mut after_break = false;
+    for node in nodes.iter_mut() {
+        if after_break && node.node_type() == &ASTType::Text {
+            let mut width = 0;
+            let indent: String = node
+                .value()
+                .chars()
+                .take_while(|c| {
+                    width += match c {
+                        ' ' => 1,
+                        '\t' => 8,
+                        _ => return false,
+                    };
+                    width <= column
+                })
+                .collect();
+            if !indent.is_empty() {
+                let value = node.value()[indent.len()..].to_string();
+                let raw_value = node.raw_value().get(indent.len()..).unwrap_or_default().to_string();
+                node.set_value(value);
+                node.set_raw_value(raw_value);
+                node.range_mut().begin.increase_ch_n(indent.chars().count() as u32);
+            }
+        }
+        after_break = node.node_type() == &ASTType::SoftBreak;
+        strip_continuation_indent(node.children_mut(), column);
+    }
+    nodes.retain(|node| node.node_type() != &ASTType::Text || !node.value().is_empty());
+}
+
+// 強調記号の前後に置ける文字 (行頭・行末も可)
+fn is_emphasis_pre(c: Option<char>) -> bool {
+    match c {
+        None => true,
+        Some(c) => c.is_whitespace() || "-({'\"".contains(c),
+    }
+}
+
+fn is_emphasis_post(c: Option<char>) -> bool {
+    match c {
+        None => true,
+        Some(c) => c.is_whitespace() || "-.,;:!?')}[\"\\".contains(c),
+    }
+}
+
+// :tag1:tag2: の形式か
+fn is_tags(s: &str) -> bool {
+    s.len() > 2
+        && s.starts_with(':')
+        && s.ends_with(':')
+        && s[1..s.len() - 1]
+            .split(':')
+            .all(|t| !t.is_empty() && t.chars().all(|c| c.is_alphanumeric() || "_@#%".contains(c)))
+}
+
+// 見出しは節(Section)として入れ子にする
+pub fn org_parse(s: &str, node: ASTNode) -> ASTArena {
+    parse_org_document(s, node, None)
+}
+
+// #+INCLUDE: のファイルを loader で読み込む
+pub fn org_parse_with_loader(s: &str, node: ASTNode, loader: &dyn FileLoader) -> ASTArena {
+    parse_org_document(s, node, Some(loader))
+}
+
+// 規則で組み立てた文書を ASTArena に移してから、木全体を見る処理を行う
+fn parse_org_document(s: &str, node: ASTNode, loader: Option<&dyn FileLoader>) -> ASTArena {
+    let parser = Parser::new(s);
+    let mut document = ASTArena::new(parser.parse_document(s, node));
+    if let Some(loader) = loader {
+        parser.resolve_includes(&mut document, loader, &mut vec![]);
+    }
+    sectionize(&mut document);
+    attach_properties(&mut document);
+    attach_affiliated_keywords(&mut document);
+    link_results(&mut document);
+    expand_macros(&mut document, |text| Parser::with_settings(text, &parser).parse_objects(text));
+    expand_noweb(&mut document);
+    update_statistics(&mut document);
+    link_radio_targets(&mut document);
+    resolve_links(&mut document);
+    build_document_meta(&mut document, parser.options.clone());
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headline_meta(meta: &ASTMetaData) -> &HeadlineMeta {
+        match meta {
+            ASTMetaData::Headline(meta) => meta,
+            _ => panic!("not a headline"),
+        }
+    }
+
+    #[test]
+    fn test_parse_headline() {
+        let src = "** TODO [#A] Title *text* :tag1:tag2:";
+        let parser = Parser::new(src);
+        let (remain, node) = parser.parse_headline()(src).unwrap();
+        assert_eq!(remain, "");
+        assert_eq!(node.value(), "Title *text*");
+        assert_eq!(node.raw_value(), src);
+        assert_eq!(node.range(), &ASTRange::new(ASTPos::new(1, 1, 0), ASTPos::new(1, 38, 37)));
+        assert_eq!(
+            headline_meta(node.meta()),
+            &HeadlineMeta {
+                level: 2,
+                todo_keyword: Some("TODO".to_string()),
+                todo_type: Some(TodoType::Todo),
+                priority: Some("A".to_string()),
+                tags: vec!["tag1".to_string(), "tag2".to_string()],
+                commented: false,
+                ..Default::default()
+            }
+        );
+        assert_eq!(node.render_debug_format(), "<headline><text>Title </text><strong><text>text</text></strong></headline>");
+    }
+
+    #[test]
+    fn test_parse_emphasis_markup() {
+        let src = "*bold* /italic/ _under_ =ver*ba*tim= ~code~ +strike+ *a*";
+        let parser = Parser::new(src);
+        let nodes: Vec<String> = parser.parse_objects(src).iter().map(|n| n.render_debug_format()).collect();
+        assert_eq!(
+            nodes.concat(),
+            "<strong><text>bold</text></strong><text> </text><emphasis><text>italic</text></emphasis><text> </text>\
+             <underline><text>under</text></underline><text> </text><verbatim>ver*ba*tim</verbatim><text> </text>\
+             <code>code</code><text> </text><strikethrough><text>strike</text></strikethrough><text> </text>\
+             <strong><text>a</text></strong>"
+        );
+        let node = parser.parse_emphasis_markup(Some(' '))(&src[16..]).unwrap().1;
+        assert_eq!(node.raw_value(), "_under_");
+        assert_eq!(node.range(), &ASTRange::new(ASTPos::new(1, 17, 16), ASTPos::new(1, 24, 23)));
+    }
+
+    #[test]
+    fn test_parse_emphasis_markup_constraints() {
+        let render = |src: &str| -> String {
+            let parser = Parser::new(src);
+            parser.parse_objects(src).iter().map(|n| n.render_debug_format()).collect()
+        };
+        // PRE, POST の制約
+        assert_eq!(render("a*b*c"), "<text>a*b*c</text>");
+        // 単語の途中の _ は下線ではない (^:t では下付き文字になる)
+        assert_eq!(
+            render("snake_case_name"),
+            "<text>snake</text><subscript><text>case</text></subscript><subscript><text>name</text></subscript>"
+        );
+        assert_eq!(render("(*bold*)."), "<text>(</text><strong><text>bold</text></strong><text>).</text>");
+        // 記号の内側に空白
+        assert_eq!(render("* not bold *"), "<text>* not bold *</text>");
+        // 改行は一つまで
+        assert_eq!(render("/a\nb/"), "<emphasis><text>a</text><softbreak /><text>b</text></emphasis>");
+        assert_eq!(render("/a\nb\nc/"), "<text>/a</text><softbreak /><text>b</text><softbreak /><text>c/</text>");
+        // 入れ子
+        assert_eq!(render("*/both/*"), "<strong><emphasis><text>both</text></emphasis></strong>");
+        assert_eq!(render("=*raw*="), "<verbatim>*raw*</verbatim>");
+    }
+
+    #[test]
+    fn test_parse_headline_unbounded_level() {
+        let src = "******** COMMENT deep\nnext";
+        let parser = Parser::new(src);
+        let (remain, node) = parser.parse_headline()(src).unwrap();
+        assert_eq!(remain, "next");
+        let meta = headline_meta(node.meta());
+        assert_eq!(meta.level, 8);
+        assert!(meta.commented);
+        assert_eq!(meta.todo_keyword, None);
+        assert_eq!(node.value(), "deep");
+
+        // 空の見出し、タグのみの見出し
+        let src = "*\n* :tag:";
+        let parser = Parser::new(src);
+        let (remain, node) = parser.parse_headline()(src).unwrap();
+        assert_eq!(node.value(), "");
+        let node = parser.parse_headline()(remain).unwrap().1;
+        assert_eq!(node.value(), "");
+        assert_eq!(headline_meta(node.meta()).tags, vec!["tag".to_string()]);
+
+        // '*' の後に空白が無いものは見出しではない
+        let parser = Parser::new("*bold*");
+        assert!(parser.parse_headline()("*bold*").is_err());
+    }
+
+    #[test]
+    fn test_todo_keywords() {
+        let keywords = TodoKeywords::from_source("#+TODO: TODO(t) NEXT | DONE(d!) CANCELED\n#+typ_todo: Alice Bob Done\n");
+        assert_eq!(keywords.todo, vec!["TODO", "NEXT", "Alice", "Bob"]);
+        assert_eq!(keywords.done, vec!["DONE", "CANCELED", "Done"]);
+        assert_eq!(TodoKeywords::from_source("no keywords"), TodoKeywords::default());
+    }
+
+    #[test]
+    fn test_parse_headline_custom_todo_keywords() {
+        let src = "#+TODO: NEXT | CANCELED\n* NEXT call\n* CANCELED party\n* TODO not a keyword";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><keyword>NEXT | CANCELED</keyword>\
+             <section><headline><text>call</text></headline></section>\
+             <section><headline><text>party</text></headline></section>\
+             <section><headline><text>TODO not a keyword</text></headline></section></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let src = "#+TITLE: doc\nparagraph\nmultiline\n\n* DONE headline\nbody";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><keyword>doc</keyword><paragraph><text>paragraph</text><softbreak /><text>multiline</text></paragraph>\
+             <section><headline><text>headline</text></headline><paragraph><text>body</text></paragraph></section></document>"
+        );
+        assert_eq!(node.root_node().range(), &ASTRange::new(ASTPos::new(1, 1, 0), ASTPos::new(6, 5, 54)));
+    }
+
+    #[test]
+    fn test_parse_many_blank_lines() {
+        let src = format!("first{}* second", "\n".repeat(100_000));
+        let node = org_parse(&src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>first</text></paragraph>\
+             <section><headline><text>second</text></headline></section></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let src = "* one
+** one-one
+text
+*** deep
+** one-two
+* two";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><headline><text>one</text></headline>\
+             <section><headline><text>one-one</text></headline><paragraph><text>text</text></paragraph>\
+             <section><headline><text>deep</text></headline></section></section>\
+             <section><headline><text>one-two</text></headline></section></section>\
+             <section><headline><text>two</text></headline></section></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_links() {
+        let render = |src: &str| -> String {
+            let parser = Parser::new(src);
+            parser.parse_objects(src).iter().map(|n| n.render_debug_format()).collect()
+        };
+        assert_eq!(
+            render("[[https://example.com][*the* site]] [[#id]]"),
+            "<link><strong><text>the</text></strong><text> site</text></link><text> </text><link>#id</link>"
+        );
+        assert_eq!(
+            render("see https://example.com/a_b_c. or <mailto:me@example.com>"),
+            "<text>see </text><link>https://example.com/a_b_c</link><text>. or </text><link>mailto:me@example.com</link>"
+        );
+        assert_eq!(render("nohttps://example.com"), "<text>nohttps://example.com</text>");
+        assert_eq!(render("[[a\\]b]]"), "<link>a]b</link>");
+        assert_eq!(render("a <<target>> b"), "<text>a </text><target>target</target><text> b</text>");
+
+        let src = "[[file:x.org::*Heading][desc]]";
+        let parser = Parser::new(src);
+        let node = parser.parse_bracket_link()(src).unwrap().1;
+        assert_eq!(node.range(), &ASTRange::new(ASTPos::new(1, 1, 0), ASTPos::new(1, 31, 30)));
+        match node.meta() {
+            ASTMetaData::Link(meta) => {
+                assert_eq!(meta.link_type, "file");
+                assert_eq!(meta.path, "x.org");
+                assert_eq!(meta.search_option, Some("*Heading".to_string()));
+                assert_eq!(meta.format, LinkFormat::Bracket);
+            }
+            _ => panic!("not a link"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_internal_links() {
+        let src = "* Intro\n:PROPERTIES:\n:CUSTOM_ID: intro\n:END:\nA <<point>> here.\n* Links\n[[#intro]] [[*Intro]] [[point]] [[Links]] [[missing]]";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+
+        fn collect_links(node: NodeRef, links: &mut Vec<LinkMeta>) {
+            if let ASTMetaData::Link(meta) = node.meta() {
+                links.push(meta.clone());
+            }
+            for child in node.children() {
+                collect_links(child, links);
+            }
+        }
+        let mut links = vec![];
+        collect_links(node.root_node(), &mut links);
+        let resolved: Vec<Option<(LinkResolutionKind, String, u32)>> = links
+            .iter()
+            .map(|l| l.resolved.as_ref().map(|r| (r.kind.clone(), r.name.clone(), r.range.begin.line())))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                Some((LinkResolutionKind::Headline, "Intro".to_string(), 1)),
+                Some((LinkResolutionKind::Headline, "Intro".to_string(), 1)),
+                Some((LinkResolutionKind::Target, "point".to_string(), 5)),
+                Some((LinkResolutionKind::Headline, "Links".to_string(), 6)),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_property_drawer() {
+        let src = "* H\n:PROPERTIES:\n:ID:       abc-123\n:Created: [2024-01-02]\n:END:\nbody";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><headline><text>H</text></headline>\
+             <properties><property>abc-123</property><property>[2024-01-02]</property></properties>\
+             <paragraph><text>body</text></paragraph></section></document>"
+        );
+        let section = node.root_node().first_child().unwrap();
+        let headline = section.first_child().unwrap();
+        match headline.meta() {
+            ASTMetaData::Headline(meta) => {
+                assert_eq!(meta.properties.get("ID"), Some(&"abc-123".to_string()));
+                assert_eq!(meta.properties.get("CREATED"), Some(&"[2024-01-02]".to_string()));
+            }
+            _ => panic!("not a headline"),
+        }
+    }
+
+    #[test]
+    fn test_parse_plain_list() {
+        let src = "- [ ] one\n  continued\n  + [@3] [X] nested\n\n- two\n\n\nafter";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><list><item><paragraph><text>one</text><softbreak /><text>continued</text></paragraph>\
+             <list><item><paragraph><text>nested</text></paragraph></item></list></item>\
+             <item><paragraph><text>two</text></paragraph></item></list>\
+             <paragraph><text>after</text></paragraph></document>"
+        );
+
+        let list = node.root_node().first_child().unwrap();
+        assert_eq!(list.meta(), &ASTMetaData::List { kind: ListKind::Unordered });
+        assert_eq!(list.range(), &ASTRange::new(ASTPos::new(1, 1, 0), ASTPos::new(5, 6, 48)));
+        let item = list.first_child().unwrap();
+        assert_eq!(
+            item.meta(),
+            &ASTMetaData::Item(ItemMeta { bullet: "-".to_string(), counter: None, checkbox: Some(Checkbox::Off) })
+        );
+        let nested = item.children().nth(1).unwrap().first_child().unwrap().meta().clone();
+        assert_eq!(
+            nested,
+            ASTMetaData::Item(ItemMeta { bullet: "+".to_string(), counter: Some(3), checkbox: Some(Checkbox::On) })
+        );
+    }
+
+    #[test]
+    fn test_item_continuation_indent() {
+        // 内容の桁 (4桁目) までの字下げを取り除く。それより深い字下げは残る
+        let src = "1. [X] one\n   two\n     three *four*\n   *five*";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><list><item><paragraph><text>one</text><softbreak /><text>two</text><softbreak />\
+             <text>  three </text><strong><text>four</text></strong><softbreak />\
+             <strong><text>five</text></strong></paragraph></item></list></document>"
+        );
+        let paragraph = node.root_node().descendants().find(|n| n.node_type() == &ASTType::Paragraph).unwrap();
+        let third = paragraph.children().nth(2).unwrap();
+        assert_eq!(third.range(), &ASTRange::new(ASTPos::new(2, 4, 14), ASTPos::new(2, 7, 17)));
+    }
+
+    #[test]
+    fn test_parse_many_blank_lines_in_contents() {
+        let src = format!("#+BEGIN_QUOTE\nfirst{}second\n#+END_QUOTE", "\n".repeat(100_000));
+        let node = org_parse(&src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><quote><paragraph><text>first</text></paragraph>\
+             <paragraph><text>second</text></paragraph></quote></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_plain_list_kinds() {
+        let kind = |src: &str| -> ASTMetaData {
+            let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+            let list = node.root_node().first_child().unwrap();
+            list.meta().clone()
+        };
+        assert_eq!(kind("1. one\n2) two"), ASTMetaData::List { kind: ListKind::Ordered });
+        assert_eq!(kind("- term :: desc"), ASTMetaData::List { kind: ListKind::Descriptive });
+        assert_eq!(kind("  * star"), ASTMetaData::List { kind: ListKind::Unordered });
+
+        let node = org_parse("- *term* :: desc\n- a::b", ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><list><item><tag><strong><text>term</text></strong></tag><paragraph><text>desc</text></paragraph></item>\
+             <item><paragraph><text>a::b</text></paragraph></item></list></document>"
+        );
+
+        // 行頭の '*' は見出し、記号の後に空白が無いものは項目ではない
+        let node = org_parse("text\n-not item\n* headline", ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>text</text><softbreak /><text>-not item</text></paragraph>\
+             <section><headline><text>headline</text></headline></section></document>"
+        );
+    }
+
+    #[test]
+    fn test_statistics_cookies() {
+        let src = "* Tasks [/]\n- [X] a [%]\n  - [X] a1\n  - [ ] a2\n  - [-] a3\n- [ ] b\n- no box\n\
+                   * Project [50%]\n** DONE one\n** TODO two\n** TODO three\n** note";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+
+        fn collect_cookies(node: NodeRef, cookies: &mut Vec<(String, StatisticsMeta)>) {
+            if let ASTMetaData::Statistics(meta) = node.meta() {
+                cookies.push((node.value().to_string(), meta.clone()));
+            }
+            for child in node.children() {
+                collect_cookies(child, cookies);
+            }
+        }
+        let mut cookies = vec![];
+        collect_cookies(node.root_node(), &mut cookies);
+        assert_eq!(
+            cookies,
+            vec![
+                ("[1/2]".to_string(), StatisticsMeta { percent: false, done: 1, total: 2 }),
+                ("[33%]".to_string(), StatisticsMeta { percent: true, done: 1, total: 3 }),
+                ("[33%]".to_string(), StatisticsMeta { percent: true, done: 1, total: 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_src_block() {
+        let src = "#+begin_src emacs-lisp -n -l \"(ref:%s)\" :results output silent :exports both\n\
+                   ,* not a headline\n  ,#+not a keyword\n(message \"hi\")\n#+END_SRC\nafter";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><src>* not a headline\n  #+not a keyword\n(message \"hi\")\n</src>\
+             <paragraph><text>after</text></paragraph></document>"
+        );
+        let block = node.root_node().first_child().unwrap();
+        assert_eq!(block.range(), &ASTRange::new(ASTPos::new(1, 1, 0), ASTPos::new(5, 10, 138)));
+        match block.meta() {
+            ASTMetaData::Block(meta) => {
+                assert_eq!(meta.name, "SRC");
+                assert_eq!(meta.language, Some("emacs-lisp".to_string()));
+                assert_eq!(meta.switches, vec!["-n".to_string(), "-l \"(ref:%s)\"".to_string()]);
+                assert_eq!(meta.header_args.get("results"), Some(&"output silent".to_string()));
+                assert_eq!(meta.header_args.get("exports"), Some(&"both".to_string()));
+            }
+            _ => panic!("not a block"),
+        }
+    }
+
+    #[test]
+    fn test_unescape_block_contents() {
+        assert_eq!(unescape_block_contents(",* a\n  ,,#+b\n,,,*c\n"), "* a\n  ,#+b\n,,*c\n");
+        // ',' に続くのが '*' か '#+' でなければそのまま
+        assert_eq!(unescape_block_contents(",,2]\n,foo\n, * x\n"), ",,2]\n,foo\n, * x\n");
+    }
+
+    #[test]
+    fn test_parse_greater_blocks() {
+        let src = "#+BEGIN_QUOTE\nquoted *text*\n- item\n#+END_QUOTE\n\
+                   #+begin_center\ncentered\n#+end_center\n\
+                   #+BEGIN_VERSE\n  line one\nline two\n#+END_VERSE\n\
+                   #+BEGIN_EXPORT html\n<b>raw</b>\n#+END_EXPORT\n\
+                   #+BEGIN_note\n#+BEGIN_EXAMPLE\n  a\n    b\n#+END_EXAMPLE\n#+END_note";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><quote><paragraph><text>quoted </text><strong><text>text</text></strong></paragraph>\
+             <list><item><paragraph><text>item</text></paragraph></item></list></quote>\
+             <center><paragraph><text>centered</text></paragraph></center>\
+             <verse><text>  line one</text><softbreak /><text>line two</text></verse>\
+             <export><b>raw</b>\n</export>\
+             <special><example>a\n  b\n</example></special></document>"
+        );
+        let blocks: Vec<BlockMeta> = node
+            .root_node()
+            .children()
+            .map(|n| match n.meta() {
+                ASTMetaData::Block(meta) => meta.clone(),
+                _ => panic!("not a block"),
+            })
+            .collect();
+        assert_eq!(blocks[3].language, Some("html".to_string()));
+        assert_eq!(blocks[4].name, "NOTE");
+
+        // 終わりの無いブロックは段落
+        let node = org_parse("#+BEGIN_QUOTE\ntext", ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>#+BEGIN</text><subscript><text>QUOTE</text></subscript><softbreak />\
+             <text>text</text></paragraph></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_drawers() {
+        let src = "* DONE H\n:PROPERTIES:\n:ID: x1\n:VAR: a=1\n:var+: b=2\n:END:\n:LOGBOOK:\n\
+                   - State \"DONE\"       from \"TODO\"       [2024-01-02 Tue 10:00]\n\
+                   CLOCK: [2024-01-02 Tue 09:00]--[2024-01-02 Tue 10:00] =>  1:00\n:END:\n\
+                   :notes:\nsome *text*\n:end:\n:NOEND:\nbody";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><headline><text>H</text></headline>\
+             <properties><property>x1</property><property>a=1</property><property>b=2</property></properties>\
+             <drawer><list><item><paragraph><text>State \"DONE\"       from \"TODO\"       </text>\
+             <timestamp>[2024-01-02 Tue 10:00]</timestamp></paragraph></item></list>\
+             <clock>[2024-01-02 Tue 09:00]--[2024-01-02 Tue 10:00] =>  1:00</clock></drawer>\
+             <drawer><paragraph><text>some </text><strong><text>text</text></strong></paragraph></drawer>\
+             <paragraph><text>:NOEND:</text><softbreak /><text>body</text></paragraph></section></document>"
+        );
+
+        let section = node.root_node().first_child().unwrap();
+        let drawers: Vec<ASTMetaData> = section.children().skip(2).take(2).map(|n| n.meta().clone()).collect();
+        assert_eq!(
+            drawers,
+            vec![
+                ASTMetaData::Drawer { name: "LOGBOOK".to_string() },
+                ASTMetaData::Drawer { name: "notes".to_string() }
+            ]
+        );
+        let headline = section.first_child().unwrap();
+        match headline.meta() {
+            ASTMetaData::Headline(meta) => {
+                assert_eq!(meta.properties.get("ID"), Some(&"x1".to_string()));
+                assert_eq!(meta.properties.get("VAR"), Some(&"a=1 b=2".to_string()));
+            }
+            _ => panic!("not a headline"),
+        }
+    }
+
+    #[test]
+    fn test_parse_planning_and_timestamps() {
+        let src = "* TODO Task\n  DEADLINE: <2024-01-05 Fri -1d> SCHEDULED: <2024-01-02 Tue 10:00 +1w>\n\
+                   :PROPERTIES:\n:ID: t1\n:END:\nMeet <2024-01-03 Wed>--<2024-01-04 Thu> or [2024-01-02].\n\
+                   SCHEDULED: <2024-01-09 Tue>";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><headline><text>Task</text></headline>\
+             <planning><timestamp><2024-01-05 Fri -1d></timestamp><timestamp><2024-01-02 Tue 10:00 +1w></timestamp></planning>\
+             <properties><property>t1</property></properties>\
+             <paragraph><text>Meet </text><timestamp><2024-01-03 Wed>--<2024-01-04 Thu></timestamp><text> or </text>\
+             <timestamp>[2024-01-02]</timestamp><text>.</text><softbreak />\
+             <text>SCHEDULED: </text><timestamp><2024-01-09 Tue></timestamp></paragraph></section></document>"
+        );
+
+        let section = node.root_node().first_child().unwrap();
+        let headline = section.first_child().unwrap();
+        let meta = headline_meta(headline.meta());
+        assert_eq!(meta.properties.get("ID"), Some(&"t1".to_string()));
+        let scheduled = meta.planning.scheduled.as_ref().unwrap();
+        assert_eq!(scheduled.date, OrgDate { year: 2024, month: 1, day: 2 });
+        assert_eq!(scheduled.time, Some(OrgTime { hour: 10, minute: 0 }));
+        assert_eq!(scheduled.repeater.map(|r| (r.value, r.unit)), Some((1, TimeUnit::Week)));
+        let deadline = meta.planning.deadline.as_ref().unwrap();
+        assert_eq!(deadline.delay.map(|d| (d.value, d.unit)), Some((1, TimeUnit::Day)));
+        assert_eq!(meta.planning.closed, None);
+
+        let planning = section.children().nth(1).unwrap();
+        assert_eq!(planning.range(), &ASTRange::new(ASTPos::new(2, 1, 12), ASTPos::new(2, 71, 82)));
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let src = "| Item | *Cost* | x2 |\n|------+--------+----|\n| / | <> | |\n| <l10> | <r> | |\n\
+                   | a | 2 | |\n| b | 3.5 | |\n| Sum | | |\n#+TBLFM: @>$2=vsum(@I..@-1)\n#+tblfm: $3=$2*2";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let table = node.root_node().first_child().unwrap();
+        assert_eq!(table.node_type(), &ASTType::Table);
+        let rows: Vec<(TableRowKind, Vec<String>)> = table
+            .children()
+            .map(|row| match row.meta() {
+                ASTMetaData::TableRow { kind } => {
+                    (kind.clone(), row.children().map(|c| c.value().clone()).collect())
+                }
+                _ => panic!("not a row"),
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (TableRowKind::Standard, vec!["Item".to_string(), "*Cost*".to_string(), "x2".to_string()]),
+                (TableRowKind::Rule, vec![]),
+                (TableRowKind::Special, vec!["/".to_string(), "<>".to_string(), "".to_string()]),
+                (TableRowKind::Special, vec!["<l10>".to_string(), "<r>".to_string(), "".to_string()]),
+                (TableRowKind::Standard, vec!["a".to_string(), "2".to_string(), "4".to_string()]),
+                (TableRowKind::Standard, vec!["b".to_string(), "3.5".to_string(), "7".to_string()]),
+                (TableRowKind::Standard, vec!["Sum".to_string(), "5.5".to_string(), "0".to_string()]),
+            ]
+        );
+        match table.meta() {
+            ASTMetaData::Table(meta) => {
+                assert_eq!(meta.formulas, vec!["@>$2=vsum(@I..@-1)".to_string(), "$3=$2*2".to_string()]);
+                assert_eq!(
+                    meta.columns,
+                    vec![
+                        TableColumn { width: Some(10), align: Some(Alignment::Left), ..Default::default() },
+                        TableColumn { width: None, align: Some(Alignment::Right), group_start: true, group_end: true },
+                        TableColumn::default(),
+                    ]
+                );
+            }
+            _ => panic!("not a table"),
+        }
+        assert_eq!(
+            table.first_child().unwrap().render_debug_format(),
+            "<row><cell><text>Item</text></cell><cell><strong><text>Cost</text></strong></cell><cell><text>x2</text></cell></row>"
+        );
+
+        let src = "| a | 1 |\n| b | 2 |\n|---|\n| sum | |\n#+TBLFM: @>$2=vsum(@1..@-1)";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><table><row><cell><text>a</text></cell><cell><text>1</text></cell></row>\
+             <row><cell><text>b</text></cell><cell><text>2</text></cell></row><row></row>\
+             <row><cell><text>sum</text></cell><cell><text>3</text></cell></row></table></document>"
+        );
+    }
+
+    #[test]
+    fn test_parse_footnotes_and_radio_targets() {
+        let src = "See <<<Radio Link>>> here[fn:1] and[fn::inline *b*].\nradio link again, radio linker no.[fn:1]\n\n\
+                   [fn:1] The note.\nmore\n\n\nAfter <<tgt>> [fn:x:def]";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>See </text><radio>Radio Link</radio><text> here</text><fnref>1</fnref>\
+             <text> and</text><fnref><text>inline </text><strong><text>b</text></strong></fnref><text>.</text>\
+             <softbreak /><link><text>radio link</text></link><text> again, radio linker no.</text><fnref>1</fnref>\
+             </paragraph><fndef><paragraph><text>The note.</text><softbreak /><text>more</text></paragraph></fndef>\
+             <paragraph><text>After </text><target>tgt</target><text> </text><fnref><text>def</text></fnref>\
+             </paragraph></document>"
+        );
+
+        let paragraph = node.root_node().first_child().unwrap();
+        let link = paragraph.children().find(|c| c.node_type() == &ASTType::Link).unwrap();
+        assert_eq!((link.range().begin.line(), link.range().begin.pos(), link.range().end.pos()), (2, 53, 63));
+        match link.meta() {
+            ASTMetaData::Link(meta) => {
+                assert_eq!((meta.link_type.as_str(), meta.path.as_str()), ("radio", "Radio Link"));
+                assert_eq!(meta.resolved.as_ref().map(|r| r.kind.clone()), Some(LinkResolutionKind::RadioTarget));
+            }
+            _ => panic!("not a link"),
+        }
+    }
+
+    #[test]
+    fn test_parse_latex_and_entities() {
+        let src = "Euler \\(e^{i\\pi}\\) and $x$, $5 and $10, $a+b$.\nA \\alpha{}b \\rarr \\alphabet \\cite[p.~3]{k} x$\n\n\
+                   \\begin{equation*}\n  x = 1\n\\end{equation*}";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>Euler </text><latex>e^{i\\pi}</latex><text> and </text><latex>x</latex>\
+             <text>, $5 and $10, </text><latex>a+b</latex><text>.</text><softbreak /><text>A </text><entity>α</entity>\
+             <text>b </text><entity>→</entity><text> </text><latex>\\alphabet</latex><text> </text>\
+             <latex>\\cite[p.~3]{k}</latex><text> x$</text></paragraph>\
+             <latexenv>\\begin{equation*}\n  x = 1\n\\end{equation*}</latexenv></document>"
+        );
+
+        let paragraph = node.root_node().first_child().unwrap();
+        let alpha = paragraph.children().find(|c| c.node_type() == &ASTType::Entity).unwrap();
+        let range = alpha.range();
+        assert_eq!((alpha.raw_value().as_str(), range.begin.pos(), range.end.pos()), ("\\alpha{}", 49, 57));
+        match alpha.meta() {
+            ASTMetaData::Entity(meta) => assert_eq!((meta.html.as_str(), meta.latex.as_str()), ("&alpha;", "\\alpha")),
+            _ => panic!("not an entity"),
+        }
+        let fragment = paragraph.children().find(|c| c.node_type() == &ASTType::LatexFragment).unwrap();
+        assert_eq!(
+            fragment.meta(),
+            &ASTMetaData::Latex(LatexMeta { delimiter: LatexDelimiter::Paren, display: false, environment: None })
+        );
+        let environment = node.root_node().children().nth(1).unwrap();
+        match environment.meta() {
+            ASTMetaData::Latex(meta) => assert_eq!(meta.environment.as_deref(), Some("equation*")),
+            _ => panic!("not a latex environment"),
+        }
+    }
+
+    #[test]
+    fn test_parse_sub_superscripts() {
+        let src = "a_{i} x^2 H_2O x^* f_{a_b} x^-1, y^{\\alpha} _x a^";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>a</text><subscript><text>i</text></subscript><text> x</text>\
+             <superscript><text>2</text></superscript><text> H</text><subscript><text>2O</text></subscript>\
+             <text> x</text><superscript><text>*</text></superscript><text> f</text><subscript><text>a</text>\
+             <subscript><text>b</text></subscript></subscript><text> x</text><superscript><text>-1</text></superscript>\
+             <text>, y</text><superscript><entity>α</entity></superscript><text> _x a^</text></paragraph></document>"
+        );
+
+        let node = org_parse("#+OPTIONS: ^:{}\na_b a_{b}", ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><keyword>^:{}</keyword><paragraph><text>a_b a</text><subscript><text>b</text></subscript>\
+             </paragraph></document>"
+        );
+        let node = org_parse("#+OPTIONS: ^:nil\na_b a_{b}", ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><keyword>^:nil</keyword><paragraph><text>a_b a_{b}</text></paragraph></document>"
+        );
+    }
+
+    #[test]
+    fn test_expand_macros() {
+        let src = "#+TITLE: Doc *T*\n#+DATE: <2024-01-02 Tue>\n#+MACRO: hello Hello, $1!\n\
+                   {{{title}}} {{{date(%Y)}}} {{{hello(a\\, b)}}} {{{n}}} {{{n}}} {{{nope}}}\n\
+                   * H\n:PROPERTIES:\n:X: px\n:END:\n{{{property(X)}}}";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let paragraph = node.root_node().children().nth(3).unwrap();
+        assert_eq!(
+            paragraph.render_debug_format(),
+            "<paragraph><macro><text>Doc </text><strong><text>T</text></strong></macro><text> </text>\
+             <macro><text>2024</text></macro><text> </text><macro><text>Hello, a, b!</text></macro><text> </text>\
+             <macro><text>1</text></macro><text> </text><macro><text>2</text></macro><text> </text><macro></macro>\
+             </paragraph>"
+        );
+        // 展開した内容の位置はマクロの位置
+        let hello = paragraph.children().nth(4).unwrap();
+        assert_eq!(hello.value(), "Hello, a, b!");
+        let text = hello.first_child().unwrap();
+        assert_eq!(text.range(), hello.range());
+        assert_eq!((text.range().begin.line(), text.range().begin.ch()), (4, 28));
+
+        let section = node.root_node().children().nth(4).unwrap();
+        assert_eq!(
+            section.children().nth(2).unwrap().render_debug_format(),
+            "<paragraph><macro><text>px</text></macro></paragraph>"
+        );
+    }
+
+    #[test]
+    fn test_resolve_includes() {
+        let mut files = std::collections::BTreeMap::new();
+        let a = "* Included\nbody\n#+INCLUDE: \"b.py\" src python :lines \"2-3\"\n";
+        files.insert("a.org".to_string(), a.to_string());
+        files.insert("b.py".to_string(), "l1\nl2\nl3\nl4\n".to_string());
+        files.insert("loop.org".to_string(), "#+INCLUDE: \"loop.org\"".to_string());
+        let src = "* H\n#+INCLUDE: \"a.org\" :minlevel 3\n#+INCLUDE: \"loop.org\"\n#+INCLUDE: \"none.org\"";
+        let node = org_parse_with_loader(src, ASTNode::new(ASTElm::new_document()), &files);
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><headline><text>H</text></headline><include><section><headline><text>Included</text>\
+             </headline><paragraph><text>body</text></paragraph><include><src>l2\nl3\n</src></include></section>\
+             </include><include><include>loop.org</include></include><include>none.org</include></section></document>"
+        );
+
+        let section = node.root_node().first_child().unwrap();
+        let include = section.children().nth(1).unwrap();
+        // 読み込んだ内容の位置は読み込んだファイルの中の位置
+        let included = include.first_child().unwrap();
+        let headline = included.first_child().unwrap();
+        assert_eq!((include.range().begin.line(), headline.range().begin.line()), (2, 1));
+        assert_eq!(headline_meta(headline.meta()).level, 3);
+        let nested = included.children().nth(2).unwrap();
+        assert_eq!(nested.first_child().unwrap().range().begin.line(), 2);
+        // どのファイルの位置かは source_file で分かる
+        let files: Vec<Option<&str>> = vec![
+            include.source_file().map(String::as_str),
+            headline.first_child().unwrap().source_file().map(String::as_str),
+            nested.source_file().map(String::as_str),
+            nested.first_child().unwrap().source_file().map(String::as_str),
+        ];
+        assert_eq!(files, vec![None, Some("a.org"), Some("a.org"), Some("b.py")]);
+
+        let errors: Vec<Option<String>> = section
+            .children()
+            .skip(2)
+            .map(|include| match include.meta() {
+                ASTMetaData::Include(meta) => meta.error.clone().or_else(|| {
+                    include.first_child().and_then(|inner| match inner.meta() {
+                        ASTMetaData::Include(meta) => meta.error.clone(),
+                        _ => None,
+                    })
+                }),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![Some("loop.org: recursive include".to_string()), Some("none.org: not found".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_expand_noweb() {
+        let src = "#+NAME: init\n#+BEGIN_SRC python\nx = 1\n#+END_SRC\n\
+                   #+BEGIN_SRC python :noweb-ref body\ny = 2\n#+END_SRC\n\
+                   #+BEGIN_SRC python :noweb yes\ndef f():\n    <<init>>\n    <<body>>\n#+END_SRC\n\
+                   #+BEGIN_SRC python :noweb strip-export\n<<init>>\n#+END_SRC";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        let expanded: Vec<Option<String>> = node
+            .root_node()
+            .children()
+            .filter_map(|c| match c.meta() {
+                ASTMetaData::Block(meta) => Some(meta.expanded.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            expanded,
+            vec![None, None, Some("def f():\n    x = 1\n    y = 2\n".to_string()), Some("\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_babel() {
+        let src = "#+NAME: double\n#+HEADER: :var n=2\n#+BEGIN_SRC python\nreturn n * 2\n#+END_SRC\n\
+                   \n\
+                   #+RESULTS[8f3a]:\n: 4\n\
+                   \n\
+                   #+CALL: double[:session s](n=4) :results silent\n\
+                   \n\
+                   #+RESULTS: double\n| 8 |\n\
+                   \n\
+                   Inline src_python[:results raw]{{1: 2}[1]} and call_double(n=1)[:results raw].";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><src>return n * 2\n</src><fixedwidth>4</fixedwidth>\
+             <call>double[:session s](n=4) :results silent</call><table><row><cell><text>8</text></cell></row></table>\
+             <paragraph><text>Inline </text><inlinesrc>{1: 2}[1]</inlinesrc><text> and </text>\
+             <inlinecall>call_double(n=1)[:results raw]</inlinecall><text>.</text></paragraph></document>"
+        );
+
+        let children: Vec<_> = node.root_node().children().collect();
+        let src_block = children[0].affiliated().unwrap();
+        assert_eq!(src_block.name.as_deref(), Some("double"));
+        assert_eq!(src_block.headers, vec![":var n=2".to_string()]);
+        let results = children[1].affiliated().unwrap().results.clone().unwrap();
+        assert_eq!((results.hash.as_deref(), results.source.as_ref()), (Some("8f3a"), Some(children[0].range())));
+        match children[2].meta() {
+            ASTMetaData::BabelCall(meta) => {
+                assert_eq!((meta.name.as_str(), meta.inside_header.as_deref()), ("double", Some(":session s")));
+                assert_eq!((meta.arguments.as_str(), meta.end_header.as_deref()), ("n=4", Some(":results silent")));
+            }
+            _ => panic!("not a babel call"),
+        }
+        let results = children[3].affiliated().unwrap().results.clone().unwrap();
+        assert_eq!((results.name.as_deref(), results.source.as_ref()), (Some("double"), Some(children[0].range())));
+
+        let paragraph = &children[4];
+        let inline: Vec<ASTMetaData> =
+            paragraph.children().skip(1).step_by(2).map(|c| c.meta().clone()).collect();
+        match &inline[0] {
+            ASTMetaData::Block(meta) => {
+                assert_eq!(meta.language.as_deref(), Some("python"));
+                assert_eq!(meta.header_args.get("results").map(String::as_str), Some("raw"));
+            }
+            _ => panic!("not an inline src block"),
+        }
+        match &inline[1] {
+            ASTMetaData::BabelCall(meta) => assert_eq!(meta.end_header.as_deref(), Some(":results raw")),
+            _ => panic!("not an inline babel call"),
+        }
+    }
+
+    #[test]
+    fn test_parse_fixed_width_and_comments() {
+        let src = ": fixed\n  :   indented\n:\n\
+                   # comment\n#\n#not a comment\n\
+                   #+begin_comment\n*not bold*\n#+end_comment\n\
+                   -----\n----\n\
+                   Line one\\\\  \nline two";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><fixedwidth>fixed\n  indented\n</fixedwidth><comment>comment\n</comment>\
+             <paragraph><text>#not a comment</text></paragraph><commentblock>*not bold*\n</commentblock><hr />\
+             <paragraph><text>----</text><softbreak /><text>Line one</text><hardbreak /><text>line two</text>\
+             </paragraph></document>"
+        );
+        let line_break = node.root_node().last_child().unwrap().children().nth(3).unwrap().raw_value().clone();
+        assert_eq!(line_break, "\\\\  \n");
+    }
+
+    #[test]
+    fn test_parse_inlinetasks_and_dynamic_blocks() {
+        let src = "* Headline\n\
+                   *************** TODO Task\n\
+                   SCHEDULED: <2024-01-02 Tue>\n\
+                   :PROPERTIES:\n:EFFORT: 1:00\n:END:\n\
+                   Details\n\
+                   *************** END\n\
+                   Text\n\
+                   *************** One line task\n\
+                   ** Sub\n\
+                   #+BEGIN: clocktable :maxlevel 2 :scope file\n\
+                   | Headline | Time |\n\
+                   #+END:";
+        let node = org_parse(src, ASTNode::new(ASTElm::new_document()));
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><headline><text>Headline</text></headline>\
+             <inlinetask><headline><text>Task</text></headline><planning><timestamp><2024-01-02 Tue></timestamp>\
+             </planning><properties><property>1:00</property></properties><paragraph><text>Details</text>\
+             </paragraph></inlinetask><paragraph><text>Text</text></paragraph>\
+             <inlinetask><headline><text>One line task</text></headline></inlinetask>\
+             <section><headline><text>Sub</text></headline><dynamic><table><row><cell><text>Headline</text></cell>\
+             <cell><text>Time</text></cell></row></table></dynamic></section></section></document>"
+        );
+
+        let section = node.root_node().first_child().unwrap();
+        let inlinetask = section.children().nth(1).unwrap();
+        assert_eq!((inlinetask.range().begin.line(), inlinetask.range().end.line()), (2, 8));
+        let headline = inlinetask.first_child().unwrap();
+        match headline.meta() {
+            ASTMetaData::Headline(meta) => {
+                assert_eq!((meta.level, meta.todo_keyword.as_deref()), (15, Some("TODO")));
+                assert_eq!(meta.properties.get("EFFORT").map(String::as_str), Some("1:00"));
+                assert!(meta.planning.scheduled.is_some());
+            }
+            _ => panic!("not a headline"),
+        }
+
+        let sub = section.children().nth(4).unwrap();
+        let dynamic = sub.children().nth(1).unwrap();
+        match dynamic.meta() {
+            ASTMetaData::Block(meta) => {
+                assert_eq!((meta.name.as_str(), meta.parameters.as_str()), ("clocktable", ":maxlevel 2 :scope file"));
+                assert_eq!(meta.header_args.get("maxlevel").map(String::as_str), Some("2"));
+            }
+            _ => panic!("not a dynamic block"),
+        }
+    }
+}
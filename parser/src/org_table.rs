@@ -0,0 +1,630 @@
+/*
+ * 表の計算式 (#+TBLFM:)
+ *
+ * 対応する範囲
+ *   参照   $3 $-1 $< $>  @2 @-1 @< @> @I @II  @2$3
+ *   範囲   @2$1..@4$3  $2..$4  @2..@-1  @I..@II
+ *   関数   vsum vmean vmin vmax vcount
+ *   演算   + - * / 括弧 単項マイナス
+ *   書式   ;%.2f ;%d  N (数値でない欄を0とする)  E (範囲の空欄を0として含める)
+ *
+ * 列の式($4=...)は最初の横線より下の行に適用し、その後で欄の式(@5$4=...)を適用する。
+ * 行番号は横線を除いた行で数える。範囲に含まれる幅指定・列グループの行は無視する。
+ * 計算できない場合は欄を #ERROR にする。
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableLine {
+    Rule,
+    Row { cells: Vec<String>, special: bool }, // special: 幅指定・列グループの行 (式の対象にしない)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    pub target: FormulaTarget,
+    pub expr: String,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormulaTarget {
+    Column(ColRef),
+    Field(RowRef, ColRef),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RowRef {
+    Abs(i64),
+    Rel(i64),
+    First,
+    Last,
+    Hline(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColRef {
+    Abs(i64),
+    Rel(i64),
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(f64),
+    Ref(Option<RowRef>, Option<ColRef>),
+    Range((Option<RowRef>, Option<ColRef>), (Option<RowRef>, Option<ColRef>)),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+enum Value {
+    Num(f64),
+    List(Vec<f64>),
+}
+
+// $4=$2*$3::@5$4=vsum(@2..@4);%.2f
+pub fn parse_formulas(s: &str) -> Vec<Formula> {
+    s.split("::").filter_map(|f| parse_formula(f.trim())).collect()
+}
+
+fn parse_formula(s: &str) -> Option<Formula> {
+    let (lhs, rhs) = s.split_once('=')?;
+    let mut cursor = Cursor::new(lhs.trim());
+    let target = match cursor.parse_reference()? {
+        (None, Some(col)) => FormulaTarget::Column(col),
+        (Some(row), Some(col)) => FormulaTarget::Field(row, col),
+        _ => return None,
+    };
+    if !cursor.at_end() {
+        return None;
+    }
+    let (expr, format) = match rhs.rsplit_once(';') {
+        Some((expr, format)) => (expr, Some(format.trim().to_string())),
+        None => (rhs, None),
+    };
+    Some(Formula { target, expr: expr.trim().to_string(), format })
+}
+
+// 式を適用して表の値を書き換える
+pub fn recalculate(lines: &mut [TableLine], formulas: &[Formula]) {
+    let mut table = Table::new(lines);
+    let fields: Vec<(usize, usize)> = formulas
+        .iter()
+        .filter_map(|f| match f.target {
+            FormulaTarget::Field(row, col) => Some((table.resolve_row(row, 1).ok()?, table.resolve_col(col, 1).ok()?)),
+            _ => None,
+        })
+        .collect();
+
+    let column_formulas = formulas.iter().filter_map(|f| match f.target {
+        FormulaTarget::Column(col) => Some((f, col)),
+        _ => None,
+    });
+    for (formula, col) in column_formulas {
+        let first_row = table.hlines.first().map(|h| h + 1).unwrap_or(1);
+        for row in first_row..=table.rows.len() {
+            let col = match table.resolve_col(col, 1) {
+                Ok(col) => col,
+                Err(_) => break,
+            };
+            if table.is_special(row) || fields.contains(&(row, col)) {
+                continue;
+            }
+            let value = table.evaluate(formula, row, col);
+            table.set(row, col, value);
+        }
+    }
+    for formula in formulas {
+        if let FormulaTarget::Field(row, col) = formula.target {
+            if let (Ok(row), Ok(col)) = (table.resolve_row(row, 1), table.resolve_col(col, 1)) {
+                let value = table.evaluate(formula, row, col);
+                table.set(row, col, value);
+            }
+        }
+    }
+    table.write_back(lines);
+}
+
+struct Table {
+    rows: Vec<(Vec<String>, bool)>, // 横線を除いた行 (欄, special)
+    hlines: Vec<usize>,             // 各横線より上にある行の数
+}
+
+impl Table {
+    fn new(lines: &[TableLine]) -> Self {
+        let mut table = Table { rows: vec![], hlines: vec![] };
+        for line in lines {
+            match line {
+                TableLine::Rule => table.hlines.push(table.rows.len()),
+                TableLine::Row { cells, special } => table.rows.push((cells.clone(), *special)),
+            }
+        }
+        table
+    }
+
+    fn write_back(self, lines: &mut [TableLine]) {
+        let mut rows = self.rows.into_iter();
+        for line in lines.iter_mut() {
+            if let TableLine::Row { cells, .. } = line {
+                if let Some((new_cells, _)) = rows.next() {
+                    *cells = new_cells;
+                }
+            }
+        }
+    }
+
+    fn columns(&self) -> usize {
+        self.rows.iter().map(|(cells, _)| cells.len()).max().unwrap_or(0)
+    }
+
+    fn is_special(&self, row: usize) -> bool {
+        self.rows[row - 1].1
+    }
+
+    fn get(&self, row: usize, col: usize) -> &str {
+        self.rows[row - 1].0.get(col - 1).map(|c| c.as_str()).unwrap_or("")
+    }
+
+    fn set(&mut self, row: usize, col: usize, value: String) {
+        let cells = &mut self.rows[row - 1].0;
+        if cells.len() < col {
+            cells.resize(col, String::new());
+        }
+        cells[col - 1] = value;
+    }
+
+    // 行番号 (1から)
+    fn resolve_row(&self, row: RowRef, current: usize) -> Result<usize, String> {
+        let row = match row {
+            RowRef::Abs(n) => n,
+            RowRef::Rel(n) => (current as i64).checked_add(n).ok_or("row out of range")?,
+            RowRef::First => 1,
+            RowRef::Last => self.rows.len() as i64,
+            RowRef::Hline(n) => self.hlines.get(n - 1).ok_or("no such hline")?.to_owned() as i64 + 1,
+        };
+        if row < 1 || row > self.rows.len() as i64 {
+            return Err(format!("row {} out of range", row));
+        }
+        Ok(row as usize)
+    }
+
+    // 範囲の終わりの横線は、その横線の直前の行を指す
+    fn resolve_range_end_row(&self, row: RowRef, current: usize) -> Result<usize, String> {
+        match row {
+            RowRef::Hline(n) => {
+                let before = *self.hlines.get(n - 1).ok_or("no such hline")?;
+                if before == 0 {
+                    return Err("empty range".to_string());
+                }
+                Ok(before)
+            }
+            _ => self.resolve_row(row, current),
+        }
+    }
+
+    fn resolve_col(&self, col: ColRef, current: usize) -> Result<usize, String> {
+        let col = match col {
+            ColRef::Abs(n) => n,
+            ColRef::Rel(n) => (current as i64).checked_add(n).ok_or("column out of range")?,
+            ColRef::First => 1,
+            ColRef::Last => self.columns() as i64,
+        };
+        if col < 1 || col > self.columns() as i64 {
+            return Err(format!("column {} out of range", col));
+        }
+        Ok(col as usize)
+    }
+
+    fn evaluate(&self, formula: &Formula, row: usize, col: usize) -> String {
+        let format = Format::parse(formula.format.as_deref().unwrap_or(""));
+        let mut cursor = Cursor::new(&formula.expr);
+        let result = cursor
+            .parse_expr()
+            .and_then(|expr| if cursor.at_end() { Some(expr) } else { None })
+            .ok_or_else(|| "syntax error".to_string())
+            .and_then(|expr| self.eval(&expr, row, col, &format));
+        match result {
+            Ok(Value::Num(n)) => format.render(n),
+            _ => "#ERROR".to_string(),
+        }
+    }
+
+    fn eval(&self, expr: &Expr, row: usize, col: usize, format: &Format) -> Result<Value, String> {
+        match expr {
+            Expr::Num(n) => Ok(Value::Num(*n)),
+            Expr::Ref(r, c) => {
+                let r = r.map(|r| self.resolve_row(r, row)).unwrap_or(Ok(row))?;
+                let c = c.map(|c| self.resolve_col(c, col)).unwrap_or(Ok(col))?;
+                let text = self.get(r, c).trim();
+                if text.is_empty() {
+                    return Ok(Value::Num(0.0));
+                }
+                format.number(text).map(Value::Num)
+            }
+            Expr::Range((r1, c1), (r2, c2)) => {
+                let r1 = r1.map(|r| self.resolve_row(r, row)).unwrap_or(Ok(row))?;
+                let r2 = r2.map(|r| self.resolve_range_end_row(r, row)).unwrap_or(Ok(row))?;
+                let c1 = c1.map(|c| self.resolve_col(c, col)).unwrap_or(Ok(col))?;
+                let c2 = c2.map(|c| self.resolve_col(c, col)).unwrap_or(Ok(col))?;
+                let mut values = vec![];
+                for r in (r1.min(r2)..=r1.max(r2)).filter(|r| !self.is_special(*r)) {
+                    for c in c1.min(c2)..=c1.max(c2) {
+                        let text = self.get(r, c).trim();
+                        if text.is_empty() {
+                            if format.keep_empty {
+                                values.push(0.0);
+                            }
+                            continue;
+                        }
+                        values.push(format.number(text)?);
+                    }
+                }
+                Ok(Value::List(values))
+            }
+            Expr::Neg(e) => Ok(Value::Num(-self.eval_num(e, row, col, format)?)),
+            Expr::Binary(op, a, b) => {
+                let a = self.eval_num(a, row, col, format)?;
+                let b = self.eval_num(b, row, col, format)?;
+                Ok(Value::Num(match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ if b == 0.0 => return Err("division by zero".to_string()),
+                    _ => a / b,
+                }))
+            }
+            Expr::Call(name, args) => {
+                let mut values = vec![];
+                for arg in args {
+                    match self.eval(arg, row, col, format)? {
+                        Value::Num(n) => values.push(n),
+                        Value::List(list) => values.extend(list),
+                    }
+                }
+                let result = match name.as_str() {
+                    "vsum" => values.iter().sum(),
+                    "vmean" if !values.is_empty() => values.iter().sum::<f64>() / values.len() as f64,
+                    "vmin" if !values.is_empty() => values.iter().cloned().fold(f64::INFINITY, f64::min),
+                    "vmax" if !values.is_empty() => values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                    "vcount" => values.len() as f64,
+                    _ => return Err(format!("cannot evaluate {}", name)),
+                };
+                Ok(Value::Num(result))
+            }
+        }
+    }
+
+    fn eval_num(&self, expr: &Expr, row: usize, col: usize, format: &Format) -> Result<f64, String> {
+        match self.eval(expr, row, col, format)? {
+            Value::Num(n) => Ok(n),
+            Value::List(_) => Err("range in arithmetic".to_string()),
+        }
+    }
+}
+
+// ;%.2f ;N ;E
+struct Format {
+    precision: Option<usize>,
+    integer: bool,
+    non_numeric_as_zero: bool,
+    keep_empty: bool,
+}
+
+impl Format {
+    fn parse(s: &str) -> Self {
+        let mut format = Format { precision: None, integer: false, non_numeric_as_zero: false, keep_empty: false };
+        let (flags, printf) = match s.find('%') {
+            Some(idx) => (&s[..idx], &s[idx..]),
+            None => (s, ""),
+        };
+        format.non_numeric_as_zero = flags.contains('N');
+        format.keep_empty = flags.contains('E');
+        if printf == "%d" {
+            format.integer = true;
+        } else if let Some(precision) = printf.strip_prefix("%.").and_then(|p| p.strip_suffix('f')) {
+            format.precision = precision.parse().ok();
+        }
+        format
+    }
+
+    fn number(&self, text: &str) -> Result<f64, String> {
+        match text.parse::<f64>() {
+            Ok(n) => Ok(n),
+            Err(_) if self.non_numeric_as_zero => Ok(0.0),
+            Err(_) => Err(format!("not a number: {}", text)),
+        }
+    }
+
+    fn render(&self, n: f64) -> String {
+        if self.integer {
+            format!("{}", n.round() as i64)
+        } else if let Some(precision) = self.precision {
+            format!("{:.*}", precision, n)
+        } else if n.fract() == 0.0 && n.abs() < 1e15 {
+            format!("{}", n as i64)
+        } else {
+            // 有効数字12桁
+            let digits = (11 - n.abs().log10().floor() as i64).max(0) as usize;
+            let s = format!("{:.*}", digits, n);
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+    }
+}
+
+// 式の字句・構文解析
+struct Cursor<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(s: &'a str) -> Self {
+        Cursor { s, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_space();
+        self.pos >= self.s.len()
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c: char| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn parse_expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.parse_term()?;
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some(op) if op == '+' || op == '-' => {
+                    self.pos += 1;
+                    let rhs = self.parse_term()?;
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                }
+                _ => return Some(lhs),
+            }
+        }
+    }
+
+    // term := factor (('*' | '/') factor)*
+    fn parse_term(&mut self) -> Option<Expr> {
+        let mut lhs = self.parse_factor()?;
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some(op) if op == '*' || op == '/' => {
+                    self.pos += 1;
+                    let rhs = self.parse_factor()?;
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                }
+                _ => return Some(lhs),
+            }
+        }
+    }
+
+    // factor := '-' factor | '(' expr ')' | number | function '(' args ')' | reference ['..' reference]
+    fn parse_factor(&mut self) -> Option<Expr> {
+        self.skip_space();
+        match self.peek()? {
+            '-' => {
+                self.pos += 1;
+                Some(Expr::Neg(Box::new(self.parse_factor()?)))
+            }
+            '(' => {
+                self.pos += 1;
+                let expr = self.parse_expr()?;
+                self.skip_space();
+                if self.eat(")") {
+                    Some(expr)
+                } else {
+                    None
+                }
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                self.take_while(|c| c.is_ascii_digit() || c == '.').parse().ok().map(Expr::Num)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric());
+                self.skip_space();
+                if !self.eat("(") {
+                    return None;
+                }
+                let mut args = vec![];
+                loop {
+                    self.skip_space();
+                    if self.eat(")") {
+                        break;
+                    }
+                    args.push(self.parse_expr()?);
+                    self.skip_space();
+                    if !self.eat(",") && self.peek() != Some(')') {
+                        return None;
+                    }
+                }
+                Some(Expr::Call(name.to_string(), args))
+            }
+            _ => {
+                let begin = self.parse_reference()?;
+                if self.eat("..") {
+                    let end = self.parse_reference()?;
+                    Some(Expr::Range(begin, end))
+                } else {
+                    Some(Expr::Ref(begin.0, begin.1))
+                }
+            }
+        }
+    }
+
+    // @2$3 @-1 $> @I
+    fn parse_reference(&mut self) -> Option<(Option<RowRef>, Option<ColRef>)> {
+        let row = if self.eat("@") {
+            Some(match self.peek()? {
+                '<' => {
+                    self.pos += 1;
+                    RowRef::First
+                }
+                '>' => {
+                    self.pos += 1;
+                    RowRef::Last
+                }
+                'I' => RowRef::Hline(self.take_while(|c| c == 'I').len()),
+                _ => match self.parse_offset()? {
+                    (true, n) => RowRef::Rel(n),
+                    (false, n) => RowRef::Abs(n),
+                },
+            })
+        } else {
+            None
+        };
+        let col = if self.eat("$") {
+            Some(match self.peek()? {
+                '<' => {
+                    self.pos += 1;
+                    ColRef::First
+                }
+                '>' => {
+                    self.pos += 1;
+                    ColRef::Last
+                }
+                _ => match self.parse_offset()? {
+                    (true, n) => ColRef::Rel(n),
+                    (false, n) => ColRef::Abs(n),
+                },
+            })
+        } else {
+            None
+        };
+        if row.is_none() && col.is_none() {
+            return None;
+        }
+        Some((row, col))
+    }
+
+    // 3 -> (false, 3), -1 -> (true, -1), +1 -> (true, 1)
+    fn parse_offset(&mut self) -> Option<(bool, i64)> {
+        let sign = match self.peek()? {
+            '-' => -1,
+            '+' => 1,
+            _ => 0,
+        };
+        if sign != 0 {
+            self.pos += 1;
+        }
+        let n: i64 = self.take_while(|c| c.is_ascii_digit()).parse().ok()?;
+        Some((sign != 0, if sign < 0 { -n } else { n }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &[&[&str]]) -> Vec<TableLine> {
+        rows.iter()
+            .map(|row| match row {
+                ["-"] => TableLine::Rule,
+                _ => TableLine::Row { cells: row.iter().map(|c| c.to_string()).collect(), special: false },
+            })
+            .collect()
+    }
+
+    fn cells(lines: &[TableLine]) -> Vec<Vec<String>> {
+        lines
+            .iter()
+            .filter_map(|line| match line {
+                TableLine::Row { cells, .. } => Some(cells.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_formulas() {
+        assert_eq!(
+            parse_formulas("$4=$2*$3::@>$4=vsum(@I..@II);%.2f"),
+            vec![
+                Formula { target: FormulaTarget::Column(ColRef::Abs(4)), expr: "$2*$3".to_string(), format: None },
+                Formula {
+                    target: FormulaTarget::Field(RowRef::Last, ColRef::Abs(4)),
+                    expr: "vsum(@I..@II)".to_string(),
+                    format: Some("%.2f".to_string()),
+                },
+            ]
+        );
+        assert_eq!(parse_formulas("not a formula"), vec![]);
+    }
+
+    #[test]
+    fn test_recalculate() {
+        let mut lines = table(&[
+            &["Item", "Price", "Qty", "Total"],
+            &["-"],
+            &["Apple", "1.5", "4", ""],
+            &["Pear", "2", "3", ""],
+            &["Fig", "0.25", "2", ""],
+            &["-"],
+            &["Sum", "", "", ""],
+            &["Mean", "", "", ""],
+        ]);
+        let formulas = parse_formulas(
+            "$4=$2*$3::@5$4=vsum(@I..@II)::@5$3=vsum(@2..@-1)::@>$2=vmean(@2$2..@4$2);%.2f::@>$4=(@-1 - $-2) / 3",
+        );
+        recalculate(&mut lines, &formulas);
+        assert_eq!(
+            cells(&lines),
+            vec![
+                vec!["Item", "Price", "Qty", "Total"],
+                vec!["Apple", "1.5", "4", "6"],
+                vec!["Pear", "2", "3", "6"],
+                vec!["Fig", "0.25", "2", "0.5"],
+                vec!["Sum", "", "9", "12.5"],
+                vec!["Mean", "1.25", "", "3.75"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recalculate_errors() {
+        let mut lines = table(&[&["a", "1", ""], &["2", "x", ""]]);
+        recalculate(&mut lines, &parse_formulas("$3=$2*2::@1$1=$9::@2$1=1/0"));
+        assert_eq!(cells(&lines), vec![vec!["#ERROR", "1", "2"], vec!["#ERROR", "x", "#ERROR"]]);
+
+        let mut lines = table(&[&["a", "1", ""], &["2", "x", ""]]);
+        recalculate(&mut lines, &parse_formulas("$3=$2*2;N"));
+        assert_eq!(cells(&lines), vec![vec!["a", "1", "2"], vec!["2", "x", "0"]]);
+
+        // 相対参照が桁あふれする場合も範囲外
+        let mut lines = table(&[&["1", "", ""]]);
+        let formulas = "@1$2=@+9223372036854775807$1::@1$3=@-9223372036854775807+$+9223372036854775807";
+        recalculate(&mut lines, &parse_formulas(formulas));
+        assert_eq!(cells(&lines), vec![vec!["1", "#ERROR", "#ERROR"]]);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::ast::*;
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1, take_while_m_n};
+use nom::character::complete::{char, digit1, one_of, space0, space1};
+use nom::combinator::{map, map_res, opt};
+use nom::error::{ErrorKind, ParseError};
+use nom::sequence::{preceded, tuple};
+use nom::{Err, IResult};
+
+/*
+ * タイムスタンプ
+ *   <2024-01-02 Tue 10:00-11:30 +1w -2d>   アクティブ (時刻の範囲、繰り返し、警告期間)
+ *   [2024-01-02 Tue]                       非アクティブ
+ *   <2024-01-02 Tue>--<2024-01-05 Fri>     日付の範囲
+ * 曜日の表記は検証しない
+ */
+pub fn parse_timestamp(s: &str) -> IResult<&str, TimestampMeta> {
+    let (remain, start) = parse_single_timestamp(s)?;
+    if let Ok((r, end)) = preceded(tag("--"), parse_single_timestamp)(remain) {
+        if end.kind == start.kind {
+            let kind = match start.kind {
+                TimestampKind::Active => TimestampKind::ActiveRange,
+                _ => TimestampKind::InactiveRange,
+            };
+            let meta = TimestampMeta { kind, end_date: Some(end.date), end_time: end.time, ..start };
+            return Ok((r, meta));
+        }
+    }
+    Ok((remain, start))
+}
+
+fn parse_single_timestamp(s: &str) -> IResult<&str, TimestampMeta> {
+    let (rest, open) = one_of("<[")(s)?;
+    let (kind, close) = match open {
+        '<' => (TimestampKind::Active, '>'),
+        _ => (TimestampKind::Inactive, ']'),
+    };
+    let (rest, date) = parse_date(rest)?;
+    let (rest, _) = opt(preceded(space1, take_while1(|c: char| c.is_alphabetic() || c == '.')))(rest)?;
+    let (rest, time) = opt(preceded(space1, tuple((parse_time, opt(preceded(char('-'), parse_time))))))(rest)?;
+
+    let mut meta = TimestampMeta {
+        kind,
+        date,
+        time: time.map(|(start, _)| start),
+        end_date: None,
+        end_time: None,
+        repeater: None,
+        delay: None,
+    };
+    if let Some((_, Some(end_time))) = time {
+        meta.kind = match kind {
+            TimestampKind::Active => TimestampKind::ActiveRange,
+            _ => TimestampKind::InactiveRange,
+        };
+        meta.end_date = Some(date);
+        meta.end_time = Some(end_time);
+    }
+
+    // 繰り返しと警告期間はどちらが先でもよい
+    let mut rest = rest;
+    loop {
+        if let Ok((r, repeater)) = preceded(space1, parse_repeater)(rest) {
+            if meta.repeater.is_none() {
+                meta.repeater = Some(repeater);
+                rest = r;
+                continue;
+            }
+        }
+        if let Ok((r, delay)) = preceded(space1, parse_delay)(rest) {
+            if meta.delay.is_none() {
+                meta.delay = Some(delay);
+                rest = r;
+                continue;
+            }
+        }
+        break;
+    }
+    let (remain, _) = preceded(space0, char(close))(rest)?;
+    Ok((remain, meta))
+}
+
+// 2024-01-02
+fn parse_date(s: &str) -> IResult<&str, OrgDate> {
+    let (remain, (year, _, month, _, day)) = tuple((
+        map_res(take_while_m_n(4, 4, |c: char| c.is_ascii_digit()), str::parse::<i32>),
+        char('-'),
+        map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_digit()), str::parse::<u32>),
+        char('-'),
+        map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_digit()), str::parse::<u32>),
+    ))(s)?;
+    match OrgDate::new(year, month, day) {
+        Some(date) => Ok((remain, date)),
+        None => Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Verify))),
+    }
+}
+
+// 9:05, 10:00
+fn parse_time(s: &str) -> IResult<&str, OrgTime> {
+    let (remain, (hour, _, minute)) = tuple((
+        map_res(take_while_m_n(1, 2, |c: char| c.is_ascii_digit()), str::parse::<u32>),
+        char(':'),
+        map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_digit()), str::parse::<u32>),
+    ))(s)?;
+    if hour > 24 || minute > 59 {
+        return Err(Err::Error(ParseError::from_error_kind(s, ErrorKind::Verify)));
+    }
+    Ok((remain, OrgTime { hour, minute }))
+}
+
+// +1w ++1w .+1w
+fn parse_repeater(s: &str) -> IResult<&str, Repeater> {
+    let (remain, (kind, (value, unit))) = tuple((
+        alt((
+            map(tag("++"), |_| RepeaterKind::CatchUp),
+            map(tag(".+"), |_| RepeaterKind::Restart),
+            map(tag("+"), |_| RepeaterKind::Cumulate),
+        )),
+        parse_interval,
+    ))(s)?;
+    Ok((remain, Repeater { kind, value, unit }))
+}
+
+// -2d --2d
+fn parse_delay(s: &str) -> IResult<&str, Delay> {
+    let (remain, (kind, (value, unit))) = tuple((
+        alt((map(tag("--"), |_| DelayKind::First), map(tag("-"), |_| DelayKind::All))),
+        parse_interval,
+    ))(s)?;
+    Ok((remain, Delay { kind, value, unit }))
+}
+
+fn parse_interval(s: &str) -> IResult<&str, (u32, TimeUnit)> {
+    tuple((
+        map_res(digit1, str::parse::<u32>),
+        map(one_of("hdwmy"), |c| match c {
+            'h' => TimeUnit::Hour,
+            'd' => TimeUnit::Day,
+            'w' => TimeUnit::Week,
+            'm' => TimeUnit::Month,
+            _ => TimeUnit::Year,
+        }),
+    ))(s)
+}
+
+/*
+ * 日付の計算
+ * 日数は 1970-01-01 からの通日 (グレゴリオ暦)
+ */
+impl OrgDate {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+            Some(OrgDate { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    pub fn to_days(&self) -> i64 {
+        let y = if self.month <= 2 { self.year as i64 - 1 } else { self.year as i64 };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let mp = (self.month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400) as i32 + if month <= 2 { 1 } else { 0 };
+        OrgDate { year, month, day }
+    }
+
+    // 0: 月曜日 ... 6: 日曜日
+    pub fn weekday(&self) -> u32 {
+        (self.to_days() + 3).rem_euclid(7) as u32
+    }
+
+    pub fn add_days(&self, n: i64) -> Self {
+        OrgDate::from_days(self.to_days() + n)
+    }
+
+    // 月末を超える日は月末にする (1-31 +1m -> 2-29)
+    pub fn add_months(&self, n: i64) -> Self {
+        let months = self.year as i64 * 12 + self.month as i64 - 1 + n;
+        let year = months.div_euclid(12) as i32;
+        let month = months.rem_euclid(12) as u32 + 1;
+        OrgDate { year, month, day: self.day.min(days_in_month(year, month)) }
+    }
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> OrgDate {
+        OrgDate::new(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let (remain, meta) = parse_timestamp("<2024-01-02 Tue 10:00-11:30 +1w -2d> rest").unwrap();
+        assert_eq!(remain, " rest");
+        assert_eq!(
+            meta,
+            TimestampMeta {
+                kind: TimestampKind::ActiveRange,
+                date: date(2024, 1, 2),
+                time: Some(OrgTime { hour: 10, minute: 0 }),
+                end_date: Some(date(2024, 1, 2)),
+                end_time: Some(OrgTime { hour: 11, minute: 30 }),
+                repeater: Some(Repeater { kind: RepeaterKind::Cumulate, value: 1, unit: TimeUnit::Week }),
+                delay: Some(Delay { kind: DelayKind::All, value: 2, unit: TimeUnit::Day }),
+            }
+        );
+
+        let meta = parse_timestamp("[2024-01-02 Tue 9:05]--[2024-01-03 Wed 10:00]").unwrap().1;
+        assert_eq!(meta.kind, TimestampKind::InactiveRange);
+        assert_eq!(meta.time, Some(OrgTime { hour: 9, minute: 5 }));
+        assert_eq!((meta.end_date, meta.end_time), (Some(date(2024, 1, 3)), Some(OrgTime { hour: 10, minute: 0 })));
+
+        let meta = parse_timestamp("<2024-02-29 --3d .+1m>").unwrap().1;
+        assert_eq!(meta.kind, TimestampKind::Active);
+        assert_eq!(meta.repeater.unwrap().kind, RepeaterKind::Restart);
+        assert_eq!(meta.delay.unwrap().kind, DelayKind::First);
+
+        // 種類の異なる範囲は繋がない
+        let (remain, meta) = parse_timestamp("<2024-01-02>--[2024-01-03]").unwrap();
+        assert_eq!((remain, meta.kind), ("--[2024-01-03]", TimestampKind::Active));
+
+        assert!(parse_timestamp("<2023-02-29>").is_err());
+        assert!(parse_timestamp("<2024-01-02 25:00>").is_err());
+        assert!(parse_timestamp("<2024-01-02 Tue").is_err());
+    }
+
+    #[test]
+    fn test_date_arithmetic() {
+        assert_eq!(date(1970, 1, 1).to_days(), 0);
+        assert_eq!(OrgDate::from_days(19724), date(2024, 1, 2));
+        assert_eq!(date(2024, 1, 2).weekday(), 1);
+        assert_eq!(date(2024, 2, 28).add_days(2), date(2024, 3, 1));
+        assert_eq!(date(2024, 1, 31).add_months(1), date(2024, 2, 29));
+        assert_eq!(date(2024, 11, 15).add_months(3), date(2025, 2, 15));
+        assert_eq!(date(1969, 12, 31).add_days(-365), date(1968, 12, 31));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::arena::*;
+use crate::ast::*;
+
+/*
+ * 見出しを節(Section)に入れ子にする
+ *
+ * 見出しは、同じかそれより浅いレベルの見出しが現れるまでの要素と下位の見出しを持つ。
+ *   <document>
+ *     (最初の見出しより前の要素)
+ *     <section> <見出し> (要素...) <section>...</section> </section>
+ *   </document>
+ *
+ * Org では常に適用し、Markdown では任意で適用する。
+ */
+pub fn sectionize(document: &mut ASTArena) {
+    let root = document.root();
+    let mut stack: Vec<(NodeId, usize)> = vec![]; // (節, レベル)
+
+    for child in document.take_children(root) {
+        if let Some(level) = document.node(child).heading_level() {
+            close_sections(document, &mut stack, level);
+            let range = *document.node(child).range();
+            let section = document.new_node(ASTElm::new_section(level, range));
+            document.append_child(stack.last().map_or(root, |(parent, _)| *parent), section);
+            document.append_child(section, child);
+            stack.push((section, level));
+        } else {
+            match stack.last() {
+                Some(&(section, _)) => append_to_section(document, section, child),
+                None => document.append_child(root, child),
+            }
+        }
+    }
+    close_sections(document, &mut stack, 0);
+}
+
+// level 以上の深さの節を閉じる (親の節の範囲を閉じた節の終わりまで広げる)
+fn close_sections(document: &mut ASTArena, stack: &mut Vec<(NodeId, usize)>, level: usize) {
+    while let Some(&(section, section_level)) = stack.last() {
+        if section_level < level {
+            break;
+        }
+        stack.pop();
+        if let Some(&(parent, _)) = stack.last() {
+            document.data_mut(parent).range.end = document.node(section).range().end;
+        }
+    }
+}
+
+fn append_to_section(document: &mut ASTArena, section: NodeId, child: NodeId) {
+    document.data_mut(section).range.end = document.node(child).range().end;
+    document.append_child(section, child);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md_parser::md_parse;
+
+    #[test]
+    fn test_sectionize_markdown() {
+        let mut node = md_parse(
+            "intro\n\n# one\n\nbody\n\n## sub\n\n### deep\n\n# two\n\nend",
+            ASTNode::new(ASTElm::new_document()),
+        );
+        sectionize(&mut node);
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><paragraph><text>intro</text></paragraph>\
+             <section><header><text>one</text></header><paragraph><text>body</text></paragraph>\
+             <section><header><text>sub</text></header>\
+             <section><header><text>deep</text></header></section></section></section>\
+             <section><header><text>two</text></header><paragraph><text>end</text></paragraph></section></document>"
+        );
+    }
+
+    #[test]
+    fn test_sectionize_skipped_level() {
+        let mut node = md_parse("### three\n\n# one\n\n### three", ASTNode::new(ASTElm::new_document()));
+        sectionize(&mut node);
+        assert_eq!(
+            node.render_debug_format(),
+            "<document><section><header><text>three</text></header></section>\
+             <section><header><text>one</text></header>\
+             <section><header><text>three</text></header></section></section></document>"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::arena::{ASTArena, NodeId, NodeMut, NodeRef};
+use crate::ast::*;
+
+/*
+ * 木を辿る (Visitor)
+ *
+ * walk は要素に入るときに enter、子を辿った後に leave を呼ぶ。
+ * enter / leave の既定の実装は要素の種類ごとのフック (enter_headline, leave_headline など) を呼ぶので、
+ * 必要な種類のフックだけを書けばよい。
+ *
+ *   struct Titles(Vec<String>);
+ *   impl Visitor for Titles {
+ *       fn enter_headline(&mut self, node: NodeRef) -> Visit {
+ *           self.0.push(node.value().clone());
+ *           Visit::Continue
+ *       }
+ *   }
+ *   walk(document.root_node(), &mut Titles(vec![]));
+ *
+ * フックに渡す NodeRef からは親・祖先を辿れる (node.parent(), node.ancestors())。
+ * VisitorMut のフックでは node.node() で同じように辿れる。
+ */
+
+// enter の戻り値: 続けて辿るかどうか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    Continue,
+    SkipChildren, // 子を辿らない (leave は呼ぶ)
+    Stop,         // 辿るのをやめる (leave も呼ばない)
+}
+
+// 要素の種類とフックの対応
+macro_rules! with_hooks {
+    ($callback:ident ! ( $($args:tt)* )) => {
+        $callback! { $($args)*
+            Document => enter_document, leave_document;
+            Paragraph => enter_paragraph, leave_paragraph;
+            Headers => enter_headers, leave_headers;
+            Text => enter_text, leave_text;
+            Emphasis => enter_emphasis, leave_emphasis;
+            Strong => enter_strong, leave_strong;
+            Underline => enter_underline, leave_underline;
+            Verbatim => enter_verbatim, leave_verbatim;
+            Code => enter_code, leave_code;
+            Strikethrough => enter_strikethrough, leave_strikethrough;
+            Highlight => enter_highlight, leave_highlight;
+            Superscript => enter_superscript, leave_superscript;
+            Subscript => enter_subscript, leave_subscript;
+            Emoji => enter_emoji, leave_emoji;
+            SoftBreak => enter_soft_break, leave_soft_break;
+            HardBreak => enter_hard_break, leave_hard_break;
+            Headline => enter_headline, leave_headline;
+            Section => enter_section, leave_section;
+            Keyword => enter_keyword, leave_keyword;
+            Link => enter_link, leave_link;
+            Target => enter_target, leave_target;
+            RadioTarget => enter_radio_target, leave_radio_target;
+            FootnoteReference => enter_footnote_reference, leave_footnote_reference;
+            FootnoteDefinition => enter_footnote_definition, leave_footnote_definition;
+            PropertyDrawer => enter_property_drawer, leave_property_drawer;
+            NodeProperty => enter_node_property, leave_node_property;
+            Drawer => enter_drawer, leave_drawer;
+            Clock => enter_clock, leave_clock;
+            Timestamp => enter_timestamp, leave_timestamp;
+            Planning => enter_planning, leave_planning;
+            Table => enter_table, leave_table;
+            TableRow => enter_table_row, leave_table_row;
+            TableCell => enter_table_cell, leave_table_cell;
+            PlainList => enter_plain_list, leave_plain_list;
+            Item => enter_item, leave_item;
+            ItemTag => enter_item_tag, leave_item_tag;
+            StatisticsCookie => enter_statistics_cookie, leave_statistics_cookie;
+            SrcBlock => enter_src_block, leave_src_block;
+            ExampleBlock => enter_example_block, leave_example_block;
+            ExportBlock => enter_export_block, leave_export_block;
+            CommentBlock => enter_comment_block, leave_comment_block;
+            VerseBlock => enter_verse_block, leave_verse_block;
+            QuoteBlock => enter_quote_block, leave_quote_block;
+            CenterBlock => enter_center_block, leave_center_block;
+            SpecialBlock => enter_special_block, leave_special_block;
+            LatexEnvironment => enter_latex_environment, leave_latex_environment;
+            LatexFragment => enter_latex_fragment, leave_latex_fragment;
+            Entity => enter_entity, leave_entity;
+            Macro => enter_macro, leave_macro;
+            Include => enter_include, leave_include;
+            BabelCall => enter_babel_call, leave_babel_call;
+            InlineBabelCall => enter_inline_babel_call, leave_inline_babel_call;
+            InlineSrcBlock => enter_inline_src_block, leave_inline_src_block;
+            FixedWidth => enter_fixed_width, leave_fixed_width;
+            Comment => enter_comment, leave_comment;
+            HorizontalRule => enter_horizontal_rule, leave_horizontal_rule;
+            Inlinetask => enter_inlinetask, leave_inlinetask;
+            DynamicBlock => enter_dynamic_block, leave_dynamic_block;
+        }
+    };
+}
+
+macro_rules! visitor_trait {
+    ($trait_name:ident, $node:ty; $( $elm_type:ident => $enter:ident, $leave:ident; )*) => {
+        pub trait $trait_name {
+            fn enter(&mut self, node: $node) -> Visit {
+                match node.node_type().clone() {
+                    $( ASTType::$elm_type => self.$enter(node), )*
+                }
+            }
+
+            fn leave(&mut self, node: $node) {
+                match node.node_type().clone() {
+                    $( ASTType::$elm_type => self.$leave(node), )*
+                }
+            }
+
+            $(
+                fn $enter(&mut self, _node: $node) -> Visit {
+                    Visit::Continue
+                }
+
+                fn $leave(&mut self, _node: $node) {}
+            )*
+        }
+    };
+}
+
+with_hooks!(visitor_trait!(Visitor, NodeRef<'_>;));
+
+// 辿りながら要素を書き換える (enter で子を足したり消したりしてもよい)
+with_hooks!(visitor_trait!(VisitorMut, &mut NodeMut<'_>;));
+
+// Stop で止まった場合は Visit::Stop を返す
+pub fn walk<V: Visitor + ?Sized>(node: NodeRef, visitor: &mut V) -> Visit {
+    match visitor.enter(node) {
+        Visit::Stop => return Visit::Stop,
+        Visit::SkipChildren => {}
+        Visit::Continue => {
+            for child in node.children() {
+                if walk(child, visitor) == Visit::Stop {
+                    return Visit::Stop;
+                }
+            }
+        }
+    }
+    visitor.leave(node);
+    Visit::Continue
+}
+
+// id から下を辿る。子を辿る間に要素が切り離されても、その時点の次の兄弟から続ける
+pub fn walk_mut<V: VisitorMut + ?Sized>(document: &mut ASTArena, id: NodeId, visitor: &mut V) -> Visit {
+    match visitor.enter(&mut NodeMut::new(document, id)) {
+        Visit::Stop => return Visit::Stop,
+        Visit::SkipChildren => {}
+        Visit::Continue => {
+            let mut child = document.get(id).first_child();
+            while let Some(current) = child {
+                let next = document.get(current).next_sibling();
+                if walk_mut(document, current, visitor) == Visit::Stop {
+                    return Visit::Stop;
+                }
+                child = match document.get(current).parent() {
+                    Some(parent) if parent == id => document.get(current).next_sibling(),
+                    _ => next,
+                };
+            }
+        }
+    }
+    visitor.leave(&mut NodeMut::new(document, id));
+    Visit::Continue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md_parser::md_parse;
+    use crate::org_parser::org_parse;
+
+    const ORG: &str = "* One\n\
+                       text /em/\n\
+                       ** Two :tag:\n\
+                       * Three\n";
+
+    // enter と leave の順を記録する
+    struct Events(Vec<String>);
+
+    impl Visitor for Events {
+        fn enter_headline(&mut self, node: NodeRef) -> Visit {
+            self.0.push(format!("enter {}", node.value()));
+            Visit::Continue
+        }
+
+        fn leave_section(&mut self, node: NodeRef) {
+            let title = node.first_child().unwrap().value();
+            self.0.push(format!("leave {}", title));
+        }
+
+        // 段落の中は辿らない
+        fn enter_paragraph(&mut self, _node: NodeRef) -> Visit {
+            self.0.push("paragraph".to_string());
+            Visit::SkipChildren
+        }
+
+        fn enter_emphasis(&mut self, _node: NodeRef) -> Visit {
+            self.0.push("emphasis".to_string());
+            Visit::Continue
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let document = org_parse(ORG, ASTNode::new(ASTElm::new_document()));
+        let mut events = Events(vec![]);
+        assert_eq!(walk(document.root_node(), &mut events), Visit::Continue);
+        assert_eq!(
+            events.0,
+            vec!["enter One", "paragraph", "enter Two", "leave Two", "leave One", "enter Three", "leave Three"]
+        );
+    }
+
+    // 見出しの上位の見出しを祖先から集める
+    struct Paths(Vec<String>);
+
+    impl Visitor for Paths {
+        fn enter_headline(&mut self, node: NodeRef) -> Visit {
+            let mut titles: Vec<&str> = node
+                .ancestors()
+                .filter(|ancestor| *ancestor.node_type() == ASTType::Section)
+                .filter_map(|section| section.first_child())
+                .map(|headline| headline.value().as_str())
+                .collect();
+            titles.reverse();
+            self.0.push(titles.join("/"));
+            Visit::Continue
+        }
+    }
+
+    #[test]
+    fn test_visitor_ancestors() {
+        let document = org_parse(ORG, ASTNode::new(ASTElm::new_document()));
+        let mut paths = Paths(vec![]);
+        walk(document.root_node(), &mut paths);
+        assert_eq!(paths.0, vec!["One", "One/Two", "Three"]);
+    }
+
+    struct FirstText(Option<String>);
+
+    impl Visitor for FirstText {
+        fn enter(&mut self, node: NodeRef) -> Visit {
+            if *node.node_type() == ASTType::Text {
+                self.0 = Some(node.value().clone());
+                return Visit::Stop;
+            }
+            Visit::Continue
+        }
+
+        fn leave(&mut self, _node: NodeRef) {
+            panic!("leave after stop");
+        }
+    }
+
+    #[test]
+    fn test_visitor_stop() {
+        let document = org_parse(ORG, ASTNode::new(ASTElm::new_document()));
+        let mut first = FirstText(None);
+        assert_eq!(walk(document.root_node(), &mut first), Visit::Stop);
+        assert_eq!(first.0.as_deref(), Some("One"));
+    }
+
+    struct Upper;
+
+    impl VisitorMut for Upper {
+        fn enter_text(&mut self, node: &mut NodeMut) -> Visit {
+            let value = node.value().to_uppercase();
+            node.set_value(value);
+            Visit::Continue
+        }
+    }
+
+    // 強調の中のテキストだけを書き換える
+    struct UpperEmphasis;
+
+    impl VisitorMut for UpperEmphasis {
+        fn enter_text(&mut self, node: &mut NodeMut) -> Visit {
+            if node.node().ancestors().any(|ancestor| *ancestor.node_type() == ASTType::Emphasis) {
+                let value = node.value().to_uppercase();
+                node.set_value(value);
+            }
+            Visit::Continue
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut document = md_parse("# Title\n\nSome *emphasis*", ASTNode::new(ASTElm::new_document()));
+        let root = document.root();
+        walk_mut(&mut document, root, &mut Upper);
+        assert_eq!(
+            document.render_debug_format(),
+            "<document><header><text>TITLE</text></header>\
+             <paragraph><text>SOME </text><emphasis><text>EMPHASIS</text></emphasis></paragraph></document>"
+        );
+
+        let mut document = md_parse("Some *emphasis*", ASTNode::new(ASTElm::new_document()));
+        let root = document.root();
+        walk_mut(&mut document, root, &mut UpperEmphasis);
+        assert_eq!(
+            document.render_debug_format(),
+            "<document><paragraph><text>Some </text><emphasis><text>EMPHASIS</text></emphasis></paragraph></document>"
+        );
+    }
+
+    #[test]
+    fn test_traversal_order() {
+        let document = md_parse("# Title\n\nSome *emphasis*", ASTNode::new(ASTElm::new_document()));
+        let types = |nodes: Vec<NodeRef>| nodes.iter().map(|node| node.node_type().clone()).collect::<Vec<_>>();
+        let root = document.root_node();
+        use ASTType::*;
+        assert_eq!(
+            types(root.pre_order().collect()),
+            vec![Document, Headers, Text, Paragraph, Text, Emphasis, Text]
+        );
+        assert_eq!(
+            types(root.post_order().collect()),
+            vec![Text, Headers, Text, Text, Emphasis, Paragraph, Document]
+        );
+        assert_eq!(types(root.descendants().collect()).len(), 6);
+        assert_eq!(types(root.children().collect()), vec![Headers, Paragraph]);
+        assert_eq!((&root.to_node()).into_iter().count(), 7);
+    }
+}
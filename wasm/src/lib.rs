@@ -1,11 +1,21 @@
 extern crate wasm_bindgen;
 
 // for web browser
+use parser::arena::ASTArena;
 use parser::ast::*;
-use parser::md_parser::md_parse;
+use parser::convert::{render_markdown, to_markdown, to_org, MarkdownOptions};
+use parser::lossless::{md_parse_lossless, org_parse_lossless};
+use parser::md_parser::{md_parse, md_parse_with_options, MdParseOptions};
+use parser::org_agenda::{agenda, render_agenda, AgendaDay, AgendaOptions};
+use parser::org_clock::{clock_report, ClockFilter};
+use parser::org_element::render_org_element;
+use parser::org_parser::{org_parse, org_parse_with_loader};
+use parser::org_timestamp::parse_timestamp;
 
 use wasm_bindgen::prelude::*;
 
+use std::collections::BTreeMap;
+
 // for other webassembly env
 use std::ffi::{CStr, CString};
 use std::mem;
@@ -13,12 +23,112 @@ use std::os::raw::{c_char, c_void};
 
 #[wasm_bindgen]
 pub fn parse_markdown(source: &str) -> String {
-    let mut node = ASTNode::new(ASTElm {
+    let node = ASTNode::new(ASTElm {
+        ..Default::default()
+    });
+    let document = md_parse(source, node);
+    serde_json::to_string(&document).unwrap()
+}
+
+// extensions: 有効にする拡張構文 {"strikethrough": true, "emoji": true} (JSON, 読めなければ例外にする)
+#[wasm_bindgen]
+pub fn parse_markdown_with_extensions(source: &str, extensions: &str) -> Result<String, JsValue> {
+    let options: MdParseOptions = serde_json::from_str(extensions)
+        .map_err(|error| JsValue::from_str(&format!("extensions is not a valid JSON object: {}", error)))?;
+    let node = md_parse_with_options(source, ASTNode::new(ASTElm::new_document()), options);
+    Ok(serde_json::to_string(&node).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn parse_org(source: &str) -> String {
+    let node = ASTNode::new(ASTElm {
+        ..Default::default()
+    });
+    let document = org_parse(source, node);
+    serde_json::to_string(&document).unwrap()
+}
+
+// 空白・記号も trivia として持つ木 (エディタでの部分的な書き換え用)
+#[wasm_bindgen]
+pub fn parse_markdown_lossless(source: &str) -> String {
+    let node = md_parse_lossless(source, ASTNode::new(ASTElm::new_document()));
+    serde_json::to_string(&node).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn parse_org_lossless(source: &str) -> String {
+    let node = org_parse_lossless(source, ASTNode::new(ASTElm::new_document()));
+    serde_json::to_string(&node).unwrap()
+}
+
+// 変換結果と警告 {"text": "...", "warnings": [...]} (JSON)
+#[wasm_bindgen]
+pub fn markdown_to_org(source: &str) -> String {
+    let node = md_parse(source, ASTNode::new(ASTElm::new_document()));
+    serde_json::to_string(&to_org(node.root_node())).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn org_to_markdown(source: &str) -> String {
+    let node = org_parse(source, ASTNode::new(ASTElm::new_document()));
+    serde_json::to_string(&to_markdown(node.root_node())).unwrap()
+}
+
+// Markdown を整形し直す (width: 折り返す幅, 0 なら折り返さない)
+#[wasm_bindgen]
+pub fn format_markdown(source: &str, width: u32) -> String {
+    let node = md_parse(source, ASTNode::new(ASTElm::new_document()));
+    let width = if width == 0 { None } else { Some(width as usize) };
+    render_markdown(node.root_node(), &MarkdownOptions { width })
+}
+
+// files: #+INCLUDE: で読み込むファイルの内容 {"file.org": "..."} (JSON)
+// files が JSON として読めなければ例外にする (読み込めない #+INCLUDE: と区別できるように)
+#[wasm_bindgen]
+pub fn parse_org_with_files(source: &str, files: &str) -> Result<String, JsValue> {
+    let files: BTreeMap<String, String> = serde_json::from_str(files)
+        .map_err(|error| JsValue::from_str(&format!("files is not a JSON object of strings: {}", error)))?;
+    let node = ASTNode::new(ASTElm {
         ..Default::default()
     });
-    node = md_parse(source, node);
-    let serialized = serde_json::to_string(&node).unwrap();
-    serialized
+    let document = org_parse_with_loader(source, node, &files);
+    Ok(serde_json::to_string(&document).unwrap())
+}
+
+// Emacs の org-element-parse-buffer と同じ形の S 式
+#[wasm_bindgen]
+pub fn org_element_sexp(source: &str) -> String {
+    render_org_element(org_parse(source, ASTNode::new(ASTElm::new_document())).root_node())
+}
+
+// parameters: clocktable と同じ引数 (:maxlevel 2 :tstart "<2024-01-01>" :tend "<2024-01-07>")
+#[wasm_bindgen]
+pub fn org_clock_report(source: &str, parameters: &str) -> String {
+    let node = org_parse(source, ASTNode::new(ASTElm::new_document()));
+    let report = clock_report(node.root_node(), &ClockFilter::from_parameters(parameters));
+    serde_json::to_string(&report).unwrap()
+}
+
+// sources: Org 文書の JSON 配列, start: "2024-01-01", span: 日数 (start を今日とする)
+#[wasm_bindgen]
+pub fn org_agenda(sources: &str, start: &str, span: u32) -> String {
+    serde_json::to_string(&build_agenda(sources, start, span)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn render_org_agenda(sources: &str, start: &str, span: u32) -> String {
+    render_agenda(&build_agenda(sources, start, span))
+}
+
+fn build_agenda(sources: &str, start: &str, span: u32) -> Vec<AgendaDay> {
+    let sources: Vec<String> = serde_json::from_str(sources).unwrap_or_default();
+    let start = match parse_timestamp(&format!("<{}>", start)) {
+        Ok((_, timestamp)) => timestamp.date,
+        Err(_) => return vec![],
+    };
+    let documents: Vec<ASTArena> =
+        sources.iter().map(|source| org_parse(source, ASTNode::new(ASTElm::new_document()))).collect();
+    agenda(&documents, &AgendaOptions::new(start, span))
 }
 
 // for other webassembly env
@@ -32,21 +142,23 @@ pub extern "C" fn allocate(size: usize) -> *mut c_void {
     pointer as *mut c_void
 }
 
+/// # Safety
+/// ptr と capacity は allocate が返したものでなければならない
 #[no_mangle]
-pub extern "C" fn deallocate(ptr: *mut c_void, capacity: usize) {
-    unsafe {
-        let _ = Vec::from_raw_parts(ptr, 0, capacity);
-    }
+pub unsafe extern "C" fn deallocate(ptr: *mut c_void, capacity: usize) {
+    let _ = Vec::from_raw_parts(ptr, 0, capacity);
 }
 
+/// # Safety
+/// ptr は ffi_parse_* が返したものでなければならない
 #[no_mangle]
-pub extern "C" fn deallocate_str( ptr: *mut c_char ) {
+pub unsafe extern "C" fn deallocate_str( ptr: *mut c_char ) {
     // retake pointer to free memory
-    unsafe { let _ = CString::from_raw( ptr ); }
+    let _ = CString::from_raw( ptr );
 }
 
 fn string_safe(str_ptr: *mut c_char) -> String {
-    unsafe { CStr::from_ptr(str_ptr).to_string_lossy().to_owned().to_string() }
+    unsafe { CStr::from_ptr(str_ptr).to_string_lossy().to_string() }
 }
 
 #[no_mangle]
@@ -55,3 +167,10 @@ pub extern "C" fn ffi_parse_markdown(source_ptr: *mut c_char) -> *mut c_char {
     let result = parse_markdown( &source );
     CString::new( result ).expect("CString::new failed").into_raw()
 }
+
+#[no_mangle]
+pub extern "C" fn ffi_parse_org(source_ptr: *mut c_char) -> *mut c_char {
+    let source = string_safe( source_ptr );
+    let result = parse_org( &source );
+    CString::new( result ).expect("CString::new failed").into_raw()
+}